@@ -2,6 +2,7 @@ mod error;
 mod paths;
 mod plugins;
 mod run;
+mod status;
 mod test;
 
 use apibara_observability::init_opentelemetry;
@@ -28,6 +29,11 @@ enum Command {
     Plugins(plugins::PluginsArgs),
     /// Test an indexer script.
     Test(test::TestArgs),
+    /// Print the status of a DNA stream.
+    Status(status::StatusArgs),
+    /// Run and manage the Starknet DNA node.
+    #[command(subcommand)]
+    StarknetNode(apibara_starknet::cli::Command),
 }
 
 #[tokio::main]
@@ -41,5 +47,9 @@ async fn main() -> Result<(), CliError> {
         Command::Run(args) => run::run(args).await,
         Command::Plugins(args) => plugins::run(args).await,
         Command::Test(args) => test::run(args).await,
+        Command::Status(args) => status::run(args).await,
+        Command::StarknetNode(command) => apibara_starknet::cli::run(command)
+            .await
+            .change_context(CliError),
     }
 }
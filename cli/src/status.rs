@@ -0,0 +1,79 @@
+use apibara_core::node::v1alpha2::{Cursor, StatusResponse};
+use apibara_sdk::{ClientBuilder, Uri};
+use clap::Args;
+use error_stack::{Result, ResultExt};
+use serde_json::{json, Value};
+
+use crate::error::CliError;
+
+#[derive(Args, Debug)]
+pub struct StatusArgs {
+    /// DNA stream url. If starting with `https://`, use a secure connection.
+    stream_url: String,
+    /// Use the authorization token when connecting to the stream.
+    #[arg(long, short = 'A', env)]
+    auth_token: Option<String>,
+    /// Print the status as json.
+    #[arg(long)]
+    json: bool,
+}
+
+pub async fn run(args: StatusArgs) -> Result<(), CliError> {
+    let stream_url = args
+        .stream_url
+        .parse::<Uri>()
+        .change_context(CliError)
+        .attach_printable("failed to parse stream url")?;
+
+    let status = ClientBuilder::default()
+        .with_bearer_token(args.auth_token)
+        .connect(stream_url)
+        .await
+        .change_context(CliError)
+        .attach_printable("failed to connect to the stream")?
+        .status()
+        .await
+        .change_context(CliError)
+        .attach_printable("failed to fetch the stream status")?;
+
+    if args.json {
+        println!("{}", status_to_json(&status));
+    } else {
+        print_status(&status);
+    }
+
+    Ok(())
+}
+
+fn print_status(status: &StatusResponse) {
+    let rows = [
+        ("head", status.current_head.as_ref()),
+        ("last ingested", status.last_ingested.as_ref()),
+        ("finalized", status.finalized.as_ref()),
+        ("earliest available", status.earliest_available.as_ref()),
+    ];
+
+    for (name, cursor) in rows {
+        let value = match cursor {
+            None => "-".to_string(),
+            Some(cursor) => cursor.to_canonical_string(),
+        };
+        println!("{:<20} {}", format!("{name}:"), value);
+    }
+}
+
+fn status_to_json(status: &StatusResponse) -> Value {
+    json!({
+        "head": status.current_head.as_ref().map(cursor_to_json),
+        "lastIngested": status.last_ingested.as_ref().map(cursor_to_json),
+        "finalized": status.finalized.as_ref().map(cursor_to_json),
+        "earliestAvailable": status.earliest_available.as_ref().map(cursor_to_json),
+    })
+}
+
+fn cursor_to_json(cursor: &Cursor) -> Value {
+    json!({
+        "orderKey": cursor.order_key,
+        "uniqueKey": format!("0x{}", hex::encode(&cursor.unique_key)),
+    })
+}
@@ -145,6 +145,12 @@ impl SnapshotGenerator {
                                 DataMessage::Invalidate { cursor } => {
                                     debug!("Ignoring invalidate: {:?}", cursor);
                                 }
+                                DataMessage::Finalize { cursor } => {
+                                    debug!("Ignoring finalize: {:?}", cursor);
+                                }
+                                DataMessage::DecodeError { end_cursor, .. } => {
+                                    debug!("Ignoring decode error: {:?}", end_cursor);
+                                }
                                 DataMessage::Heartbeat => {
                                     debug!("Ignoring heartbeat");
                                 }
@@ -1,5 +1,8 @@
 pub mod v1alpha2 {
-    use std::fmt::{self, Display};
+    use std::{
+        cmp::Ordering,
+        fmt::{self, Display},
+    };
 
     use serde::{
         de::{self, Deserialize, Deserializer, Visitor},
@@ -16,6 +19,67 @@ pub mod v1alpha2 {
         NODE_DESCRIPTOR_SET
     }
 
+    impl Cursor {
+        pub fn new(order_key: u64, unique_key: Vec<u8>) -> Self {
+            Cursor {
+                order_key,
+                unique_key,
+            }
+        }
+
+        /// Compares the position of two cursors in the stream.
+        ///
+        /// Cursors are ordered by their order key first and unique key second,
+        /// so that cursors on different branches at the same height are never equal.
+        pub fn cmp_position(&self, other: &Cursor) -> Ordering {
+            self.order_key
+                .cmp(&other.order_key)
+                .then_with(|| self.unique_key.cmp(&other.unique_key))
+        }
+
+        /// Returns true if the cursor comes strictly before `other`, according
+        /// to [Cursor::cmp_position].
+        pub fn is_before(&self, other: &Cursor) -> bool {
+            self.cmp_position(other) == Ordering::Less
+        }
+
+        /// Returns true if the cursor comes strictly after `other`, according
+        /// to [Cursor::cmp_position].
+        pub fn is_after(&self, other: &Cursor) -> bool {
+            self.cmp_position(other) == Ordering::Greater
+        }
+
+        /// Returns true if both cursors point to the same block.
+        ///
+        /// An empty unique key matches any unique key at the same height.
+        pub fn is_same_block(&self, other: &Cursor) -> bool {
+            self.order_key == other.order_key && self.has_same_unique_key(other)
+        }
+
+        /// Compares the unique key (block hash) of two cursors, ignoring their height.
+        ///
+        /// An empty unique key matches any unique key.
+        pub fn has_same_unique_key(&self, other: &Cursor) -> bool {
+            self.unique_key.is_empty()
+                || other.unique_key.is_empty()
+                || self.unique_key == other.unique_key
+        }
+
+        /// Returns true if the cursor is not part of the chain ending at `head`.
+        ///
+        /// This is the case if it's after the head, or at the same height but on
+        /// a different branch.
+        pub fn is_invalidated_by(&self, head: &Cursor) -> bool {
+            self.order_key > head.order_key
+                || (self.order_key == head.order_key && !self.has_same_unique_key(head))
+        }
+
+        /// Returns the cursor formatted as `<order key>/0x<unique key>`.
+        pub fn to_canonical_string(&self) -> String {
+            format!("{}/0x{}", self.order_key, hex::encode(&self.unique_key))
+        }
+    }
+
     impl Serialize for Cursor {
         fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
         where
@@ -149,6 +213,39 @@ mod tests {
         assert_eq!(cursor, back);
     }
 
+    #[test]
+    fn test_cursor_ordering() {
+        use std::cmp::Ordering;
+
+        let a = super::v1alpha2::Cursor::new(1, vec![1]);
+        let b = super::v1alpha2::Cursor::new(2, vec![0]);
+        let c = super::v1alpha2::Cursor::new(2, vec![1]);
+        assert!(a.is_before(&b));
+        assert!(b.is_after(&a));
+        assert!(b.is_before(&c));
+        assert!(!b.is_after(&c));
+        assert!(!c.is_before(&c) && !c.is_after(&c));
+        assert_eq!(a.cmp_position(&b), Ordering::Less);
+        assert_eq!(b.cmp_position(&c), Ordering::Less);
+        assert_eq!(c.cmp_position(&c), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_cursor_invalidation() {
+        let head = super::v1alpha2::Cursor::new(10, vec![1]);
+        assert!(!super::v1alpha2::Cursor::new(9, vec![2]).is_invalidated_by(&head));
+        assert!(!super::v1alpha2::Cursor::new(10, vec![1]).is_invalidated_by(&head));
+        assert!(!super::v1alpha2::Cursor::new(10, vec![]).is_invalidated_by(&head));
+        assert!(super::v1alpha2::Cursor::new(10, vec![2]).is_invalidated_by(&head));
+        assert!(super::v1alpha2::Cursor::new(11, vec![1]).is_invalidated_by(&head));
+    }
+
+    #[test]
+    fn test_cursor_canonical_string() {
+        let cursor = super::v1alpha2::Cursor::new(42, vec![0xab, 0xcd]);
+        assert_eq!(cursor.to_canonical_string(), "42/0xabcd");
+    }
+
     #[test]
     fn test_data_finality_serialization() {
         let serialized = serde_json::to_string(&DataFinality::DataStatusUnknown).unwrap();
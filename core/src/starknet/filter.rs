@@ -133,6 +133,39 @@ impl TransactionFilter {
         self
     }
 
+    /// Create `InvokeTransactionV3Filter` from `TransactionFilter`
+    pub fn invoke_transaction_v3<F>(&mut self, closure: F) -> &mut Self
+    where
+        F: Fn(InvokeTransactionV3Filter) -> InvokeTransactionV3Filter,
+    {
+        self.filter = Some(transaction_filter::Filter::InvokeV3(closure(
+            InvokeTransactionV3Filter::default(),
+        )));
+        self
+    }
+
+    /// Create `DeclareTransactionV3Filter` from `TransactionFilter`
+    pub fn declare_transaction_v3<F>(&mut self, closure: F) -> &mut Self
+    where
+        F: Fn(DeclareTransactionV3Filter) -> DeclareTransactionV3Filter,
+    {
+        self.filter = Some(transaction_filter::Filter::DeclareV3(closure(
+            DeclareTransactionV3Filter::default(),
+        )));
+        self
+    }
+
+    /// Create `DeployAccountTransactionV3Filter` from `TransactionFilter`
+    pub fn deploy_account_transaction_v3<F>(&mut self, closure: F) -> &mut Self
+    where
+        F: Fn(DeployAccountTransactionV3Filter) -> DeployAccountTransactionV3Filter,
+    {
+        self.filter = Some(transaction_filter::Filter::DeployAccountV3(closure(
+            DeployAccountTransactionV3Filter::default(),
+        )));
+        self
+    }
+
     /// Builds final `TransactionFilter`
     pub fn build(&mut self) -> Self {
         self.clone()
@@ -171,6 +204,44 @@ impl InvokeTransactionV1Filter {
         self.calldata = calldata;
         self
     }
+
+    /// Filter transactions with a call to the given contract.
+    pub fn with_contract_address(mut self, address: FieldElement) -> Self {
+        self.contract_address = Some(address);
+        self
+    }
+
+    /// Filter transactions with a call to the given selector.
+    pub fn with_entry_point_selector(mut self, selector: FieldElement) -> Self {
+        self.entry_point_selector = Some(selector);
+        self
+    }
+}
+
+impl InvokeTransactionV3Filter {
+    /// Filter transaction with sender address.
+    pub fn with_sender_address(mut self, address: FieldElement) -> Self {
+        self.sender_address = Some(address);
+        self
+    }
+
+    /// Filter with call data.
+    pub fn with_calldata(mut self, calldata: Vec<FieldElement>) -> Self {
+        self.calldata = calldata;
+        self
+    }
+
+    /// Filter transactions with a call to the given contract.
+    pub fn with_contract_address(mut self, address: FieldElement) -> Self {
+        self.contract_address = Some(address);
+        self
+    }
+
+    /// Filter transactions with a call to the given selector.
+    pub fn with_entry_point_selector(mut self, selector: FieldElement) -> Self {
+        self.entry_point_selector = Some(selector);
+        self
+    }
 }
 
 impl DeployTransactionFilter {
@@ -206,6 +277,20 @@ impl DeclareTransactionFilter {
     }
 }
 
+impl DeclareTransactionV3Filter {
+    /// Filter transaction with sender address.
+    pub fn with_sender_address(mut self, address: FieldElement) -> Self {
+        self.sender_address = Some(address);
+        self
+    }
+
+    /// Filter with class hash.
+    pub fn with_class_hash(mut self, class_hash: FieldElement) -> Self {
+        self.class_hash = Some(class_hash);
+        self
+    }
+}
+
 impl L1HandlerTransactionFilter {
     /// Filter transaction with contract address.
     pub fn with_contract_address(mut self, address: FieldElement) -> Self {
@@ -246,6 +331,26 @@ impl DeployAccountTransactionFilter {
     }
 }
 
+impl DeployAccountTransactionV3Filter {
+    /// Filter transaction with contract address salt.
+    pub fn with_contract_address_salt(mut self, address: FieldElement) -> Self {
+        self.contract_address_salt = Some(address);
+        self
+    }
+
+    /// Filter transaction with class hash.
+    pub fn with_class_hash(mut self, class_hash: FieldElement) -> Self {
+        self.class_hash = Some(class_hash);
+        self
+    }
+
+    /// Filter transaction with calldata.
+    pub fn with_constructor_calldata(mut self, constructor_calldata: Vec<FieldElement>) -> Self {
+        self.constructor_calldata = constructor_calldata;
+        self
+    }
+}
+
 impl EventFilter {
     /// Filter event from address.
     pub fn with_from_address(mut self, address: FieldElement) -> Self {
@@ -416,6 +521,9 @@ impl TransactionFilter {
             Some(transaction_filter::Filter::Declare(filter)) => filter.matches(tx),
             Some(transaction_filter::Filter::L1Handler(filter)) => filter.matches(tx),
             Some(transaction_filter::Filter::DeployAccount(filter)) => filter.matches(tx),
+            Some(transaction_filter::Filter::InvokeV3(filter)) => filter.matches(tx),
+            Some(transaction_filter::Filter::DeclareV3(filter)) => filter.matches(tx),
+            Some(transaction_filter::Filter::DeployAccountV3(filter)) => filter.matches(tx),
         }
     }
 }
@@ -439,6 +547,28 @@ impl InvokeTransactionV1Filter {
             Some(transaction::Transaction::InvokeV1(tx)) => {
                 self.sender_address.matches(&tx.sender_address)
                     && self.calldata.prefix_matches(&tx.calldata)
+                    && calls_match(
+                        &self.contract_address,
+                        &self.entry_point_selector,
+                        &tx.calldata,
+                    )
+            }
+            _ => false,
+        }
+    }
+}
+
+impl InvokeTransactionV3Filter {
+    pub fn matches(&self, tx: &Transaction) -> bool {
+        match tx.transaction.as_ref() {
+            Some(transaction::Transaction::InvokeV3(tx)) => {
+                self.sender_address.matches(&tx.sender_address)
+                    && self.calldata.prefix_matches(&tx.calldata)
+                    && calls_match(
+                        &self.contract_address,
+                        &self.entry_point_selector,
+                        &tx.calldata,
+                    )
             }
             _ => false,
         }
@@ -474,6 +604,18 @@ impl DeclareTransactionFilter {
     }
 }
 
+impl DeclareTransactionV3Filter {
+    pub fn matches(&self, tx: &Transaction) -> bool {
+        match tx.transaction.as_ref() {
+            Some(transaction::Transaction::DeclareV3(tx)) => {
+                self.class_hash.matches(&tx.class_hash)
+                    && self.sender_address.matches(&tx.sender_address)
+            }
+            _ => false,
+        }
+    }
+}
+
 impl L1HandlerTransactionFilter {
     pub fn matches(&self, tx: &Transaction) -> bool {
         match tx.transaction.as_ref() {
@@ -504,6 +646,99 @@ impl DeployAccountTransactionFilter {
     }
 }
 
+impl DeployAccountTransactionV3Filter {
+    pub fn matches(&self, tx: &Transaction) -> bool {
+        match tx.transaction.as_ref() {
+            Some(transaction::Transaction::DeployAccountV3(tx)) => {
+                self.class_hash.matches(&tx.class_hash)
+                    && self
+                        .contract_address_salt
+                        .matches(&tx.contract_address_salt)
+                    && self
+                        .constructor_calldata
+                        .prefix_matches(&tx.constructor_calldata)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Returns true if any of the calls encoded in the account calldata matches
+/// the given contract address and selector.
+fn calls_match(
+    contract_address: &Option<FieldElement>,
+    entry_point_selector: &Option<FieldElement>,
+    calldata: &[FieldElement],
+) -> bool {
+    if contract_address.is_none() && entry_point_selector.is_none() {
+        return true;
+    }
+
+    decode_account_calls(calldata)
+        .unwrap_or_default()
+        .into_iter()
+        .any(|(to, selector)| {
+            contract_address.matches(&Some(to.clone()))
+                && entry_point_selector.matches(&Some(selector.clone()))
+        })
+}
+
+/// Decodes the `(contract_address, selector)` of the calls in an account
+/// `__execute__` calldata.
+///
+/// Supports both the Cairo 1 layout (`[len, (to, selector, data_len, data...)...]`)
+/// and the legacy Cairo 0 layout (`[len, (to, selector, offset, data_len)..., data_len, data...]`).
+/// Returns `None` if the calldata doesn't match either layout.
+fn decode_account_calls(calldata: &[FieldElement]) -> Option<Vec<(&FieldElement, &FieldElement)>> {
+    decode_cairo1_calls(calldata).or_else(|| decode_cairo0_calls(calldata))
+}
+
+fn decode_cairo1_calls(calldata: &[FieldElement]) -> Option<Vec<(&FieldElement, &FieldElement)>> {
+    let (len, mut rest) = calldata.split_first()?;
+    let len = field_element_to_usize(len)?;
+    let mut calls = Vec::new();
+    for _ in 0..len {
+        let [to, selector, data_len, tail @ ..] = rest else {
+            return None;
+        };
+        let data_len = field_element_to_usize(data_len)?;
+        if tail.len() < data_len {
+            return None;
+        }
+        calls.push((to, selector));
+        rest = &tail[data_len..];
+    }
+
+    if !rest.is_empty() {
+        return None;
+    }
+
+    Some(calls)
+}
+
+fn decode_cairo0_calls(calldata: &[FieldElement]) -> Option<Vec<(&FieldElement, &FieldElement)>> {
+    let (len, rest) = calldata.split_first()?;
+    let len = field_element_to_usize(len)?;
+    let call_array = rest.get(..len.checked_mul(4)?)?;
+    let (data_len, data) = rest[call_array.len()..].split_first()?;
+    if field_element_to_usize(data_len)? != data.len() {
+        return None;
+    }
+
+    let calls = call_array
+        .chunks_exact(4)
+        .map(|call| (&call[0], &call[1]))
+        .collect();
+    Some(calls)
+}
+
+fn field_element_to_usize(value: &FieldElement) -> Option<usize> {
+    if value.lo_lo != 0 || value.lo_hi != 0 || value.hi_lo != 0 {
+        return None;
+    }
+    usize::try_from(value.hi_hi).ok()
+}
+
 impl EventFilter {
     pub fn matches(&self, event: &Event) -> bool {
         self.from_address.matches(&event.from_address)
@@ -604,9 +839,17 @@ impl StateUpdateFilter {
 
 #[cfg(test)]
 mod tests {
-    use super::{Filter, HeaderFilter};
+    use super::{
+        transaction, FieldElement, Filter, HeaderFilter, InvokeTransactionV1,
+        InvokeTransactionV1Filter, InvokeTransactionV3, InvokeTransactionV3Filter, Transaction,
+        TransactionFilter,
+    };
     use crate::filter::Filter as FilterTrait;
 
+    fn fe(value: u64) -> FieldElement {
+        FieldElement::from_u64(value)
+    }
+
     #[test]
     fn test_merge_header() {
         {
@@ -667,4 +910,86 @@ mod tests {
         a.merge_filter(b);
         assert_eq!(a.messages.len(), 3);
     }
+
+    #[test]
+    fn test_invoke_calls_cairo1() {
+        // two calls: 0x10::0x20(1, 2) and 0x30::0x40()
+        let calldata = vec![
+            fe(2),
+            fe(0x10),
+            fe(0x20),
+            fe(2),
+            fe(1),
+            fe(2),
+            fe(0x30),
+            fe(0x40),
+            fe(0),
+        ];
+        let tx = Transaction {
+            transaction: Some(transaction::Transaction::InvokeV3(InvokeTransactionV3 {
+                sender_address: Some(fe(1)),
+                calldata,
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+
+        let matching = InvokeTransactionV3Filter::default()
+            .with_contract_address(fe(0x30))
+            .with_entry_point_selector(fe(0x40));
+        assert!(matching.matches(&tx));
+
+        let wrong_pair = InvokeTransactionV3Filter::default()
+            .with_contract_address(fe(0x10))
+            .with_entry_point_selector(fe(0x40));
+        assert!(!wrong_pair.matches(&tx));
+
+        let wrong_sender = InvokeTransactionV3Filter::default()
+            .with_sender_address(fe(2))
+            .with_entry_point_selector(fe(0x20));
+        assert!(!wrong_sender.matches(&tx));
+
+        let wrong_version =
+            InvokeTransactionV1Filter::default().with_entry_point_selector(fe(0x20));
+        assert!(!wrong_version.matches(&tx));
+    }
+
+    #[test]
+    fn test_invoke_calls_cairo0() {
+        // two calls: 0x10::0x20(1) and 0x30::0x40(2)
+        let calldata = vec![
+            fe(2),
+            fe(0x10),
+            fe(0x20),
+            fe(0),
+            fe(1),
+            fe(0x30),
+            fe(0x40),
+            fe(1),
+            fe(1),
+            fe(2),
+            fe(1),
+            fe(2),
+        ];
+        let tx = Transaction {
+            transaction: Some(transaction::Transaction::InvokeV1(InvokeTransactionV1 {
+                sender_address: Some(fe(1)),
+                calldata,
+            })),
+            ..Default::default()
+        };
+
+        let filter = TransactionFilter::default()
+            .invoke_transaction_v1(|f| {
+                f.with_contract_address(fe(0x10))
+                    .with_entry_point_selector(fe(0x20))
+            })
+            .build();
+        assert!(filter.matches(&tx));
+
+        let filter = TransactionFilter::default()
+            .invoke_transaction_v1(|f| f.with_entry_point_selector(fe(0x50)))
+            .build();
+        assert!(!filter.matches(&tx));
+    }
 }
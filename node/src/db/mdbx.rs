@@ -1,9 +1,10 @@
-use std::{marker::PhantomData, ops::Range, path::Path};
+use std::{ffi::CString, marker::PhantomData, ops::Range, path::Path};
 
 use apibara_core::stream::{MessageData, RawMessageData};
 use libmdbx::{
-    Cursor, Database, DatabaseFlags, Environment, EnvironmentBuilder, EnvironmentKind,
-    Error as MdbxError, Geometry, TableObject, Transaction, TransactionKind, WriteFlags, RW,
+    Cursor, Database, DatabaseFlags, Environment, EnvironmentBuilder, EnvironmentFlags,
+    EnvironmentKind, Error as MdbxError, Geometry, Mode, TableObject, Transaction, TransactionKind,
+    WriteFlags, RW,
 };
 use prost::Message;
 
@@ -41,6 +42,8 @@ pub type MdbxResult<T> = Result<T, MdbxError>;
 pub struct MdbxEnvironmentBuilder<E: EnvironmentKind> {
     env: EnvironmentBuilder<E>,
     max_dbs: usize,
+    max_readers: Option<u64>,
+    read_only: bool,
     geometry: Geometry<Range<usize>>,
 }
 
@@ -51,6 +54,11 @@ pub trait MdbxEnvironmentExt<E: EnvironmentKind> {
 
     /// Creates a new mdbx environment builder.
     fn builder() -> MdbxEnvironmentBuilder<E>;
+
+    /// Copy the environment to the given path, omitting free pages.
+    ///
+    /// The destination must not exist.
+    fn copy_compact(&self, dest: &Path) -> MdbxResult<()>;
 }
 
 /// Extension methods over mdbx RO and RW transactions.
@@ -80,6 +88,20 @@ impl<E: EnvironmentKind> MdbxEnvironmentExt<E> for Environment<E> {
     fn builder() -> MdbxEnvironmentBuilder<E> {
         MdbxEnvironmentBuilder::new()
     }
+
+    fn copy_compact(&self, dest: &Path) -> MdbxResult<()> {
+        let dest =
+            CString::new(dest.to_string_lossy().as_bytes()).map_err(MdbxError::decode_error)?;
+        // SAFETY: the environment pointer is valid for the lifetime of `self`
+        // and the path is a valid nul-terminated string.
+        let rc = unsafe {
+            libmdbx::ffi::mdbx_env_copy(self.env(), dest.as_ptr(), libmdbx::ffi::MDBX_CP_COMPACT)
+        };
+        if rc != 0 {
+            return Err(MdbxError::from_err_code(rc));
+        }
+        Ok(())
+    }
 }
 
 impl<E: EnvironmentKind> MdbxEnvironmentBuilder<E> {
@@ -99,6 +121,8 @@ impl<E: EnvironmentKind> MdbxEnvironmentBuilder<E> {
         MdbxEnvironmentBuilder {
             env,
             max_dbs: 100,
+            max_readers: None,
+            read_only: false,
             geometry,
         }
     }
@@ -118,8 +142,31 @@ impl<E: EnvironmentKind> MdbxEnvironmentBuilder<E> {
         self
     }
 
+    /// Change the maximum number of concurrent readers.
+    pub fn with_max_readers(mut self, max_readers: u64) -> Self {
+        self.max_readers = Some(max_readers);
+        self
+    }
+
+    /// Open the environment in read-only mode.
+    ///
+    /// The environment can be shared with another process that writes to it.
+    pub fn with_read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
     /// Open the environment.
     pub fn open(mut self, path: &Path) -> MdbxResult<Environment<E>> {
+        if let Some(max_readers) = self.max_readers {
+            self.env.set_max_readers(max_readers);
+        }
+        if self.read_only {
+            self.env.set_flags(EnvironmentFlags {
+                mode: Mode::ReadOnly,
+                ..Default::default()
+            });
+        }
         self.env
             .set_geometry(self.geometry)
             .set_max_dbs(self.max_dbs)
@@ -0,0 +1,420 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock, Weak},
+    time::{Duration, Instant},
+};
+
+use jsonwebtoken::{jwk::JwkSet, DecodingKey, Validation};
+use serde::Deserialize;
+use tokio::sync::Notify;
+use tonic::{metadata::MetadataValue, service::Interceptor, Request, Status};
+use tracing::{debug, warn};
+
+/// How often the JWKS keys are refreshed.
+const JWKS_REFRESH_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Minimum time between two refreshes triggered by unknown key ids.
+const JWKS_MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Metadata key containing the identity of the authenticated client.
+///
+/// Use this key with [MetadataKeyRequestObserver](super::MetadataKeyRequestObserver)
+/// to meter requests per client.
+pub const AUTH_IDENTITY_METADATA_KEY: &str = "x-apibara-identity";
+
+/// How stream requests are authenticated.
+#[derive(Clone, Default)]
+pub enum AuthConfiguration {
+    /// Accept all requests.
+    #[default]
+    NoAuth,
+    /// Accept bearer tokens from the given map of token to identity.
+    StaticTokens(HashMap<String, String>),
+    /// Accept JWTs signed with the given HMAC secret.
+    JwtSecret(String),
+    /// Accept JWTs signed with one of the keys published at the given url.
+    ///
+    /// Keys are refreshed periodically and when a token is signed with an
+    /// unknown key id.
+    Jwks(String),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AuthError {
+    #[error("failed to fetch jwks")]
+    FetchJwks(#[from] reqwest::Error),
+    #[error("invalid jwk")]
+    InvalidJwk(#[from] jsonwebtoken::errors::Error),
+}
+
+/// A tonic interceptor that validates bearer tokens.
+///
+/// On success, the client identity is stored in the request metadata under
+/// [AUTH_IDENTITY_METADATA_KEY].
+#[derive(Clone)]
+pub struct Authenticator {
+    inner: Arc<AuthenticatorInner>,
+}
+
+enum AuthenticatorInner {
+    NoAuth,
+    StaticTokens(HashMap<String, String>),
+    Jwt {
+        keys: RwLock<Vec<JwtKey>>,
+        /// Notified to refresh the keys, if they're fetched from a url.
+        refresh: Option<Arc<Notify>>,
+    },
+}
+
+struct JwtKey {
+    key_id: Option<String>,
+    key: DecodingKey,
+}
+
+#[derive(Deserialize)]
+struct Claims {
+    sub: Option<String>,
+}
+
+impl Authenticator {
+    /// Creates a new authenticator, fetching the signing keys if needed.
+    ///
+    /// Must be called from within a tokio runtime.
+    pub async fn new(configuration: AuthConfiguration) -> Result<Self, AuthError> {
+        Self::with_refresh_intervals(
+            configuration,
+            JWKS_REFRESH_INTERVAL,
+            JWKS_MIN_REFRESH_INTERVAL,
+        )
+        .await
+    }
+
+    async fn with_refresh_intervals(
+        configuration: AuthConfiguration,
+        refresh_interval: Duration,
+        min_refresh_interval: Duration,
+    ) -> Result<Self, AuthError> {
+        let inner = match configuration {
+            AuthConfiguration::NoAuth => AuthenticatorInner::NoAuth,
+            AuthConfiguration::StaticTokens(tokens) => AuthenticatorInner::StaticTokens(tokens),
+            AuthConfiguration::JwtSecret(secret) => AuthenticatorInner::Jwt {
+                keys: RwLock::new(vec![JwtKey {
+                    key_id: None,
+                    key: DecodingKey::from_secret(secret.as_bytes()),
+                }]),
+                refresh: None,
+            },
+            AuthConfiguration::Jwks(url) => {
+                let keys = fetch_jwks(&url).await?;
+                let refresh = Arc::new(Notify::new());
+                let inner = Arc::new(AuthenticatorInner::Jwt {
+                    keys: RwLock::new(keys),
+                    refresh: Some(refresh.clone()),
+                });
+                tokio::spawn(refresh_jwks(
+                    Arc::downgrade(&inner),
+                    url,
+                    refresh,
+                    refresh_interval,
+                    min_refresh_interval,
+                ));
+                return Ok(Authenticator { inner });
+            }
+        };
+
+        Ok(Authenticator {
+            inner: Arc::new(inner),
+        })
+    }
+
+    /// Returns the identity of the client with the given bearer token.
+    fn authenticate(&self, token: &str) -> Option<String> {
+        match self.inner.as_ref() {
+            AuthenticatorInner::NoAuth => None,
+            AuthenticatorInner::StaticTokens(tokens) => tokens.get(token).cloned(),
+            AuthenticatorInner::Jwt { keys, refresh } => {
+                // jsonwebtoken rejects keys that don't match the algorithm family,
+                // so it's safe to use the algorithm from the header.
+                let header = jsonwebtoken::decode_header(token).ok()?;
+                let validation = Validation::new(header.alg);
+
+                let keys = keys.read().expect("jwt keys lock poisoned");
+                let mut candidates = keys
+                    .iter()
+                    .filter(|k| k.key_id.is_none() || k.key_id == header.kid)
+                    .peekable();
+
+                // the key may have been rotated since the last refresh.
+                if candidates.peek().is_none() {
+                    if let Some(refresh) = refresh {
+                        debug!(kid = ?header.kid, "unknown jwt key id");
+                        refresh.notify_one();
+                    }
+                    return None;
+                }
+
+                let data = candidates.find_map(|k| {
+                    jsonwebtoken::decode::<Claims>(token, &k.key, &validation)
+                        .map_err(|err| debug!(error = ?err, "invalid jwt"))
+                        .ok()
+                })?;
+
+                // the identity is used for metering, don't accept tokens without one.
+                if data.claims.sub.is_none() {
+                    debug!("jwt without sub claim");
+                }
+                data.claims.sub
+            }
+        }
+    }
+}
+
+async fn fetch_jwks(url: &str) -> Result<Vec<JwtKey>, AuthError> {
+    let jwks: JwkSet = reqwest::get(url).await?.json().await?;
+    jwks.keys
+        .iter()
+        .map(|jwk| {
+            Ok(JwtKey {
+                key_id: jwk.common.key_id.clone(),
+                key: DecodingKey::from_jwk(jwk)?,
+            })
+        })
+        .collect()
+}
+
+/// Refreshes the JWKS keys every `interval`, or when notified.
+///
+/// Stops when the authenticator is dropped.
+async fn refresh_jwks(
+    inner: Weak<AuthenticatorInner>,
+    url: String,
+    refresh: Arc<Notify>,
+    interval: Duration,
+    min_interval: Duration,
+) {
+    let mut last_refresh = Instant::now();
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {},
+            _ = refresh.notified() => {
+                // don't let clients with unknown key ids flood the jwks endpoint.
+                let elapsed = last_refresh.elapsed();
+                if elapsed < min_interval {
+                    tokio::time::sleep(min_interval - elapsed).await;
+                }
+            },
+        }
+
+        let Some(inner) = inner.upgrade() else {
+            return;
+        };
+        let AuthenticatorInner::Jwt { keys, .. } = inner.as_ref() else {
+            return;
+        };
+
+        last_refresh = Instant::now();
+        match fetch_jwks(&url).await {
+            Ok(new_keys) => {
+                debug!(url = %url, count = new_keys.len(), "refreshed jwks");
+                *keys.write().expect("jwt keys lock poisoned") = new_keys;
+            }
+            Err(err) => {
+                warn!(url = %url, error = ?err, "failed to refresh jwks");
+            }
+        }
+    }
+}
+
+impl Interceptor for Authenticator {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        // clients must not be able to choose their own identity.
+        request.metadata_mut().remove(AUTH_IDENTITY_METADATA_KEY);
+
+        if matches!(self.inner.as_ref(), AuthenticatorInner::NoAuth) {
+            return Ok(request);
+        }
+
+        let token = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| Status::unauthenticated("missing bearer token"))?;
+
+        let identity = self
+            .authenticate(token)
+            .ok_or_else(|| Status::unauthenticated("invalid bearer token"))?;
+
+        if let Ok(identity) = MetadataValue::try_from(identity) {
+            request
+                .metadata_mut()
+                .insert(AUTH_IDENTITY_METADATA_KEY, identity);
+        }
+
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        convert::Infallible,
+        sync::{Arc, Mutex},
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Body, Request as HttpRequest, Response, Server,
+    };
+    use jsonwebtoken::{Algorithm, EncodingKey, Header};
+    use serde::Serialize;
+    use tonic::{service::Interceptor, Request};
+
+    use super::{AuthConfiguration, Authenticator, AUTH_IDENTITY_METADATA_KEY};
+
+    /// Base64 encoded secrets, valid with and without url-safe encoding.
+    const FIRST_SECRET: &str = "YXBpYmFyYSB0ZXN0IHNlY3JldCBrZXkh";
+    const SECOND_SECRET: &str = "YW5vdGhlciBhcGliYXJhIHRlc3Qga2V5";
+
+    #[derive(Serialize)]
+    struct TestClaims {
+        sub: Option<String>,
+        exp: u64,
+    }
+
+    fn jwt(kid: Option<&str>, secret: &str, sub: Option<&str>) -> String {
+        let header = Header {
+            kid: kid.map(str::to_string),
+            ..Header::new(Algorithm::HS256)
+        };
+        let exp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap() + Duration::from_secs(60);
+        let claims = TestClaims {
+            sub: sub.map(str::to_string),
+            exp: exp.as_secs(),
+        };
+        let key = EncodingKey::from_base64_secret(secret).unwrap();
+        jsonwebtoken::encode(&header, &claims, &key).unwrap()
+    }
+
+    fn jwks(keys: &[(&str, &str)]) -> String {
+        let keys = keys
+            .iter()
+            .map(|(kid, secret)| {
+                format!(r#"{{"kty":"oct","kid":"{kid}","alg":"HS256","k":"{secret}"}}"#)
+            })
+            .collect::<Vec<_>>();
+        format!(r#"{{"keys":[{}]}}"#, keys.join(","))
+    }
+
+    /// Serves the given JWKS, returns its url.
+    fn serve_jwks(jwks: Arc<Mutex<String>>) -> String {
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service_fn(move |_| {
+            let jwks = jwks.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |_: HttpRequest<Body>| {
+                    let body = jwks.lock().unwrap().clone();
+                    async move { Ok::<_, Infallible>(Response::new(Body::from(body))) }
+                }))
+            }
+        }));
+        let url = format!("http://{}/jwks.json", server.local_addr());
+        tokio::spawn(server);
+        url
+    }
+
+    fn identity(auth: &mut Authenticator, token: &str) -> Option<String> {
+        let request = auth.call(request_with_token(token)).ok()?;
+        let identity = request.metadata().get(AUTH_IDENTITY_METADATA_KEY)?;
+        Some(identity.to_str().unwrap().to_string())
+    }
+
+    fn request_with_token(token: &str) -> Request<()> {
+        let mut request = Request::new(());
+        request
+            .metadata_mut()
+            .insert("authorization", format!("Bearer {token}").parse().unwrap());
+        request
+    }
+
+    #[tokio::test]
+    async fn test_static_tokens() {
+        let tokens = HashMap::from([("secret".to_string(), "alice".to_string())]);
+        let mut auth = Authenticator::new(AuthConfiguration::StaticTokens(tokens))
+            .await
+            .unwrap();
+
+        let request = auth.call(request_with_token("secret")).unwrap();
+        let identity = request.metadata().get(AUTH_IDENTITY_METADATA_KEY).unwrap();
+        assert_eq!(identity, "alice");
+
+        assert!(auth.call(request_with_token("other")).is_err());
+        assert!(auth.call(Request::new(())).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_jwt_secret() {
+        // FIRST_SECRET, decoded.
+        let secret = "apibara test secret key!".to_string();
+        let mut auth = Authenticator::new(AuthConfiguration::JwtSecret(secret))
+            .await
+            .unwrap();
+
+        let token = jwt(None, FIRST_SECRET, Some("alice"));
+        assert_eq!(identity(&mut auth, &token).as_deref(), Some("alice"));
+
+        // tokens without identity are rejected.
+        let token = jwt(None, FIRST_SECRET, None);
+        assert!(auth.call(request_with_token(&token)).is_err());
+
+        let token = jwt(None, SECOND_SECRET, Some("alice"));
+        assert!(auth.call(request_with_token(&token)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_jwks_refresh_on_unknown_kid() {
+        let keys = Arc::new(Mutex::new(jwks(&[("first", FIRST_SECRET)])));
+        let url = serve_jwks(keys.clone());
+        let mut auth = Authenticator::with_refresh_intervals(
+            AuthConfiguration::Jwks(url),
+            Duration::from_secs(3600),
+            Duration::ZERO,
+        )
+        .await
+        .unwrap();
+
+        let first = jwt(Some("first"), FIRST_SECRET, Some("alice"));
+        assert_eq!(identity(&mut auth, &first).as_deref(), Some("alice"));
+
+        // a token signed with the wrong key is rejected.
+        let forged = jwt(Some("first"), SECOND_SECRET, Some("alice"));
+        assert!(auth.call(request_with_token(&forged)).is_err());
+
+        // the key is rotated, tokens with the new kid are accepted after a refresh.
+        let second = jwt(Some("second"), SECOND_SECRET, Some("bob"));
+        assert!(auth.call(request_with_token(&second)).is_err());
+        *keys.lock().unwrap() = jwks(&[("first", FIRST_SECRET), ("second", SECOND_SECRET)]);
+
+        let mut identity_after_refresh = None;
+        for _ in 0..50 {
+            identity_after_refresh = identity(&mut auth, &second);
+            if identity_after_refresh.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(identity_after_refresh.as_deref(), Some("bob"));
+    }
+
+    #[tokio::test]
+    async fn test_no_auth_strips_identity() {
+        let mut auth = Authenticator::new(AuthConfiguration::NoAuth).await.unwrap();
+        let mut request = Request::new(());
+        request
+            .metadata_mut()
+            .insert(AUTH_IDENTITY_METADATA_KEY, "admin".parse().unwrap());
+        let request = auth.call(request).unwrap();
+        assert!(request.metadata().get(AUTH_IDENTITY_METADATA_KEY).is_none());
+    }
+}
@@ -1,4 +1,7 @@
+use std::collections::HashMap;
+
 use crate::o11y::{self, Counter, KeyValue};
+use serde::Deserialize;
 use tonic::metadata::MetadataMap;
 use tracing::{debug_span, Span};
 
@@ -10,6 +13,13 @@ pub trait RequestObserver: Send + Sync + 'static {
 
     /// Returns a meter to be used when metering a `stream_data` request.
     fn stream_data_meter(&self, metadata: &MetadataMap) -> Self::Meter;
+
+    /// Returns the blocks per second limit of a `stream_data` request.
+    ///
+    /// `None` means the server default applies.
+    fn stream_data_blocks_per_second(&self, _metadata: &MetadataMap) -> Option<u32> {
+        None
+    }
 }
 
 pub trait RequestMeter: Send + Sync + 'static {
@@ -18,6 +28,12 @@ pub trait RequestMeter: Send + Sync + 'static {
 
     /// Increments the counter for the total bytes sent by the given amount.
     fn increment_bytes_sent_counter(&self, amount: u64);
+
+    /// Increments the counter for the number of streams opened.
+    fn increment_streams_opened_counter(&self);
+
+    /// Increments the counter for the number of batches sent.
+    fn increment_batches_sent_counter(&self);
 }
 
 /// A [RequestObserver] that adds no context.
@@ -28,13 +44,47 @@ pub struct SimpleRequestObserver {}
 pub struct SimpleMeter {
     counter: Counter<u64>,
     bytes_sent_counter: Counter<u64>,
+    streams_opened_counter: Counter<u64>,
+    batches_sent_counter: Counter<u64>,
 }
 
 /// A [RequestObserver] that adds a specific metadata value to the span and meter.
 ///
 /// This can be used to add information like current user or api keys.
+///
+/// When configured with tenants, requests are also routed to a tenant based
+/// on a metadata value. Each tenant has its own rate limit and is added to
+/// the span and meter.
 pub struct MetadataKeyRequestObserver {
     keys: Vec<String>,
+    tenants: Option<TenantRouter>,
+}
+
+/// Map metadata values to tenants.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TenantConfiguration {
+    /// Metadata key used to identify the tenant.
+    pub metadata_key: String,
+    /// Tenants, by name.
+    #[serde(default)]
+    pub tenants: HashMap<String, TenantLimits>,
+}
+
+/// A tenant and its limits.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TenantLimits {
+    /// Metadata values that identify the tenant.
+    #[serde(default)]
+    pub values: Vec<String>,
+    /// Blocks per second each stream can receive. `None` uses the server default.
+    pub blocks_per_second: Option<u32>,
+}
+
+#[derive(Debug)]
+struct TenantRouter {
+    metadata_key: String,
+    /// Tenant name and limits, by metadata value.
+    tenants: HashMap<String, (String, TenantLimits)>,
 }
 
 /// A [RequestMeter] that adds information about the key used.
@@ -42,6 +92,8 @@ pub struct MetadataKeyMeter {
     metadata: Vec<KeyValue>,
     counter: Counter<u64>,
     bytes_sent_counter: Counter<u64>,
+    streams_opened_counter: Counter<u64>,
+    batches_sent_counter: Counter<u64>,
 }
 
 impl Default for SimpleMeter {
@@ -51,6 +103,8 @@ impl Default for SimpleMeter {
         SimpleMeter {
             counter,
             bytes_sent_counter,
+            streams_opened_counter: new_streams_opened_counter(),
+            batches_sent_counter: new_batches_sent_counter(),
         }
     }
 }
@@ -63,13 +117,49 @@ impl MetadataKeyMeter {
             metadata,
             counter,
             bytes_sent_counter,
+            streams_opened_counter: new_streams_opened_counter(),
+            batches_sent_counter: new_batches_sent_counter(),
         }
     }
 }
 
 impl MetadataKeyRequestObserver {
     pub fn new(keys: Vec<String>) -> Self {
-        MetadataKeyRequestObserver { keys }
+        MetadataKeyRequestObserver {
+            keys,
+            tenants: None,
+        }
+    }
+
+    /// Route requests to tenants based on the given configuration.
+    pub fn with_tenants(mut self, configuration: TenantConfiguration) -> Self {
+        self.tenants = Some(TenantRouter::new(configuration));
+        self
+    }
+
+    /// Returns the name and limits of the tenant that sent the request, if any.
+    fn tenant(&self, metadata: &MetadataMap) -> Option<(&str, &TenantLimits)> {
+        let router = self.tenants.as_ref()?;
+        let value = metadata.get(&router.metadata_key)?.to_str().ok()?;
+        router
+            .tenants
+            .get(value)
+            .map(|(name, limits)| (name.as_str(), limits))
+    }
+}
+
+impl TenantRouter {
+    fn new(configuration: TenantConfiguration) -> Self {
+        let mut tenants = HashMap::new();
+        for (name, limits) in configuration.tenants {
+            for value in &limits.values {
+                tenants.insert(value.clone(), (name.clone(), limits.clone()));
+            }
+        }
+        TenantRouter {
+            metadata_key: configuration.metadata_key,
+            tenants,
+        }
     }
 }
 
@@ -96,17 +186,30 @@ impl RequestMeter for SimpleMeter {
         let cx = o11y::Context::current();
         self.bytes_sent_counter.add(&cx, amount, &[]);
     }
+
+    fn increment_streams_opened_counter(&self) {
+        let cx = o11y::Context::current();
+        self.streams_opened_counter.add(&cx, 1, &[]);
+    }
+
+    fn increment_batches_sent_counter(&self) {
+        let cx = o11y::Context::current();
+        self.batches_sent_counter.add(&cx, 1, &[]);
+    }
 }
 
 impl RequestObserver for MetadataKeyRequestObserver {
     type Meter = MetadataKeyMeter;
 
-    fn stream_data_span(&self, _metadata: &MetadataMap) -> Span {
-        debug_span!("stream_data")
+    fn stream_data_span(&self, metadata: &MetadataMap) -> Span {
+        match self.tenant(metadata) {
+            Some((tenant, _)) => debug_span!("stream_data", tenant = tenant),
+            None => debug_span!("stream_data"),
+        }
     }
 
     fn stream_data_meter(&self, metadata: &MetadataMap) -> Self::Meter {
-        let mut result = Vec::with_capacity(self.keys.len());
+        let mut result = Vec::with_capacity(self.keys.len() + 1);
         for key in &self.keys {
             if let Some(value) = metadata.get(key) {
                 if let Ok(value) = value.to_str() {
@@ -114,8 +217,16 @@ impl RequestObserver for MetadataKeyRequestObserver {
                 }
             }
         }
+        if let Some((tenant, _)) = self.tenant(metadata) {
+            result.push(KeyValue::new("tenant", tenant.to_owned()));
+        }
         MetadataKeyMeter::new(result)
     }
+
+    fn stream_data_blocks_per_second(&self, metadata: &MetadataMap) -> Option<u32> {
+        let (_, limits) = self.tenant(metadata)?;
+        limits.blocks_per_second
+    }
 }
 
 impl RequestMeter for MetadataKeyMeter {
@@ -132,6 +243,18 @@ impl RequestMeter for MetadataKeyMeter {
         let attributes = self.metadata.as_slice();
         self.bytes_sent_counter.add(&cx, amount, attributes);
     }
+
+    fn increment_streams_opened_counter(&self) {
+        let cx = o11y::Context::current();
+        self.streams_opened_counter
+            .add(&cx, 1, self.metadata.as_slice());
+    }
+
+    fn increment_batches_sent_counter(&self) {
+        let cx = o11y::Context::current();
+        self.batches_sent_counter
+            .add(&cx, 1, self.metadata.as_slice());
+    }
 }
 
 fn new_data_out_counter() -> Counter<u64> {
@@ -143,3 +266,61 @@ fn new_bytes_sent_counter() -> Counter<u64> {
     let meter = o11y::meter("stream_data");
     meter.u64_counter("stream_bytes_sent").init()
 }
+
+fn new_streams_opened_counter() -> Counter<u64> {
+    let meter = o11y::meter("stream_data");
+    meter.u64_counter("streams_opened").init()
+}
+
+fn new_batches_sent_counter() -> Counter<u64> {
+    let meter = o11y::meter("stream_data");
+    meter.u64_counter("stream_batches_sent").init()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use tonic::metadata::MetadataMap;
+
+    use super::{MetadataKeyRequestObserver, RequestObserver, TenantConfiguration, TenantLimits};
+
+    fn metadata_with_key(value: &'static str) -> MetadataMap {
+        let mut metadata = MetadataMap::new();
+        metadata.insert("x-api-key", value.parse().unwrap());
+        metadata
+    }
+
+    #[test]
+    fn test_tenant_rate_limit() {
+        let mut tenants = HashMap::new();
+        tenants.insert(
+            "indexing".to_string(),
+            TenantLimits {
+                values: vec!["key-1".to_string(), "key-2".to_string()],
+                blocks_per_second: Some(100),
+            },
+        );
+        tenants.insert(
+            "analytics".to_string(),
+            TenantLimits {
+                values: vec!["key-3".to_string()],
+                blocks_per_second: None,
+            },
+        );
+        let observer =
+            MetadataKeyRequestObserver::new(Vec::default()).with_tenants(TenantConfiguration {
+                metadata_key: "x-api-key".to_string(),
+                tenants,
+            });
+
+        let limit = observer.stream_data_blocks_per_second(&metadata_with_key("key-2"));
+        assert_eq!(limit, Some(100));
+        let limit = observer.stream_data_blocks_per_second(&metadata_with_key("key-3"));
+        assert_eq!(limit, None);
+        let limit = observer.stream_data_blocks_per_second(&metadata_with_key("unknown"));
+        assert_eq!(limit, None);
+        let limit = observer.stream_data_blocks_per_second(&MetadataMap::new());
+        assert_eq!(limit, None);
+    }
+}
@@ -1,10 +1,18 @@
+mod auth;
 mod metadata;
 mod quota;
+mod request_metrics;
+
+pub use self::auth::{AuthConfiguration, AuthError, Authenticator, AUTH_IDENTITY_METADATA_KEY};
 
 pub use self::metadata::{
     MetadataKeyRequestObserver, RequestMeter, RequestObserver, SimpleMeter, SimpleRequestObserver,
+    TenantConfiguration, TenantLimits,
 };
 
 pub use self::quota::{
-    QuotaClient, QuotaClientFactory, QuotaConfiguration, QuotaError, QuotaStatus,
+    LocalQuotaLimits, QuotaClient, QuotaClientFactory, QuotaConfiguration, QuotaError, QuotaMeter,
+    QuotaPolicy, QuotaStatus, StaticQuotaPolicy,
 };
+
+pub use self::request_metrics::{RequestMetricsLayer, RequestMetricsService};
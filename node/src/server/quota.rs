@@ -1,8 +1,16 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
 use apibara_core::quota::v1::{
     quota_client::QuotaClient as GrpcQuotaClient, CheckRequest, QuotaStatus as GrpcQuotaStatus,
     UpdateAndCheckRequest,
 };
 use hyper::Uri;
+use serde::Deserialize;
 use tonic::{metadata::MetadataMap, transport::Channel, Request};
 use tracing::debug;
 
@@ -52,11 +60,66 @@ pub enum QuotaConfiguration {
         /// Quota server address.
         server_address: Uri,
     },
+    /// Quota tracked in memory, per client.
+    ///
+    /// Usage is reset when the node restarts.
+    LocalQuota {
+        /// Metadata key used to identify the client.
+        client_metadata_key: String,
+        /// Length of the quota period.
+        period: Duration,
+        /// Limits of each client.
+        policy: Arc<dyn QuotaPolicy>,
+        /// Receives the data units consumed by each client.
+        meter: Option<Arc<dyn QuotaMeter>>,
+    },
+}
+
+/// Decides the limits of each client, checked every time a batch is sent.
+///
+/// Implement this trait to load limits from an external system.
+pub trait QuotaPolicy: Send + Sync + fmt::Debug + 'static {
+    /// Returns the limits of the given client.
+    fn limits(&self, client_name: &str) -> LocalQuotaLimits;
+}
+
+/// Receives the data units consumed by each client.
+///
+/// Implement this trait to report usage to an external billing system.
+pub trait QuotaMeter: Send + Sync + fmt::Debug + 'static {
+    /// Records that the client consumed `data_units` more data units.
+    fn record_usage(&self, client_name: &str, data_units: u64);
+}
+
+/// A [QuotaPolicy] with fixed limits.
+#[derive(Debug, Clone, Default)]
+pub struct StaticQuotaPolicy {
+    /// Limits applied to clients without specific limits.
+    pub default_limits: LocalQuotaLimits,
+    /// Limits for specific clients.
+    pub client_limits: HashMap<String, LocalQuotaLimits>,
+}
+
+/// Limits enforced by the local quota. `None` means unlimited.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LocalQuotaLimits {
+    /// Data units the client can consume in a period.
+    pub data_units_per_period: Option<u64>,
+    /// Number of streams the client can open at the same time.
+    pub max_concurrent_streams: Option<usize>,
 }
 
 #[derive(Debug, Clone)]
 pub struct QuotaClientFactory {
     configuration: QuotaConfiguration,
+    local_usage: Arc<Mutex<HashMap<String, LocalUsage>>>,
+}
+
+#[derive(Debug, Clone)]
+struct LocalUsage {
+    period_start: Instant,
+    data_units: u64,
+    active_streams: usize,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -70,14 +133,30 @@ pub struct RemoteQuotaClient {
     client_name: Option<String>,
 }
 
+/// Tracks the quota of one stream.
+///
+/// The stream counts towards the client's concurrent streams until dropped.
+#[derive(Debug)]
+pub struct LocalQuotaClient {
+    usage: Arc<Mutex<HashMap<String, LocalUsage>>>,
+    client_name: String,
+    policy: Arc<dyn QuotaPolicy>,
+    meter: Option<Arc<dyn QuotaMeter>>,
+    period: Duration,
+}
+
 pub enum QuotaClient {
     NoQuotaClient(NoQuotaClient),
     RemoteQuotaClient(RemoteQuotaClient),
+    LocalQuotaClient(LocalQuotaClient),
 }
 
 impl QuotaClientFactory {
     pub fn new(configuration: QuotaConfiguration) -> Self {
-        QuotaClientFactory { configuration }
+        QuotaClientFactory {
+            configuration,
+            local_usage: Default::default(),
+        }
     }
 
     pub async fn client_with_metadata(
@@ -129,6 +208,28 @@ impl QuotaClientFactory {
                     network_name.clone(),
                 ))
             }
+            QuotaConfiguration::LocalQuota {
+                client_metadata_key,
+                period,
+                policy,
+                meter,
+            } => {
+                let client_name = metadata
+                    .get(client_metadata_key)
+                    .ok_or(QuotaError::MissingClientMetadataKey)?
+                    .to_str()
+                    .map_err(|_| QuotaError::InvalidClientMetadataKey)?
+                    .to_string();
+
+                let client = LocalQuotaClient::new(
+                    self.local_usage.clone(),
+                    client_name,
+                    policy.clone(),
+                    meter.clone(),
+                    *period,
+                );
+                Ok(QuotaClient::LocalQuotaClient(client))
+            }
         }
     }
 }
@@ -152,6 +253,7 @@ impl QuotaClient {
         match self {
             QuotaClient::NoQuotaClient(client) => Ok(client.check()),
             QuotaClient::RemoteQuotaClient(client) => Ok(client.check().await?),
+            QuotaClient::LocalQuotaClient(client) => Ok(client.check()),
         }
     }
 
@@ -159,6 +261,7 @@ impl QuotaClient {
         match self {
             QuotaClient::NoQuotaClient(client) => Ok(client.update_and_check(du)),
             QuotaClient::RemoteQuotaClient(client) => Ok(client.update_and_check(du).await?),
+            QuotaClient::LocalQuotaClient(client) => Ok(client.update_and_check(du)),
         }
     }
 }
@@ -226,6 +329,112 @@ impl RemoteQuotaClient {
     }
 }
 
+impl LocalQuotaClient {
+    fn new(
+        usage: Arc<Mutex<HashMap<String, LocalUsage>>>,
+        client_name: String,
+        policy: Arc<dyn QuotaPolicy>,
+        meter: Option<Arc<dyn QuotaMeter>>,
+        period: Duration,
+    ) -> Self {
+        {
+            let mut usage = usage.lock().expect("quota lock poisoned");
+            let now = Instant::now();
+            // forget clients without streams whose period ended, they start from zero anyway.
+            usage.retain(|_, usage| !usage.is_expired(now, period));
+            let usage = usage
+                .entry(client_name.clone())
+                .or_insert_with(|| LocalUsage::new(now));
+            usage.active_streams += 1;
+        }
+
+        LocalQuotaClient {
+            usage,
+            client_name,
+            policy,
+            meter,
+            period,
+        }
+    }
+
+    pub fn check(&self) -> QuotaStatus {
+        self.update_and_check(0)
+    }
+
+    pub fn update_and_check(&self, du: u64) -> QuotaStatus {
+        let mut usage = self.usage.lock().expect("quota lock poisoned");
+        let now = Instant::now();
+        let usage = usage
+            .entry(self.client_name.clone())
+            .or_insert_with(|| LocalUsage::new(now));
+
+        if now.duration_since(usage.period_start) >= self.period {
+            usage.period_start = now;
+            usage.data_units = 0;
+        }
+
+        usage.data_units += du;
+
+        if du > 0 {
+            if let Some(meter) = &self.meter {
+                meter.record_usage(&self.client_name, du);
+            }
+        }
+
+        let limits = self.policy.limits(&self.client_name);
+        let data_exceeded = limits
+            .data_units_per_period
+            .map(|limit| usage.data_units > limit)
+            .unwrap_or(false);
+        let streams_exceeded = limits
+            .max_concurrent_streams
+            .map(|limit| usage.active_streams > limit)
+            .unwrap_or(false);
+
+        if data_exceeded || streams_exceeded {
+            QuotaStatus::Exceeded
+        } else {
+            QuotaStatus::Ok
+        }
+    }
+}
+
+impl Drop for LocalQuotaClient {
+    fn drop(&mut self) {
+        let mut usage = self.usage.lock().expect("quota lock poisoned");
+        if let Some(client_usage) = usage.get_mut(&self.client_name) {
+            client_usage.active_streams = client_usage.active_streams.saturating_sub(1);
+            if client_usage.is_expired(Instant::now(), self.period) {
+                usage.remove(&self.client_name);
+            }
+        }
+    }
+}
+
+impl LocalUsage {
+    fn new(now: Instant) -> Self {
+        LocalUsage {
+            period_start: now,
+            data_units: 0,
+            active_streams: 0,
+        }
+    }
+
+    /// Returns true if the usage can be forgotten without changing the quota.
+    fn is_expired(&self, now: Instant, period: Duration) -> bool {
+        self.active_streams == 0 && now.duration_since(self.period_start) >= period
+    }
+}
+
+impl QuotaPolicy for StaticQuotaPolicy {
+    fn limits(&self, client_name: &str) -> LocalQuotaLimits {
+        self.client_limits
+            .get(client_name)
+            .unwrap_or(&self.default_limits)
+            .clone()
+    }
+}
+
 impl QuotaError {
     pub fn human_readable(&self) -> &'static str {
         match &self {
@@ -238,3 +447,138 @@ impl QuotaError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use tonic::metadata::MetadataMap;
+
+    use super::{
+        LocalQuotaLimits, QuotaClientFactory, QuotaConfiguration, QuotaMeter, StaticQuotaPolicy,
+    };
+
+    #[derive(Debug, Default)]
+    struct TestMeter {
+        usage: Mutex<Vec<(String, u64)>>,
+    }
+
+    impl QuotaMeter for TestMeter {
+        fn record_usage(&self, client_name: &str, data_units: u64) {
+            let mut usage = self.usage.lock().unwrap();
+            usage.push((client_name.to_string(), data_units));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_local_quota() {
+        let meter = Arc::new(TestMeter::default());
+        let factory = QuotaClientFactory::new(QuotaConfiguration::LocalQuota {
+            client_metadata_key: "x-client".to_string(),
+            period: Duration::from_secs(3600),
+            policy: Arc::new(StaticQuotaPolicy {
+                default_limits: LocalQuotaLimits {
+                    data_units_per_period: Some(10),
+                    max_concurrent_streams: None,
+                },
+                client_limits: HashMap::new(),
+            }),
+            meter: Some(meter.clone()),
+        });
+
+        let mut alice = MetadataMap::new();
+        alice.insert("x-client", "alice".parse().unwrap());
+        let mut bob = MetadataMap::new();
+        bob.insert("x-client", "bob".parse().unwrap());
+
+        assert!(factory
+            .client_with_metadata(&MetadataMap::new())
+            .await
+            .is_err());
+
+        let client = factory.client_with_metadata(&alice).await.unwrap();
+        assert!(!client.update_and_check(10).await.unwrap().is_exceeded());
+        assert!(client.update_and_check(1).await.unwrap().is_exceeded());
+
+        // usage is shared between streams of the same client.
+        let client = factory.client_with_metadata(&alice).await.unwrap();
+        assert!(client.check().await.unwrap().is_exceeded());
+
+        let client = factory.client_with_metadata(&bob).await.unwrap();
+        assert!(!client.check().await.unwrap().is_exceeded());
+
+        let usage = meter.usage.lock().unwrap();
+        assert_eq!(
+            *usage,
+            vec![("alice".to_string(), 10), ("alice".to_string(), 1)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_local_quota_evicts_expired_usage() {
+        let factory = QuotaClientFactory::new(QuotaConfiguration::LocalQuota {
+            client_metadata_key: "x-client".to_string(),
+            period: Duration::ZERO,
+            policy: Arc::new(StaticQuotaPolicy::default()),
+            meter: None,
+        });
+
+        for client in ["alice", "bob", "carol"] {
+            let mut metadata = MetadataMap::new();
+            metadata.insert("x-client", client.parse().unwrap());
+            let client = factory.client_with_metadata(&metadata).await.unwrap();
+            client.update_and_check(1).await.unwrap();
+        }
+
+        assert!(factory.local_usage.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_local_quota_concurrent_streams() {
+        let mut client_limits = HashMap::new();
+        client_limits.insert(
+            "bob".to_string(),
+            LocalQuotaLimits {
+                data_units_per_period: None,
+                max_concurrent_streams: Some(2),
+            },
+        );
+        let factory = QuotaClientFactory::new(QuotaConfiguration::LocalQuota {
+            client_metadata_key: "x-client".to_string(),
+            period: Duration::from_secs(3600),
+            policy: Arc::new(StaticQuotaPolicy {
+                default_limits: LocalQuotaLimits {
+                    data_units_per_period: None,
+                    max_concurrent_streams: Some(1),
+                },
+                client_limits,
+            }),
+            meter: None,
+        });
+
+        let mut alice = MetadataMap::new();
+        alice.insert("x-client", "alice".parse().unwrap());
+        let mut bob = MetadataMap::new();
+        bob.insert("x-client", "bob".parse().unwrap());
+
+        let first = factory.client_with_metadata(&alice).await.unwrap();
+        assert!(!first.check().await.unwrap().is_exceeded());
+        let second = factory.client_with_metadata(&alice).await.unwrap();
+        assert!(second.check().await.unwrap().is_exceeded());
+
+        // closing a stream frees a slot.
+        drop(first);
+        assert!(!second.check().await.unwrap().is_exceeded());
+
+        // bob has a higher limit.
+        let _first = factory.client_with_metadata(&bob).await.unwrap();
+        let second = factory.client_with_metadata(&bob).await.unwrap();
+        assert!(!second.check().await.unwrap().is_exceeded());
+        let third = factory.client_with_metadata(&bob).await.unwrap();
+        assert!(third.check().await.unwrap().is_exceeded());
+    }
+}
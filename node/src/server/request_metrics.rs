@@ -0,0 +1,130 @@
+//! Rate, errors, and duration metrics for gRPC servers.
+use std::{
+    sync::Arc,
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use futures::future::BoxFuture;
+use hyper::{Request, Response};
+use tower::{Layer, Service};
+
+use crate::o11y::{self, Counter, Histogram, KeyValue};
+
+/// A tower layer that records metrics for every gRPC request.
+///
+/// The following metrics are recorded, by `method` and `status`:
+///
+///  - `grpc_server_requests`: number of requests.
+///  - `grpc_server_request_duration_seconds`: time until the response headers
+///    are sent. For streaming methods, this doesn't include the stream duration.
+///
+/// The status is the gRPC status code returned in the response headers, `0`
+/// (ok) for responses that report their status in the trailers.
+#[derive(Clone)]
+pub struct RequestMetricsLayer {
+    metrics: Arc<RequestMetrics>,
+}
+
+/// The service created by [RequestMetricsLayer].
+#[derive(Clone)]
+pub struct RequestMetricsService<S> {
+    inner: S,
+    metrics: Arc<RequestMetrics>,
+}
+
+struct RequestMetrics {
+    requests: Counter<u64>,
+    duration: Histogram<f64>,
+}
+
+impl RequestMetricsLayer {
+    pub fn new() -> Self {
+        let metrics = RequestMetrics {
+            requests: new_requests_counter(),
+            duration: new_request_duration_histogram(),
+        };
+        RequestMetricsLayer {
+            metrics: Arc::new(metrics),
+        }
+    }
+}
+
+impl Default for RequestMetricsLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> Layer<S> for RequestMetricsLayer {
+    type Service = RequestMetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestMetricsService {
+            inner,
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for RequestMetricsService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let method = request.uri().path().to_string();
+        let metrics = self.metrics.clone();
+        let start = Instant::now();
+        let response = self.inner.call(request);
+
+        Box::pin(async move {
+            let response = response.await;
+            let status = match &response {
+                Ok(response) => grpc_status(response),
+                Err(_) => "error".to_string(),
+            };
+
+            let cx = o11y::Context::current();
+            let attributes = &[
+                KeyValue::new("method", method),
+                KeyValue::new("status", status),
+            ];
+            metrics.requests.add(&cx, 1, attributes);
+            metrics
+                .duration
+                .record(&cx, start.elapsed().as_secs_f64(), attributes);
+
+            response
+        })
+    }
+}
+
+fn grpc_status<B>(response: &Response<B>) -> String {
+    response
+        .headers()
+        .get("grpc-status")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("0")
+        .to_string()
+}
+
+fn new_requests_counter() -> Counter<u64> {
+    let meter = o11y::meter("grpc_server");
+    meter.u64_counter("grpc_server_requests").init()
+}
+
+fn new_request_duration_histogram() -> Histogram<f64> {
+    let meter = o11y::meter("grpc_server");
+    meter
+        .f64_histogram("grpc_server_request_duration_seconds")
+        .init()
+}
@@ -28,6 +28,21 @@ where
     pub finality: DataFinality,
     pub starting_cursor: Option<C>,
     pub filter: Vec<F>,
+    /// Maximum number of batches sent but not acknowledged by the client.
+    pub max_unacknowledged_batches: Option<usize>,
+}
+
+/// A request sent by the client over the stream.
+#[derive(Clone, Debug)]
+pub enum StreamRequest<C, F>
+where
+    C: Cursor,
+    F: Message + Default + Clone,
+{
+    /// Change the stream configuration.
+    Configure(StreamConfiguration<C, F>),
+    /// The client processed all data up to and including the cursor.
+    Acknowledge(C),
 }
 
 #[derive(Default)]
@@ -75,7 +90,16 @@ where
     fn handle_request(
         &mut self,
         request: StreamDataRequest,
-    ) -> Result<StreamConfiguration<C, F>, StreamError> {
+    ) -> Result<StreamRequest<C, F>, StreamError> {
+        if let Some(cursor) = request.acknowledge {
+            return match C::from_proto(&cursor) {
+                Some(cursor) => Ok(StreamRequest::Acknowledge(cursor)),
+                None => Err(StreamError::invalid_request(
+                    "invalid acknowledge cursor".to_string(),
+                )),
+            };
+        }
+
         let batch_size = request.batch_size.unwrap_or(DEFAULT_BATCH_SIZE as u64) as usize;
         let batch_size = batch_size.clamp(MIN_BATCH_SIZE, MAX_BATCH_SIZE);
 
@@ -127,17 +151,28 @@ where
             },
         };
 
+        let max_unacknowledged_batches = match request.max_unacknowledged_batches {
+            None => None,
+            Some(0) => {
+                return Err(StreamError::invalid_request(
+                    "max unacknowledged batches must be greater than zero".to_string(),
+                ));
+            }
+            Some(max) => Some(max as usize),
+        };
+
         let configuration = StreamConfiguration {
             batch_size,
             finality,
             stream_id,
             filter,
             starting_cursor,
+            max_unacknowledged_batches,
         };
 
         self.current = Some(configuration.clone());
 
-        Ok(configuration)
+        Ok(StreamRequest::Configure(configuration))
     }
 }
 
@@ -148,7 +183,7 @@ where
     S: Stream<Item = Result<StreamDataRequest, E>>,
     E: std::error::Error + Send + Sync + 'static,
 {
-    type Item = Result<StreamConfiguration<C, F>, StreamError>;
+    type Item = Result<StreamRequest<C, F>, StreamError>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Option<Self::Item>> {
         let this = self.project();
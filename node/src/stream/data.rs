@@ -1,8 +1,11 @@
 use core::num::NonZeroU32;
-use std::time::{Duration, Instant};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
 
 use apibara_core::node::v1alpha2::{
-    stream_data_response, Data, DataFinality, Heartbeat, Invalidate, StreamDataResponse,
+    stream_data_response, Data, DataFinality, Finalize, Heartbeat, Invalidate, StreamDataResponse,
 };
 use async_stream::stream;
 use futures::{stream::FusedStream, Stream, StreamExt};
@@ -18,11 +21,17 @@ use crate::{
 
 use super::{
     BatchProducer, CursorProducer, IngestionMessage, IngestionResponse, ReconfigureResponse,
-    StreamConfiguration, StreamError,
+    StreamConfiguration, StreamError, StreamRequest,
 };
 
+/// Maximum size of the data in a single response, in bytes.
+///
+/// Batches larger than this are split, keeping the response well below the
+/// default gRPC message size limit of 4 MiB.
+const MAX_DATA_SIZE_BYTES: usize = 3 * 1024 * 1024;
+
 pub fn new_data_stream<C, F, B, M>(
-    configuration_stream: impl Stream<Item = Result<StreamConfiguration<C, F>, StreamError>> + Unpin,
+    configuration_stream: impl Stream<Item = Result<StreamRequest<C, F>, StreamError>> + Unpin,
     ingestion_stream: impl Stream<Item = Result<IngestionMessage<C>, StreamError>> + Unpin,
     mut cursor_producer: impl CursorProducer<Cursor = C, Filter = F> + Unpin + FusedStream,
     mut batch_producer: impl BatchProducer<Cursor = C, Filter = F, Block = B>,
@@ -54,6 +63,11 @@ where
 
         let mut data_units = 0u64;
 
+        // Order keys of the batches sent but not yet acknowledged by the client.
+        // Only tracked if the client enabled flow control.
+        let mut max_unacknowledged_batches: Option<usize> = None;
+        let mut unacknowledged = VecDeque::new();
+
         match quota_client.check().await.map_err(StreamError::internal)? {
             QuotaStatus::Ok => {},
             QuotaStatus::Exceeded => {
@@ -84,17 +98,36 @@ where
                 // only at the end, produce new data.
                 biased;
 
-                configuration_message = configuration_stream.select_next_some() => {
+                request = configuration_stream.select_next_some() => {
+                    let configuration_message = match request {
+                        Ok(StreamRequest::Acknowledge(cursor)) => {
+                            acknowledge_batches(&mut unacknowledged, &cursor);
+                            continue;
+                        },
+                        Ok(StreamRequest::Configure(configuration)) => Ok(configuration),
+                        Err(err) => Err(err),
+                    };
+
                     has_configuration = true;
+                    let new_max_unacknowledged_batches = configuration_message
+                        .as_ref()
+                        .map(|configuration| configuration.max_unacknowledged_batches)
+                        .unwrap_or_default();
                     match handle_configuration_message(&mut cursor_producer, &mut batch_producer, configuration_message).await {
                         Ok((new_stream_id, batch_size, configure_response)) => {
                             stream_id = new_stream_id;
+                            max_unacknowledged_batches = new_max_unacknowledged_batches;
+                            unacknowledged.clear();
                             limiter = new_rate_limiter(blocks_per_second_quota, batch_size);
                             // send invalidate message if the specified cursor is no longer valid.
                             match configure_response {
                                 ReconfigureResponse::Ok => {},
-                                ReconfigureResponse::MissingStartingCursor => {
-                                    yield Err(StreamError::invalid_request("the specified starting cursor doesn't exist".to_string()));
+                                ReconfigureResponse::MissingStartingCursor { cursor, nearest_available, chain_id } => {
+                                    yield Err(StreamError::unknown_starting_cursor(
+                                        cursor.to_proto(),
+                                        nearest_available.map(|c| c.to_proto()),
+                                        chain_id,
+                                    ));
                                     break;
                                 },
                                 ReconfigureResponse::Invalidate(cursor) => {
@@ -125,11 +158,26 @@ where
                                 cursor: Some(cursor.to_proto()),
                             };
 
+                            // the client won't acknowledge invalidated data.
+                            let invalidated_key = cursor.to_proto().order_key;
+                            unacknowledged.retain(|key| *key <= invalidated_key);
+
                             yield Ok(StreamDataResponse {
                                 stream_id,
                                 message: Some(Message::Invalidate(message)),
                             });
                         },
+                        Ok(IngestionResponse::Finalize(cursor)) => {
+                            use stream_data_response::Message;
+                            let message = Finalize {
+                                cursor: Some(cursor.to_proto()),
+                            };
+
+                            yield Ok(StreamDataResponse {
+                                stream_id,
+                                message: Some(Message::Finalize(message)),
+                            });
+                        },
                         Ok(IngestionResponse::Ok) => {
                             // nothing to do.
                             // either message was a new accepted/finalized block, or stream is at
@@ -142,13 +190,14 @@ where
                     }
                 },
 
-                batch_cursor = cursor_producer.select_next_some(), if has_configuration => {
+                batch_cursor = cursor_producer.select_next_some(), if has_configuration && has_window(max_unacknowledged_batches, &unacknowledged) => {
                     use stream_data_response::Message;
 
                     match handle_batch_cursor(&mut cursor_producer, &mut batch_producer, batch_cursor, &meter, &limiter).await {
-                        Ok((data, finality)) => {
+                        Ok((messages, finality)) => {
+                            let has_data = messages.iter().any(|data| !data.data.is_empty());
                             let should_send_data =
-                                if !data.data.is_empty() || finality == DataFinality::DataStatusAccepted {
+                                if has_data || finality == DataFinality::DataStatusAccepted {
                                     true
                                 } else {
                                     last_batch_sent.elapsed() > max_batch_interval
@@ -159,7 +208,7 @@ where
                                 continue
                             }
 
-                            data_units += data.data.len() as u64;
+                            data_units += messages.iter().map(|data| data.data.len() as u64).sum::<u64>();
 
                             if last_quota_sent.elapsed() > quota_interval {
                                 match quota_client.update_and_check(data_units).await {
@@ -179,10 +228,20 @@ where
                             }
 
                             last_batch_sent = Instant::now();
-                            yield Ok(StreamDataResponse {
-                                stream_id,
-                                message: Some(Message::Data(data)),
-                            });
+                            // a batch split over multiple messages counts once, it's acknowledged
+                            // with the end cursor of its last message.
+                            if max_unacknowledged_batches.is_some() {
+                                if let Some(end_cursor) = messages.last().and_then(|data| data.end_cursor.as_ref()) {
+                                    unacknowledged.push_back(end_cursor.order_key);
+                                }
+                            }
+                            for data in messages {
+                                meter.increment_batches_sent_counter();
+                                yield Ok(StreamDataResponse {
+                                    stream_id,
+                                    message: Some(Message::Data(data)),
+                                });
+                            }
                         },
                         Err(err) => {
                             yield Err(err);
@@ -252,7 +311,7 @@ async fn handle_batch_cursor<C, F, B, M>(
     batch_cursor: Result<BatchCursor<C>, StreamError>,
     meter: &M,
     limiter: &DefaultDirectRateLimiter,
-) -> Result<(Data, DataFinality), StreamError>
+) -> Result<(Vec<Data>, DataFinality), StreamError>
 where
     C: Cursor + Send + Sync,
     F: Message + Default + Clone,
@@ -293,46 +352,84 @@ where
     );
 
     async move {
-        let next_batch_span = debug_span!(
-            "next_batch",
-            start_cursor = ?start_cursor,
-            end_cursor = ?end_cursor,
-        );
-
-        let batch = batch_producer
-            .next_batch(cursors.into_iter(), meter)
-            .instrument(next_batch_span)
-            .await?;
-
-        let serialize_batch_span = debug_span!(
-            "serialize_batch",
-            start_cursor = ?start_cursor,
-            end_cursor = ?end_cursor,
-        );
-
-        let data = serialize_batch_span.in_scope(|| {
-            batch
-                .iter()
-                .map(|block| block.encode_to_vec())
-                .collect::<Vec<_>>()
-        });
+        let mut messages = Vec::new();
+        let mut data = Vec::new();
+        let mut data_size_bytes = 0;
+        let mut total_size_bytes = 0;
+        let mut batch_start_cursor = start_cursor;
+        let mut previous_cursor = None;
+
+        // Produce data one block at a time so that batches that are too large
+        // can be split between blocks.
+        for cursor in cursors {
+            let next_batch_span = debug_span!("next_batch", cursor = ?cursor);
+            let batch = batch_producer
+                .next_batch(std::iter::once(cursor.clone()), meter)
+                .instrument(next_batch_span)
+                .await?;
+
+            let serialize_batch_span = debug_span!("serialize_batch", cursor = ?cursor);
+            let block_data = serialize_batch_span.in_scope(|| {
+                batch
+                    .iter()
+                    .map(|block| block.encode_to_vec())
+                    .collect::<Vec<_>>()
+            });
+            let block_size_bytes = block_data.iter().map(|block| block.len()).sum::<usize>();
+
+            // A single block larger than the limit is sent on its own.
+            if !data.is_empty() && data_size_bytes + block_size_bytes > MAX_DATA_SIZE_BYTES {
+                trace!(size = data_size_bytes, "split batch");
+                messages.push(Data {
+                    cursor: batch_start_cursor.map(|cursor| cursor.to_proto()),
+                    end_cursor: previous_cursor.as_ref().map(|cursor: &C| cursor.to_proto()),
+                    finality: finality as i32,
+                    data: std::mem::take(&mut data),
+                });
+                batch_start_cursor = previous_cursor.clone();
+                data_size_bytes = 0;
+            }
 
-        let total_size_bytes = data.iter().map(|block| block.len()).sum::<usize>();
-        meter.increment_bytes_sent_counter(total_size_bytes as u64);
+            data.extend(block_data);
+            data_size_bytes += block_size_bytes;
+            total_size_bytes += block_size_bytes;
+            previous_cursor = Some(cursor);
+        }
 
-        let data = Data {
-            cursor: start_cursor.map(|cursor| cursor.to_proto()),
+        messages.push(Data {
+            cursor: batch_start_cursor.map(|cursor| cursor.to_proto()),
             end_cursor: end_cursor.map(|cursor| cursor.to_proto()),
             finality: finality as i32,
             data,
-        };
+        });
 
-        Ok((data, finality))
+        meter.increment_bytes_sent_counter(total_size_bytes as u64);
+
+        Ok((messages, finality))
     }
     .instrument(handle_batch_span)
     .await
 }
 
+/// Returns true if more batches can be sent before the client acknowledges data.
+fn has_window(max_unacknowledged_batches: Option<usize>, unacknowledged: &VecDeque<u64>) -> bool {
+    match max_unacknowledged_batches {
+        None => true,
+        Some(max) => unacknowledged.len() < max,
+    }
+}
+
+/// Removes all batches up to and including the acknowledged cursor.
+fn acknowledge_batches<C: Cursor>(unacknowledged: &mut VecDeque<u64>, cursor: &C) {
+    let acknowledged_key = cursor.to_proto().order_key;
+    while let Some(key) = unacknowledged.front() {
+        if *key > acknowledged_key {
+            break;
+        }
+        unacknowledged.pop_front();
+    }
+}
+
 fn new_rate_limiter(blocks_per_second_quota: u32, batch_size: usize) -> DefaultDirectRateLimiter {
     // Convert to quota per minute to allow some bursting at the beginning.
     let quota_per_minute =
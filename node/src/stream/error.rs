@@ -1,3 +1,4 @@
+use apibara_core::node::v1alpha2::Cursor;
 use tracing::warn;
 
 #[derive(Debug, thiserror::Error)]
@@ -8,6 +9,14 @@ pub enum StreamError {
     QuotaExceeded,
     #[error("invalid request: {message}")]
     InvalidRequest { message: String },
+    #[error("the specified starting cursor doesn't exist: block {}", .cursor.order_key)]
+    UnknownStartingCursor {
+        cursor: Cursor,
+        /// The available cursor closest to the requested one.
+        nearest_available: Option<Cursor>,
+        /// Identifies the chain the node is following.
+        chain_id: Option<String>,
+    },
 }
 
 impl StreamError {
@@ -19,6 +28,18 @@ impl StreamError {
         StreamError::QuotaExceeded
     }
 
+    pub fn unknown_starting_cursor(
+        cursor: Cursor,
+        nearest_available: Option<Cursor>,
+        chain_id: Option<String>,
+    ) -> Self {
+        StreamError::UnknownStartingCursor {
+            cursor,
+            nearest_available,
+            chain_id,
+        }
+    }
+
     pub fn internal(err: impl Into<Box<dyn std::error::Error + Send + Sync + 'static>>) -> Self {
         StreamError::Internal(err.into())
     }
@@ -29,10 +50,46 @@ impl StreamError {
                 warn!(err = ?err, "stream error");
                 tonic::Status::internal("internal server error")
             }
-            StreamError::QuotaExceeded => tonic::Status::resource_exhausted(
-                "monthly data quota exceeded. Please contact support.",
-            ),
+            StreamError::QuotaExceeded => {
+                tonic::Status::resource_exhausted("quota exceeded. Please contact support.")
+            }
             StreamError::InvalidRequest { message } => tonic::Status::invalid_argument(message),
+            StreamError::UnknownStartingCursor {
+                cursor,
+                nearest_available,
+                chain_id,
+            } => {
+                let mut message = format!(
+                    "the specified starting cursor doesn't exist: {}",
+                    cursor.to_canonical_string()
+                );
+                if let Some(nearest) = &nearest_available {
+                    message.push_str(&format!(
+                        ". nearest available cursor: {}",
+                        nearest.to_canonical_string()
+                    ));
+                }
+                if let Some(chain_id) = &chain_id {
+                    message.push_str(&format!(". node chain: {chain_id}"));
+                }
+
+                let mut status = tonic::Status::not_found(message);
+                let metadata = status.metadata_mut();
+                if let Some(nearest) = nearest_available {
+                    match nearest.to_canonical_string().parse() {
+                        Ok(nearest) => {
+                            metadata.insert("x-nearest-cursor", nearest);
+                        }
+                        Err(err) => {
+                            warn!(err = ?err, "invalid nearest cursor metadata");
+                        }
+                    }
+                }
+                if let Some(chain_id) = chain_id.and_then(|id| id.parse().ok()) {
+                    metadata.insert("x-chain-id", chain_id);
+                }
+                status
+            }
         }
     }
 }
@@ -6,7 +6,7 @@ mod ingestion;
 mod producers;
 mod response;
 
-pub use self::configuration::{StreamConfiguration, StreamConfigurationStream};
+pub use self::configuration::{StreamConfiguration, StreamConfigurationStream, StreamRequest};
 pub use self::data::new_data_stream;
 pub use self::error::StreamError;
 pub use self::heartbeat::Heartbeat;
@@ -10,6 +10,8 @@ use crate::{core::Cursor, server::RequestMeter};
 pub enum IngestionResponse<C: Cursor> {
     /// Invalidate all data after the given cursor.
     Invalidate(C),
+    /// Data up to and including the given cursor is now finalized.
+    Finalize(C),
     /// No invalidation is required.
     Ok,
 }
@@ -22,7 +24,14 @@ pub enum ReconfigureResponse<C: Cursor> {
     /// No invalidation is required.
     Ok,
     /// The specified starting cursor doesn't exists.
-    MissingStartingCursor,
+    MissingStartingCursor {
+        /// The requested starting cursor.
+        cursor: C,
+        /// The available cursor closest to the requested one.
+        nearest_available: Option<C>,
+        /// Identifies the chain the node is following.
+        chain_id: Option<String>,
+    },
 }
 
 /// A batch cursor.
@@ -0,0 +1,139 @@
+//! Configuration of the OpenTelemetry exporters.
+use std::env;
+
+use error_stack::{Result, ResultExt};
+use opentelemetry::{
+    sdk::{trace::Sampler, Resource},
+    KeyValue,
+};
+use opentelemetry_otlp::{TonicExporterBuilder, WithExportConfig};
+use tonic_otlp::metadata::{MetadataKey, MetadataMap, MetadataValue};
+
+use crate::OpenTelemetryInitError;
+
+const OTEL_EXPORTER_OTLP_HEADERS: &str = "OTEL_EXPORTER_OTLP_HEADERS";
+const OTEL_TRACES_SAMPLER_ARG: &str = "OTEL_TRACES_SAMPLER_ARG";
+const SENTRY_DSN: &str = "SENTRY_DSN";
+
+/// Configure where and how traces and metrics are exported.
+///
+/// Values not set here fall back to the standard `OTEL_*` env variables.
+#[derive(Debug, Clone, Default)]
+pub struct OpenTelemetryConfig {
+    endpoint: Option<String>,
+    headers: Vec<(String, String)>,
+    resource_attributes: Vec<KeyValue>,
+    trace_sampling_ratio: Option<f64>,
+    error_reporting_dsn: Option<String>,
+}
+
+impl OpenTelemetryConfig {
+    /// Creates a new configuration from the env variables.
+    ///
+    /// Headers are read from `OTEL_EXPORTER_OTLP_HEADERS` (`key1=value1,key2=value2`)
+    /// and the trace sampling ratio from `OTEL_TRACES_SAMPLER_ARG`.
+    /// Errors are reported to the Sentry-compatible service at `SENTRY_DSN`, if set.
+    pub fn from_env() -> Self {
+        let headers = env::var(OTEL_EXPORTER_OTLP_HEADERS)
+            .map(|value| parse_headers(&value))
+            .unwrap_or_default();
+        let trace_sampling_ratio = env::var(OTEL_TRACES_SAMPLER_ARG)
+            .ok()
+            .and_then(|value| value.parse().ok());
+        let error_reporting_dsn = env::var(SENTRY_DSN).ok();
+
+        OpenTelemetryConfig {
+            headers,
+            trace_sampling_ratio,
+            error_reporting_dsn,
+            ..OpenTelemetryConfig::default()
+        }
+    }
+
+    /// Exports traces and metrics to the given OTLP endpoint.
+    pub fn with_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// Sends the given header with every export request, e.g. for authentication.
+    pub fn with_header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((key.into(), value.into()));
+        self
+    }
+
+    /// Adds the attribute to the resource of all traces and metrics.
+    pub fn with_resource_attribute(mut self, attribute: KeyValue) -> Self {
+        self.resource_attributes.push(attribute);
+        self
+    }
+
+    /// Sets the `service.version` resource attribute, included in logs and traces.
+    pub fn with_service_version(self, version: impl Into<String>) -> Self {
+        self.with_resource_attribute(KeyValue::new("service.version", version.into()))
+    }
+
+    /// Samples only the given ratio (between `0.0` and `1.0`) of root traces.
+    pub fn with_trace_sampling_ratio(mut self, ratio: f64) -> Self {
+        self.trace_sampling_ratio = Some(ratio);
+        self
+    }
+
+    /// Reports errors to the Sentry-compatible service at the given DSN.
+    pub fn with_error_reporting(mut self, dsn: impl Into<String>) -> Self {
+        self.error_reporting_dsn = Some(dsn.into());
+        self
+    }
+
+    pub(crate) fn error_reporting_dsn(&self) -> Option<&str> {
+        self.error_reporting_dsn.as_deref()
+    }
+
+    pub(crate) fn resource(&self) -> Resource {
+        Resource::default().merge(&Resource::new(self.resource_attributes.clone()))
+    }
+
+    pub(crate) fn sampler(&self) -> Sampler {
+        let root = match self.trace_sampling_ratio {
+            Some(ratio) => Sampler::TraceIdRatioBased(ratio),
+            None => Sampler::AlwaysOn,
+        };
+        Sampler::ParentBased(Box::new(root))
+    }
+
+    pub(crate) fn otlp_exporter(&self) -> Result<TonicExporterBuilder, OpenTelemetryInitError> {
+        let mut exporter = opentelemetry_otlp::new_exporter().tonic().with_env();
+
+        if let Some(endpoint) = &self.endpoint {
+            exporter = exporter.with_endpoint(endpoint);
+        }
+
+        if !self.headers.is_empty() {
+            let mut metadata = MetadataMap::new();
+            for (key, value) in &self.headers {
+                let key = MetadataKey::from_bytes(key.as_bytes())
+                    .change_context(OpenTelemetryInitError)
+                    .attach_printable_lazy(|| format!("invalid otlp header name: {key}"))?;
+                let value: MetadataValue<_> = value
+                    .parse()
+                    .change_context(OpenTelemetryInitError)
+                    .attach_printable_lazy(|| format!("invalid otlp header value for {key}"))?;
+                metadata.insert(key, value);
+            }
+            exporter = exporter.with_metadata(metadata);
+        }
+
+        Ok(exporter)
+    }
+}
+
+/// Parses a list of `key=value` pairs separated by commas.
+fn parse_headers(value: &str) -> Vec<(String, String)> {
+    value
+        .split(',')
+        .filter_map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            Some((key.trim().to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
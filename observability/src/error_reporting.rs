@@ -0,0 +1,105 @@
+//! Report errors to a Sentry-compatible service.
+use std::{any::type_name, borrow::Cow, sync::OnceLock, time::Duration};
+
+use error_stack::{AttachmentKind, FrameKind, Report, Result, ResultExt};
+use opentelemetry::{sdk::Resource, Key};
+use sentry::{
+    protocol::{Event, Exception, Level, Value},
+    types::Dsn,
+    ClientInitGuard, ClientOptions,
+};
+
+use crate::OpenTelemetryInitError;
+
+static ERROR_REPORTER: OnceLock<ClientInitGuard> = OnceLock::new();
+
+/// Starts sending error reports to the service at the given DSN.
+///
+/// The service name and version are taken from the resource attributes.
+pub(crate) fn init_error_reporting(
+    dsn: &str,
+    resource: &Resource,
+) -> Result<(), OpenTelemetryInitError> {
+    let dsn: Dsn = dsn
+        .parse()
+        .change_context(OpenTelemetryInitError)
+        .attach_printable("invalid error reporting dsn")?;
+
+    let service_name = resource
+        .get(Key::new("service.name"))
+        .map(|v| v.as_str().into_owned());
+    let release = resource
+        .get(Key::new("service.version"))
+        .map(|v| Cow::Owned(v.as_str().into_owned()));
+
+    let guard = sentry::init(ClientOptions {
+        dsn: Some(dsn),
+        release,
+        ..ClientOptions::default()
+    });
+
+    if let Some(service_name) = service_name {
+        sentry::configure_scope(|scope| scope.set_tag("service", service_name));
+    }
+
+    // keep the client alive for the lifetime of the process.
+    let _ = ERROR_REPORTER.set(guard);
+
+    Ok(())
+}
+
+/// Sends the report to the error reporting service, if configured.
+///
+/// The event includes the error contexts and all printable attachments.
+pub fn report_error<C>(report: &Report<C>)
+where
+    C: error_stack::Context,
+{
+    if ERROR_REPORTER.get().is_none() {
+        return;
+    }
+
+    let mut contexts = Vec::new();
+    let mut attachments = Vec::new();
+    for frame in report.frames() {
+        match frame.kind() {
+            FrameKind::Context(context) => contexts.push(Value::from(context.to_string())),
+            FrameKind::Attachment(AttachmentKind::Printable(attachment)) => {
+                attachments.push(Value::from(attachment.to_string()))
+            }
+            _ => {}
+        }
+    }
+
+    let message = report.current_context().to_string();
+    let exception = Exception {
+        ty: type_name::<C>().to_string(),
+        value: Some(message.clone()),
+        ..Exception::default()
+    };
+
+    let mut event = Event {
+        level: Level::Error,
+        message: Some(message),
+        exception: vec![exception].into(),
+        ..Event::default()
+    };
+    event
+        .extra
+        .insert("contexts".to_string(), Value::from(contexts));
+    event
+        .extra
+        .insert("attachments".to_string(), Value::from(attachments));
+
+    sentry::capture_event(event);
+}
+
+/// Waits until all pending error reports are sent, up to the given timeout.
+///
+/// Call this before exiting the process, otherwise reports may be lost.
+pub fn flush_error_reports(timeout: Duration) -> bool {
+    ERROR_REPORTER
+        .get()
+        .map(|guard| guard.flush(Some(timeout)))
+        .unwrap_or(true)
+}
@@ -0,0 +1,224 @@
+//! JSON log lines correlated with traces.
+use std::fmt;
+
+use opentelemetry::{
+    sdk::Resource,
+    trace::{TraceContextExt, TraceId},
+    Key,
+};
+use serde_json::{Map, Value};
+use tracing::{
+    field::{Field, Visit},
+    Event, Subscriber,
+};
+use tracing_opentelemetry::OtelData;
+use tracing_subscriber::{
+    fmt::{
+        format::Writer,
+        time::{FormatTime, SystemTime},
+        FmtContext, FormatEvent, FormatFields, FormattedFields,
+    },
+    registry::LookupSpan,
+};
+
+/// Formats events as one JSON object per line.
+///
+/// Every line includes the service name and version, and the trace and span
+/// ids of the current span so that logs can be joined with traces.
+///
+/// The fields of the spans in scope are included in `spans`, from the root
+/// span to the current span. Use it together with
+/// [tracing_subscriber::fmt::format::JsonFields] so that span fields are
+/// recorded as JSON.
+pub struct JsonFormat {
+    service_name: Option<String>,
+    service_version: Option<String>,
+}
+
+impl JsonFormat {
+    pub fn new(resource: &Resource) -> Self {
+        let service_name = resource
+            .get(Key::new("service.name"))
+            .map(|v| v.as_str().into_owned());
+        let service_version = resource
+            .get(Key::new("service.version"))
+            .map(|v| v.as_str().into_owned());
+        JsonFormat {
+            service_name,
+            service_version,
+        }
+    }
+}
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let metadata = event.metadata();
+        let mut line = Map::new();
+
+        let mut timestamp = String::new();
+        SystemTime.format_time(&mut Writer::new(&mut timestamp))?;
+        line.insert("timestamp".to_string(), timestamp.into());
+        line.insert("level".to_string(), metadata.level().as_str().into());
+        line.insert("target".to_string(), metadata.target().into());
+
+        if let Some(service_name) = &self.service_name {
+            line.insert("service.name".to_string(), service_name.clone().into());
+        }
+        if let Some(service_version) = &self.service_version {
+            line.insert(
+                "service.version".to_string(),
+                service_version.clone().into(),
+            );
+        }
+
+        if let Some(scope) = ctx.event_scope() {
+            let spans = scope
+                .from_root()
+                .map(|span| {
+                    let mut value = Map::new();
+                    value.insert("name".to_string(), span.name().into());
+                    if let Some(fields) = span.extensions().get::<FormattedFields<N>>() {
+                        match serde_json::from_str::<Map<String, Value>>(&fields.fields) {
+                            Ok(fields) => value.extend(fields),
+                            Err(_) if fields.fields.is_empty() => {}
+                            Err(_) => {
+                                value.insert("fields".to_string(), fields.fields.clone().into());
+                            }
+                        }
+                    }
+                    Value::Object(value)
+                })
+                .collect::<Vec<_>>();
+            line.insert("spans".to_string(), spans.into());
+        }
+
+        if let Some(span) = ctx.lookup_current() {
+            line.insert("span".to_string(), span.name().into());
+            if let Some(otel) = span.extensions().get::<OtelData>() {
+                let trace_id = otel
+                    .builder
+                    .trace_id
+                    .unwrap_or_else(|| otel.parent_cx.span().span_context().trace_id());
+                if trace_id != TraceId::INVALID {
+                    line.insert("trace_id".to_string(), trace_id.to_string().into());
+                }
+                if let Some(span_id) = otel.builder.span_id {
+                    line.insert("span_id".to_string(), span_id.to_string().into());
+                }
+            }
+        }
+
+        let mut fields = JsonVisitor::default();
+        event.record(&mut fields);
+        line.insert("fields".to_string(), Value::Object(fields.0));
+
+        let line = serde_json::to_string(&line).map_err(|_| fmt::Error)?;
+        writeln!(writer, "{line}")
+    }
+}
+
+#[derive(Default)]
+struct JsonVisitor(Map<String, Value>);
+
+impl Visit for JsonVisitor {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{value:?}").into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+
+    use serde_json::{json, Value};
+    use tracing_subscriber::fmt::format::JsonFields;
+
+    use super::JsonFormat;
+
+    #[derive(Clone, Default)]
+    struct TestWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for TestWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_json_format() {
+        let writer = TestWriter::default();
+        let subscriber = tracing_subscriber::fmt()
+            .fmt_fields(JsonFields::new())
+            .event_format(JsonFormat {
+                service_name: Some("test".to_string()),
+                service_version: None,
+            })
+            .with_writer({
+                let writer = writer.clone();
+                move || writer.clone()
+            })
+            .finish();
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("stream_data", tenant = "acme");
+            let _outer = span.enter();
+            let span = tracing::info_span!("batch", size = 3);
+            let _inner = span.enter();
+            tracing::info!(blocks = 2, "sent batch");
+        });
+
+        let output = writer.0.lock().unwrap();
+        let line: Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["service.name"], "test");
+        assert_eq!(line["span"], "batch");
+        assert_eq!(
+            line["fields"],
+            json!({ "message": "sent batch", "blocks": 2 })
+        );
+        assert_eq!(
+            line["spans"],
+            json!([
+                { "name": "stream_data", "tenant": "acme" },
+                { "name": "batch", "size": 3 },
+            ])
+        );
+    }
+}
@@ -1,26 +1,56 @@
 //! # OpenTelemetry helpers
 
-use std::{env, fmt};
+mod config;
+mod error_reporting;
+mod json;
+mod log_filter;
+mod propagation;
+mod runtime;
+
+use std::{convert::Infallible, env, fmt, net::SocketAddr};
 
 use error_stack::{Result, ResultExt};
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
 use opentelemetry::{
     global,
     sdk::{
-        self, export::metrics::aggregation::cumulative_temporality_selector, metrics::selectors,
-        Resource,
+        self,
+        export::metrics::aggregation::cumulative_temporality_selector,
+        metrics::{controllers, controllers::BasicController, processors, selectors},
+        propagation::TraceContextPropagator,
     },
 };
-use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_prometheus::PrometheusExporter;
+use prometheus::{Encoder, TextEncoder};
 use tracing::Subscriber;
 
+use crate::{
+    error_reporting::init_error_reporting, json::JsonFormat, log_filter::serve_log_filter,
+    runtime::register_runtime_metrics,
+};
+
 pub use opentelemetry::metrics::{ObservableCounter, ObservableGauge};
 pub use opentelemetry::{Context, Key, KeyValue};
 use tracing_opentelemetry::MetricsLayer;
-use tracing_subscriber::{prelude::*, registry::LookupSpan, EnvFilter, Layer};
+use tracing_subscriber::{
+    fmt::format::JsonFields, prelude::*, registry::LookupSpan, reload, EnvFilter, Layer,
+};
 
-pub use opentelemetry::metrics::{Counter, Meter};
+pub use config::OpenTelemetryConfig;
+pub use error_reporting::{flush_error_reports, report_error};
+pub use opentelemetry::metrics::{Counter, Histogram, Meter, UpDownCounter};
+pub use propagation::set_parent_from_headers;
 
 const OTEL_SDK_DISABLED: &str = "OTEL_SDK_DISABLED";
+const OTEL_METRICS_EXPORTER: &str = "OTEL_METRICS_EXPORTER";
+const OTEL_EXPORTER_PROMETHEUS_HOST: &str = "OTEL_EXPORTER_PROMETHEUS_HOST";
+const OTEL_EXPORTER_PROMETHEUS_PORT: &str = "OTEL_EXPORTER_PROMETHEUS_PORT";
+const RUST_LOG_ADMIN_ADDRESS: &str = "RUST_LOG_ADMIN_ADDRESS";
+const RUST_LOG_ADMIN_TOKEN: &str = "RUST_LOG_ADMIN_TOKEN";
 
 pub type BoxedLayer<S> = Box<dyn Layer<S> + Send + Sync>;
 
@@ -38,7 +68,15 @@ pub fn meter(name: &'static str) -> Meter {
     global::meter(name)
 }
 
+/// Initializes tracing and metrics, configured with the env variables.
 pub fn init_opentelemetry() -> Result<(), OpenTelemetryInitError> {
+    init_opentelemetry_with_config(OpenTelemetryConfig::from_env())
+}
+
+/// Initializes tracing and metrics with the given exporter configuration.
+pub fn init_opentelemetry_with_config(
+    config: OpenTelemetryConfig,
+) -> Result<(), OpenTelemetryInitError> {
     // The otel sdk doesn't follow the disabled env variable flag.
     // so we manually implement it to disable otel exports.
     // we diverge from the spec by defaulting to disabled.
@@ -50,19 +88,33 @@ pub fn init_opentelemetry() -> Result<(), OpenTelemetryInitError> {
         std::env::set_var("RUST_LOG", "info");
     }
 
-    let mut layers = vec![stdout()];
+    let mut layers = vec![stdout(&config)?];
 
     if !sdk_disabled {
-        let otel_layer = otel()?;
+        // propagate the trace context with the W3C `traceparent` header.
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let otel_layer = otel(&config)?;
         layers.push(otel_layer);
     }
 
     tracing_subscriber::registry().with(layers).init();
 
+    if let Some(dsn) = config.error_reporting_dsn() {
+        init_error_reporting(dsn, &config.resource())?;
+    }
+
+    if !sdk_disabled {
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            register_runtime_metrics(handle)
+                .change_context(OpenTelemetryInitError)
+                .attach_printable("failed to register runtime metrics")?;
+        }
+    }
+
     Ok(())
 }
 
-fn otel<S>() -> Result<BoxedLayer<S>, OpenTelemetryInitError>
+fn otel<S>(config: &OpenTelemetryConfig) -> Result<BoxedLayer<S>, OpenTelemetryInitError>
 where
     S: Subscriber + Send + Sync,
     for<'a> S: LookupSpan<'a>,
@@ -72,22 +124,20 @@ where
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("INFO"));
 
     // Both tracer and meter are configured with environment variables.
-    let meter = opentelemetry_otlp::new_pipeline()
-        .metrics(
-            selectors::simple::inexpensive(),
-            cumulative_temporality_selector(),
-            opentelemetry::runtime::Tokio,
-        )
-        .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_env())
-        .with_resource(Resource::default())
-        .build()
-        .change_context(OpenTelemetryInitError)
-        .attach_printable("failed to create metrics pipeline")?;
+    let metrics_exporter = env::var(OTEL_METRICS_EXPORTER).unwrap_or_else(|_| "otlp".to_string());
+    let meter = match metrics_exporter.as_str() {
+        "prometheus" => prometheus_metrics(config)?,
+        _ => otlp_metrics(config)?,
+    };
 
     let tracer = opentelemetry_otlp::new_pipeline()
         .tracing()
-        .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_env())
-        .with_trace_config(sdk::trace::config().with_resource(Resource::default()))
+        .with_exporter(config.otlp_exporter()?)
+        .with_trace_config(
+            sdk::trace::config()
+                .with_resource(config.resource())
+                .with_sampler(config.sampler()),
+        )
         .install_batch(opentelemetry::runtime::Tokio)
         .change_context(OpenTelemetryInitError)
         .attach_printable("failed to create tracing pipeline")?;
@@ -102,23 +152,116 @@ where
     Ok(otel_layer)
 }
 
-fn stdout<S>() -> BoxedLayer<S>
+fn otlp_metrics(config: &OpenTelemetryConfig) -> Result<BasicController, OpenTelemetryInitError> {
+    opentelemetry_otlp::new_pipeline()
+        .metrics(
+            selectors::simple::inexpensive(),
+            cumulative_temporality_selector(),
+            opentelemetry::runtime::Tokio,
+        )
+        .with_exporter(config.otlp_exporter()?)
+        .with_resource(config.resource())
+        .build()
+        .change_context(OpenTelemetryInitError)
+        .attach_printable("failed to create metrics pipeline")
+}
+
+/// Serve metrics at `/metrics` for prometheus to scrape.
+///
+/// The address is configured with the `OTEL_EXPORTER_PROMETHEUS_HOST` and
+/// `OTEL_EXPORTER_PROMETHEUS_PORT` env variables, and defaults to `0.0.0.0:9464`.
+fn prometheus_metrics(
+    config: &OpenTelemetryConfig,
+) -> Result<BasicController, OpenTelemetryInitError> {
+    let host = env::var(OTEL_EXPORTER_PROMETHEUS_HOST).unwrap_or_else(|_| "0.0.0.0".to_string());
+    let port = env::var(OTEL_EXPORTER_PROMETHEUS_PORT).unwrap_or_else(|_| "9464".to_string());
+    let address: SocketAddr = format!("{host}:{port}")
+        .parse()
+        .change_context(OpenTelemetryInitError)
+        .attach_printable("invalid prometheus exporter address")?;
+
+    let controller = controllers::basic(processors::factory(
+        selectors::simple::inexpensive(),
+        cumulative_temporality_selector(),
+    ))
+    .with_resource(config.resource())
+    .build();
+    let exporter = opentelemetry_prometheus::exporter(controller.clone()).init();
+
+    let server = Server::try_bind(&address)
+        .change_context(OpenTelemetryInitError)
+        .attach_printable_lazy(|| format!("failed to bind prometheus exporter to {address}"))?
+        .serve(make_service_fn(move |_| {
+            let exporter = exporter.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let exporter = exporter.clone();
+                    async move { Ok::<_, Infallible>(prometheus_response(&exporter, req)) }
+                }))
+            }
+        }));
+
+    tokio::spawn(async move {
+        if let Err(err) = server.await {
+            tracing::error!(err = ?err, "prometheus exporter error");
+        }
+    });
+
+    Ok(controller)
+}
+
+fn prometheus_response(exporter: &PrometheusExporter, req: Request<Body>) -> Response<Body> {
+    if req.method() != Method::GET || req.uri().path() != "/metrics" {
+        return Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty())
+            .expect("valid response");
+    }
+
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    if let Err(err) = encoder.encode(&exporter.registry().gather(), &mut buffer) {
+        return Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(Body::from(err.to_string()))
+            .expect("valid response");
+    }
+
+    Response::builder()
+        .header(CONTENT_TYPE, encoder.format_type())
+        .body(Body::from(buffer))
+        .expect("valid response")
+}
+
+/// Log to stdout.
+///
+/// If the `RUST_LOG_ADMIN_ADDRESS` env variable is set, the log filter can be
+/// changed at runtime by sending a `PUT /log-filter` request to that address.
+/// Requests must send the `RUST_LOG_ADMIN_TOKEN` env variable as bearer token,
+/// if set, otherwise the address must be a loopback address.
+fn stdout<S>(config: &OpenTelemetryConfig) -> Result<BoxedLayer<S>, OpenTelemetryInitError>
 where
-    S: Subscriber,
+    S: Subscriber + Send + Sync + 'static,
     for<'a> S: LookupSpan<'a>,
 {
     let log_env_filter =
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("INFO"));
+    let (log_env_filter, log_filter_handle) = reload::Layer::new(log_env_filter);
+
+    if let Ok(address) = env::var(RUST_LOG_ADMIN_ADDRESS) {
+        let token = env::var(RUST_LOG_ADMIN_TOKEN).ok();
+        serve_log_filter(&address, token, log_filter_handle)?;
+    }
 
     let json_fmt = std::env::var("RUST_LOG_FORMAT")
         .map(|val| val == "json")
         .unwrap_or(false);
 
-    if json_fmt {
+    let layer = if json_fmt {
         tracing_subscriber::fmt::layer()
             .with_ansi(false)
-            .with_target(true)
-            .json()
+            .fmt_fields(JsonFields::new())
+            .event_format(JsonFormat::new(&config.resource()))
             .with_filter(log_env_filter)
             .boxed()
     } else {
@@ -127,5 +270,7 @@ where
             .with_target(false)
             .with_filter(log_env_filter)
             .boxed()
-    }
+    };
+
+    Ok(layer)
 }
@@ -0,0 +1,140 @@
+//! Change the log filter of a running process.
+use std::{
+    convert::Infallible,
+    net::{Ipv4Addr, SocketAddr},
+    sync::Arc,
+};
+
+use error_stack::{Result, ResultExt};
+use hyper::{
+    header::AUTHORIZATION,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use tracing_subscriber::{reload::Handle, EnvFilter};
+
+use crate::OpenTelemetryInitError;
+
+const LOG_FILTER_PATH: &str = "/log-filter";
+
+/// Serve the log filter at `/log-filter`.
+///
+/// `GET` returns the current filter, `PUT` replaces it with the directives in
+/// the request body, e.g. `info,apibara_starknet::ingestion=debug`.
+///
+/// The address is either a socket address or a port, in which case the server
+/// binds to `127.0.0.1`. If `token` is set, `PUT` requests must send it as a
+/// bearer token. Without a token, the server only binds to loopback addresses.
+pub fn serve_log_filter<S: 'static>(
+    address: &str,
+    token: Option<String>,
+    handle: Handle<EnvFilter, S>,
+) -> Result<(), OpenTelemetryInitError> {
+    let address = match address.parse::<u16>() {
+        Ok(port) => SocketAddr::from((Ipv4Addr::LOCALHOST, port)),
+        Err(_) => address
+            .parse()
+            .change_context(OpenTelemetryInitError)
+            .attach_printable("invalid log filter admin address")?,
+    };
+
+    if token.is_none() && !address.ip().is_loopback() {
+        return Err(OpenTelemetryInitError)
+            .attach_printable("log filter admin without token must bind to a loopback address");
+    }
+
+    let token = Arc::new(token);
+
+    let server = Server::try_bind(&address)
+        .change_context(OpenTelemetryInitError)
+        .attach_printable_lazy(|| format!("failed to bind log filter admin to {address}"))?
+        .serve(make_service_fn(move |_| {
+            let handle = handle.clone();
+            let token = token.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let handle = handle.clone();
+                    let token = token.clone();
+                    async move {
+                        Ok::<_, Infallible>(
+                            log_filter_response(&handle, token.as_deref(), req).await,
+                        )
+                    }
+                }))
+            }
+        }));
+
+    tokio::spawn(async move {
+        if let Err(err) = server.await {
+            tracing::error!(err = ?err, "log filter admin error");
+        }
+    });
+
+    Ok(())
+}
+
+async fn log_filter_response<S: 'static>(
+    handle: &Handle<EnvFilter, S>,
+    token: Option<&str>,
+    req: Request<Body>,
+) -> Response<Body> {
+    if req.uri().path() != LOG_FILTER_PATH {
+        return response(StatusCode::NOT_FOUND, Body::empty());
+    }
+
+    match *req.method() {
+        Method::GET => match handle.with_current(|filter| filter.to_string()) {
+            Ok(filter) => response(StatusCode::OK, Body::from(filter)),
+            Err(err) => response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Body::from(err.to_string()),
+            ),
+        },
+        Method::PUT => {
+            if !is_authorized(token, &req) {
+                return response(StatusCode::UNAUTHORIZED, Body::empty());
+            }
+            let body = match hyper::body::to_bytes(req.into_body()).await {
+                Ok(body) => body,
+                Err(err) => return response(StatusCode::BAD_REQUEST, Body::from(err.to_string())),
+            };
+            let directives = String::from_utf8_lossy(&body);
+            let filter = match EnvFilter::try_new(directives.trim()) {
+                Ok(filter) => filter,
+                Err(err) => return response(StatusCode::BAD_REQUEST, Body::from(err.to_string())),
+            };
+            let new_filter = filter.to_string();
+            match handle.reload(filter) {
+                Ok(_) => {
+                    tracing::info!(filter = %new_filter, "log filter updated");
+                    response(StatusCode::OK, Body::from(new_filter))
+                }
+                Err(err) => response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Body::from(err.to_string()),
+                ),
+            }
+        }
+        _ => response(StatusCode::METHOD_NOT_ALLOWED, Body::empty()),
+    }
+}
+
+/// Returns true if the request sends the expected bearer token, if any.
+fn is_authorized(token: Option<&str>, req: &Request<Body>) -> bool {
+    let Some(token) = token else {
+        return true;
+    };
+    req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|value| value == token)
+        .unwrap_or(false)
+}
+
+fn response(status: StatusCode, body: Body) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(body)
+        .expect("valid response")
+}
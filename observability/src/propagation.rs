@@ -0,0 +1,29 @@
+//! Propagate the trace context across services.
+use hyper::HeaderMap;
+use opentelemetry::{global, propagation::Extractor, Context};
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Sets the parent of the span to the remote context in the request headers.
+///
+/// The context is read from the W3C `traceparent` and `tracestate` headers.
+pub fn set_parent_from_headers(span: &Span, headers: &HeaderMap) {
+    let parent = extract_context(headers);
+    span.set_parent(parent);
+}
+
+fn extract_context(headers: &HeaderMap) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)))
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl<'a> Extractor for HeaderExtractor<'a> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
@@ -0,0 +1,99 @@
+//! Process and tokio runtime metrics.
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use opentelemetry::{global, metrics::MetricsError};
+use tokio::runtime::Handle;
+
+/// How often the scheduler latency is sampled.
+const SCHEDULER_LATENCY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Registers the process and tokio runtime gauges.
+///
+/// The following metrics are exported:
+///
+///  - `process_resident_memory_bytes`: resident set size (Linux only).
+///  - `process_open_fds`: number of open file descriptors (Linux only).
+///  - `tokio_workers`: number of runtime worker threads.
+///  - `tokio_alive_tasks`: number of alive tasks.
+///  - `tokio_scheduler_latency_seconds`: how late a timer task is woken up,
+///    a sign that the runtime is overloaded or blocked.
+///
+/// The tokio task metrics are only available when building with
+/// `RUSTFLAGS="--cfg tokio_unstable"`.
+pub fn register_runtime_metrics(handle: Handle) -> Result<(), MetricsError> {
+    let meter = global::meter("runtime");
+    let rss_gauge = meter
+        .u64_observable_gauge("process_resident_memory_bytes")
+        .init();
+    let fds_gauge = meter.u64_observable_gauge("process_open_fds").init();
+    #[cfg(tokio_unstable)]
+    let workers_gauge = meter.u64_observable_gauge("tokio_workers").init();
+    #[cfg(tokio_unstable)]
+    let alive_tasks_gauge = meter.u64_observable_gauge("tokio_alive_tasks").init();
+    let scheduler_latency_gauge = meter
+        .f64_observable_gauge("tokio_scheduler_latency_seconds")
+        .init();
+
+    let scheduler_latency = Arc::new(AtomicU64::default());
+    handle.spawn(sample_scheduler_latency(scheduler_latency.clone()));
+
+    meter.register_callback(move |cx| {
+        if let Some(rss) = resident_memory_bytes() {
+            rss_gauge.observe(cx, rss, &[]);
+        }
+        if let Some(fds) = open_fds() {
+            fds_gauge.observe(cx, fds, &[]);
+        }
+
+        #[cfg(tokio_unstable)]
+        {
+            let metrics = handle.metrics();
+            workers_gauge.observe(cx, metrics.num_workers() as u64, &[]);
+            alive_tasks_gauge.observe(cx, metrics.active_tasks_count() as u64, &[]);
+        }
+
+        let latency = Duration::from_micros(scheduler_latency.load(Ordering::Relaxed));
+        scheduler_latency_gauge.observe(cx, latency.as_secs_f64(), &[]);
+    })
+}
+
+/// Measures how much later than requested a sleeping task is woken up.
+async fn sample_scheduler_latency(latency: Arc<AtomicU64>) {
+    loop {
+        let start = Instant::now();
+        tokio::time::sleep(SCHEDULER_LATENCY_INTERVAL).await;
+        let delay = start.elapsed().saturating_sub(SCHEDULER_LATENCY_INTERVAL);
+        latency.store(delay.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn resident_memory_bytes() -> Option<u64> {
+    // the resident set size is reported in kB, e.g. `VmRSS:     1234 kB`.
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(target_os = "linux")]
+fn open_fds() -> Option<u64> {
+    let entries = std::fs::read_dir("/proc/self/fd").ok()?;
+    Some(entries.count() as u64)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn resident_memory_bytes() -> Option<u64> {
+    None
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_fds() -> Option<u64> {
+    None
+}
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use apibara_observability::init_opentelemetry;
+use apibara_observability::{init_opentelemetry_with_config, OpenTelemetryConfig};
 use apibara_operator::{
     configuration::{Configuration, SinkConfiguration},
     controller,
@@ -89,9 +89,11 @@ async fn start(args: StartArgs) -> Result<(), OperatorError> {
 
 #[tokio::main]
 async fn main() -> Result<(), OperatorError> {
-    init_opentelemetry()
-        .change_context(OperatorError)
-        .attach_printable("failed to initialize opentelemetry")?;
+    init_opentelemetry_with_config(
+        OpenTelemetryConfig::from_env().with_service_version(env!("CARGO_PKG_VERSION")),
+    )
+    .change_context(OperatorError)
+    .attach_printable("failed to initialize opentelemetry")?;
     let args = Cli::parse();
 
     match args.command {
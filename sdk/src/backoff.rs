@@ -0,0 +1,180 @@
+//! Retry policy used when connecting to the server.
+use std::{error::Error as StdError, fmt, io, sync::Arc, time::Duration};
+
+use exponential_backoff::Backoff;
+
+/// Information about a failed connection attempt that is about to be retried.
+#[derive(Debug)]
+pub struct RetryAttempt<'a> {
+    /// The attempt that failed, starting from 1.
+    pub attempt: u32,
+    /// How long the client waits before the next attempt.
+    pub delay: Duration,
+    /// The connection error.
+    pub error: &'a tonic::transport::Error,
+}
+
+type OnRetry = Arc<dyn Fn(&RetryAttempt<'_>) + Send + Sync>;
+
+/// How [crate::ClientBuilder::connect] retries transient connection failures.
+///
+/// The delay between attempts grows exponentially from `min_delay` to
+/// `max_delay`, with some random jitter to avoid all clients reconnecting
+/// at the same time. Errors that are not transient, for example invalid TLS
+/// certificates, are returned immediately.
+#[derive(Clone)]
+pub struct BackoffPolicy {
+    max_retries: u32,
+    min_delay: Duration,
+    max_delay: Duration,
+    factor: u32,
+    jitter: f32,
+    on_retry: Option<OnRetry>,
+}
+
+impl BackoffPolicy {
+    /// A policy that never retries.
+    pub fn none() -> Self {
+        BackoffPolicy {
+            max_retries: 0,
+            ..BackoffPolicy::default()
+        }
+    }
+
+    /// Set the maximum number of retries after the first attempt.
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Set the delay before the first retry.
+    pub fn with_min_delay(mut self, min_delay: Duration) -> Self {
+        self.min_delay = min_delay;
+        self
+    }
+
+    /// Set the maximum delay between retries.
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Set how much the delay grows after each retry.
+    pub fn with_factor(mut self, factor: u32) -> Self {
+        self.factor = factor;
+        self
+    }
+
+    /// Set the jitter, as a fraction of the delay between `0.0` and `1.0`.
+    pub fn with_jitter(mut self, jitter: f32) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Call `on_retry` before waiting for the next attempt.
+    ///
+    /// Use it to log or record metrics about connection failures.
+    pub fn with_on_retry<F>(mut self, on_retry: F) -> Self
+    where
+        F: Fn(&RetryAttempt<'_>) + Send + Sync + 'static,
+    {
+        self.on_retry = Some(Arc::new(on_retry));
+        self
+    }
+
+    /// Returns the delays between attempts.
+    pub(crate) fn delays(&self) -> Vec<Duration> {
+        let mut backoff = Backoff::new(self.max_retries, self.min_delay, Some(self.max_delay));
+        backoff.set_factor(self.factor);
+        backoff.set_jitter(self.jitter);
+        (&backoff)
+            .into_iter()
+            .take(self.max_retries as usize)
+            .collect()
+    }
+
+    pub(crate) fn notify_retry(&self, attempt: &RetryAttempt<'_>) {
+        if let Some(on_retry) = &self.on_retry {
+            on_retry(attempt);
+        }
+    }
+}
+
+impl Default for BackoffPolicy {
+    fn default() -> Self {
+        BackoffPolicy {
+            max_retries: 5,
+            min_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            factor: 2,
+            jitter: 0.3,
+            on_retry: None,
+        }
+    }
+}
+
+impl fmt::Debug for BackoffPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BackoffPolicy")
+            .field("max_retries", &self.max_retries)
+            .field("min_delay", &self.min_delay)
+            .field("max_delay", &self.max_delay)
+            .field("factor", &self.factor)
+            .field("jitter", &self.jitter)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Returns true if the connection error is worth retrying.
+///
+/// DNS failures, refused and reset connections, and timeouts are reported
+/// as io errors by the connector. Errors caused by invalid input or data,
+/// like TLS certificate errors, will fail again on retry.
+pub(crate) fn is_transient(error: &tonic::transport::Error) -> bool {
+    let mut source = error.source();
+    while let Some(err) = source {
+        if let Some(err) = err.downcast_ref::<io::Error>() {
+            return !matches!(
+                err.kind(),
+                io::ErrorKind::InvalidInput
+                    | io::ErrorKind::InvalidData
+                    | io::ErrorKind::PermissionDenied
+                    | io::ErrorKind::Unsupported
+            );
+        }
+        if let Some(err) = err.downcast_ref::<hyper::Error>() {
+            if err.is_connect() || err.is_timeout() {
+                return true;
+            }
+        }
+        source = err.source();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::BackoffPolicy;
+
+    #[test]
+    fn test_backoff_delays() {
+        let policy = BackoffPolicy::default()
+            .with_max_retries(4)
+            .with_min_delay(Duration::from_millis(100))
+            .with_max_delay(Duration::from_millis(500))
+            .with_jitter(0.0);
+
+        let delays = policy.delays();
+        assert_eq!(delays.len(), 4);
+        assert!(delays.windows(2).all(|w| w[0] <= w[1]));
+        assert!(delays.iter().all(|d| *d <= Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn test_backoff_none() {
+        let policy = BackoffPolicy::none();
+        assert_eq!(policy.delays().len(), 0);
+    }
+}
@@ -16,6 +16,10 @@ pub struct Configuration<F: Message + Default> {
     pub finality: Option<DataFinality>,
     /// The data filter.
     pub filter: F,
+    /// Maximum number of batches sent by the server before the client
+    /// acknowledges them.
+    #[serde(default)]
+    pub max_unacknowledged_batches: Option<u64>,
 }
 
 pub type ConfigurationClient<F> = mpsc::Sender<Configuration<F>>;
@@ -48,6 +52,7 @@ where
             starting_cursor,
             finality,
             filter,
+            max_unacknowledged_batches: None,
         }
     }
 
@@ -63,6 +68,8 @@ where
             finality: self.finality.map(Into::into),
             filter,
             multi_filter: Vec::default(),
+            max_unacknowledged_batches: self.max_unacknowledged_batches,
+            acknowledge: None,
         })
     }
 
@@ -93,6 +100,14 @@ where
         self
     }
 
+    /// Limit the number of batches sent before the client acknowledges them.
+    ///
+    /// Use [crate::DataStream::acknowledge] to acknowledge data once processed.
+    pub fn with_max_unacknowledged_batches(mut self, max: u64) -> Self {
+        self.max_unacknowledged_batches = Some(max);
+        self
+    }
+
     /// Configure the data filter.
     pub fn with_filter<G>(mut self, filter_closure: G) -> Self
     where
@@ -114,6 +129,7 @@ where
             starting_cursor: None,
             finality: None,
             filter: F::default(),
+            max_unacknowledged_batches: None,
         }
     }
 }
@@ -208,4 +224,19 @@ mod tests {
         assert_eq!(4, config.filter.transactions.len());
         assert_eq!(4, config.filter.events.len());
     }
+
+    #[test]
+    fn test_config_with_max_unacknowledged_batches() {
+        let request = Configuration::<Filter>::default()
+            .to_stream_data_request()
+            .unwrap();
+        assert!(request.max_unacknowledged_batches.is_none());
+
+        let request = Configuration::<Filter>::default()
+            .with_max_unacknowledged_batches(5)
+            .to_stream_data_request()
+            .unwrap();
+        assert_eq!(Some(5), request.max_unacknowledged_batches);
+        assert!(request.acknowledge.is_none());
+    }
 }
@@ -1,4 +1,7 @@
+pub mod backoff;
 pub mod configuration;
+pub mod merge;
+mod tls;
 
 use core::fmt;
 use std::{
@@ -14,6 +17,7 @@ use apibara_core::node::v1alpha2::{
 };
 use error_stack::{Result, ResultExt};
 use futures::Stream;
+use opentelemetry::{global, propagation::Injector};
 use pin_project::pin_project;
 use prost::Message;
 use serde::{Deserialize, Serialize};
@@ -21,12 +25,13 @@ use tokio::sync::mpsc::{self, Sender};
 use tokio_stream::{wrappers::ReceiverStream, StreamExt, Timeout};
 use tonic::{
     codegen::InterceptedService,
-    metadata::{AsciiMetadataValue, KeyAndValueRef},
+    metadata::{AsciiMetadataKey, AsciiMetadataValue, KeyAndValueRef},
     service::Interceptor,
     transport::Channel,
     Streaming,
 };
-use tracing::debug;
+use tracing::{debug, warn};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 // Re-export tonic Uri
 pub use http::uri::InvalidUri;
@@ -41,7 +46,11 @@ pub use tonic::{
 pub type MetadataKey = tonic::metadata::MetadataKey<tonic::metadata::Ascii>;
 pub type MetadataValue = tonic::metadata::MetadataValue<tonic::metadata::Ascii>;
 
+pub use crate::backoff::{BackoffPolicy, RetryAttempt};
 pub use crate::configuration::Configuration;
+pub use crate::merge::{MergedDataStream, MergedMessage};
+
+use crate::tls::TlsOptions;
 
 #[derive(Debug)]
 pub struct ClientError;
@@ -71,14 +80,52 @@ pub enum DataMessage<D: Message + Default> {
         /// The batch of data.
         batch: Vec<D>,
     },
+    /// Some items in the batch ending at `end_cursor` could not be decoded.
+    ///
+    /// Only sent with [DecodeErrorPolicy::SkipWithCallback], right before the
+    /// batch data without the failed items.
+    DecodeError {
+        /// The batch end cursor.
+        end_cursor: Cursor,
+        /// The items that failed to decode.
+        errors: Vec<BatchItemError>,
+    },
     /// Invalidate all data received after the given cursor.
     Invalidate {
         /// The cursor.
         cursor: Option<Cursor>,
     },
+    /// All data received up to and including the given cursor is now finalized.
+    Finalize {
+        /// The cursor.
+        cursor: Option<Cursor>,
+    },
     Heartbeat,
 }
 
+/// A batch item that could not be decoded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchItemError {
+    /// The index of the item in the batch.
+    pub index: usize,
+    /// The decode error message.
+    pub error: String,
+}
+
+/// How [DataStream] handles batch items that can't be decoded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DecodeErrorPolicy {
+    /// Return an error from the stream.
+    FailFast,
+    /// Skip the items that can't be decoded.
+    #[default]
+    Skip,
+    /// Skip the items that can't be decoded and send a [DataMessage::DecodeError]
+    /// with the errors before the batch.
+    SkipWithCallback,
+}
+
 /// Data stream client.
 #[derive(Clone)]
 pub struct StreamClient {
@@ -94,6 +141,8 @@ pub struct ClientBuilder {
     max_message_size: Option<usize>,
     metadata: MetadataMap,
     timeout: Duration,
+    backoff: BackoffPolicy,
+    tls: TlsOptions,
 }
 
 /// A stream of on-chain data.
@@ -111,6 +160,8 @@ where
     #[pin]
     inner: Pin<Box<Timeout<Streaming<StreamDataResponse>>>>,
     inner_tx: Sender<StreamDataRequest>,
+    decode_error_policy: DecodeErrorPolicy,
+    pending_message: Option<DataMessage<D>>,
     _data: PhantomData<D>,
 }
 
@@ -151,15 +202,54 @@ impl ClientBuilder {
         self
     }
 
+    /// Set how to retry transient failures when connecting to the server.
+    ///
+    /// Use [BackoffPolicy::none] to return the first connection error.
+    pub fn with_backoff_policy(mut self, backoff: BackoffPolicy) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Trust the CA certificates in the given PEM, for servers using a private CA.
+    ///
+    /// The PEM can contain multiple certificates. The system roots are still trusted.
+    pub fn with_ca_certificate(mut self, pem: impl Into<Vec<u8>>) -> Self {
+        self.tls.ca_certificate = Some(pem.into());
+        self
+    }
+
+    /// Authenticate with the given client certificate and key (mTLS), both PEM encoded.
+    pub fn with_client_identity(
+        mut self,
+        cert: impl Into<Vec<u8>>,
+        key: impl Into<Vec<u8>>,
+    ) -> Self {
+        self.tls.identity = Some((cert.into(), key.into()));
+        self
+    }
+
+    /// Use `domain_name` for SNI and certificate verification instead of the url host.
+    pub fn with_tls_domain_name(mut self, domain_name: impl Into<String>) -> Self {
+        self.tls.domain_name = Some(domain_name.into());
+        self
+    }
+
+    /// Don't verify the server certificate.
+    ///
+    /// This is insecure and should only be used in development.
+    pub fn with_insecure_skip_verify(mut self, insecure_skip_verify: bool) -> Self {
+        self.tls.insecure_skip_verify = insecure_skip_verify;
+        self
+    }
+
     /// Create and connect to the stream at the given url.
     ///
     /// If a configuration was provided, the client will immediately send it to the server upon
     /// connecting.
+    ///
+    /// Transient connection failures are retried according to the backoff policy.
     pub async fn connect(self, url: Uri) -> Result<StreamClient, ClientError> {
-        let channel = Channel::builder(url)
-            .connect()
-            .await
-            .change_context(ClientError)?;
+        let channel = self.connect_channel(url).await?;
         let interceptor = MetadataInterceptor::new(self.metadata, self.token)?;
 
         let mut default_client = ProtoStreamClient::with_interceptor(channel, interceptor);
@@ -174,6 +264,43 @@ impl ClientBuilder {
             timeout: self.timeout,
         })
     }
+
+    async fn connect_channel(&self, url: Uri) -> Result<Channel, ClientError> {
+        let endpoint = self.tls.configure_endpoint(Channel::builder(url))?;
+        let connector = self.tls.insecure_connector()?;
+        let mut delays = self.backoff.delays().into_iter();
+        let mut attempt = 1;
+        loop {
+            let result = match &connector {
+                Some(connector) => endpoint.connect_with_connector(connector.clone()).await,
+                None => endpoint.connect().await,
+            };
+            let error = match result {
+                Ok(channel) => return Ok(channel),
+                Err(error) => error,
+            };
+
+            let delay = match delays.next() {
+                Some(delay) if backoff::is_transient(&error) => delay,
+                _ => {
+                    return Err(error)
+                        .change_context(ClientError)
+                        .attach_printable_lazy(|| {
+                            format!("failed to connect after {attempt} attempts")
+                        });
+                }
+            };
+
+            warn!(attempt, delay = ?delay, error = ?error, "failed to connect, retrying");
+            self.backoff.notify_retry(&RetryAttempt {
+                attempt,
+                delay,
+                error: &error,
+            });
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
 }
 
 impl Default for ClientBuilder {
@@ -183,6 +310,8 @@ impl Default for ClientBuilder {
             max_message_size: None,
             metadata: MetadataMap::default(),
             timeout: Duration::from_secs(45),
+            backoff: BackoffPolicy::default(),
+            tls: TlsOptions::default(),
         }
     }
 }
@@ -218,6 +347,8 @@ impl StreamClient {
             configuration_stream: configuration,
             inner: inner_stream,
             inner_tx,
+            decode_error_policy: DecodeErrorPolicy::default(),
+            pending_message: None,
             _data: PhantomData,
         };
 
@@ -240,6 +371,8 @@ impl StreamClient {
             finality: configuration.finality.map(|f| f as i32),
             filter: configuration.filter.encode_to_vec(),
             multi_filter: Vec::default(),
+            max_unacknowledged_batches: None,
+            acknowledge: None,
         };
 
         let inner_stream = self
@@ -285,6 +418,8 @@ impl StreamClient {
             finality: configuration.finality.map(|f| f as i32),
             filter: Vec::default(),
             multi_filter,
+            max_unacknowledged_batches: None,
+            acknowledge: None,
         };
 
         let inner_stream = self
@@ -317,6 +452,43 @@ impl StreamClient {
     }
 }
 
+impl<F, D, C> DataStream<F, D, C>
+where
+    F: Message + Default,
+    D: Message + Default,
+    C: Stream<Item = Configuration<F>> + Send + Sync + 'static,
+{
+    /// Set how to handle batch items that can't be decoded.
+    ///
+    /// Defaults to [DecodeErrorPolicy::Skip].
+    pub fn with_decode_error_policy(mut self, policy: DecodeErrorPolicy) -> Self {
+        self.decode_error_policy = policy;
+        self
+    }
+
+    /// Acknowledges all data up to and including the given cursor.
+    ///
+    /// Only needed if the configuration limits the number of unacknowledged
+    /// batches, in that case the server stops sending data until the client
+    /// acknowledges it.
+    ///
+    /// Waits for space in the request queue, returns an error if the stream
+    /// is closed.
+    pub async fn acknowledge(&self, cursor: Cursor) -> Result<(), ClientError> {
+        let request = StreamDataRequest {
+            stream_id: Some(self.stream_id),
+            acknowledge: Some(cursor),
+            ..StreamDataRequest::default()
+        };
+
+        self.inner_tx
+            .send(request)
+            .await
+            .change_context(ClientError)
+            .attach_printable("failed to send acknowledgement, stream closed")
+    }
+}
+
 impl<F, D, C> Stream for DataStream<F, D, C>
 where
     F: Message + Default,
@@ -327,6 +499,10 @@ where
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         let this = self.project();
+        if let Some(message) = this.pending_message.take() {
+            return Poll::Ready(Some(Ok(message)));
+        }
+
         match this.configuration_stream.poll_next(cx) {
             Poll::Ready(None) => return Poll::Ready(None),
             Poll::Ready(Some(configuration)) => {
@@ -338,6 +514,8 @@ where
                     finality: configuration.finality.map(|f| f as i32),
                     filter: configuration.filter.encode_to_vec(),
                     multi_filter: Vec::default(),
+                    max_unacknowledged_batches: configuration.max_unacknowledged_batches,
+                    acknowledge: None,
                 };
 
                 this.inner_tx
@@ -367,18 +545,24 @@ where
                             Poll::Pending
                         }
                         Some(stream_data_response::Message::Data(data)) => {
-                            let batch = data
-                                .data
-                                .into_iter()
-                                .map(|b| D::decode(b.as_slice()))
-                                .filter_map(|b| b.ok())
-                                .collect::<Vec<D>>();
+                            let (batch, errors) =
+                                decode_batch::<D>(data.data, *this.decode_error_policy)?;
+                            let end_cursor = data.end_cursor.unwrap_or_default();
                             let message = DataMessage::Data {
                                 cursor: data.cursor,
-                                end_cursor: data.end_cursor.unwrap_or_default(),
+                                end_cursor: end_cursor.clone(),
                                 finality: DataFinality::from_i32(data.finality).unwrap_or_default(),
                                 batch,
                             };
+
+                            if errors.is_empty()
+                                || *this.decode_error_policy != DecodeErrorPolicy::SkipWithCallback
+                            {
+                                return Poll::Ready(Some(Ok(message)));
+                            }
+
+                            *this.pending_message = Some(message);
+                            let message = DataMessage::DecodeError { end_cursor, errors };
                             Poll::Ready(Some(Ok(message)))
                         }
                         Some(stream_data_response::Message::Invalidate(invalidate)) => {
@@ -387,6 +571,12 @@ where
                             };
                             Poll::Ready(Some(Ok(message)))
                         }
+                        Some(stream_data_response::Message::Finalize(finalize)) => {
+                            let message = DataMessage::Finalize {
+                                cursor: finalize.cursor,
+                            };
+                            Poll::Ready(Some(Ok(message)))
+                        }
                         Some(stream_data_response::Message::Heartbeat(_)) => {
                             debug!("received heartbeat");
                             cx.waker().wake_by_ref();
@@ -425,6 +615,12 @@ impl<D: Message + Default> DataMessage<D> {
                 };
                 Some(message)
             }
+            Some(stream_data_response::Message::Finalize(finalize)) => {
+                let message = DataMessage::Finalize {
+                    cursor: finalize.cursor,
+                };
+                Some(message)
+            }
         }
     }
 }
@@ -472,6 +668,12 @@ where
                         };
                         Poll::Ready(Some(Ok(message)))
                     }
+                    Some(stream_data_response::Message::Finalize(finalize)) => {
+                        let message = DataMessage::Finalize {
+                            cursor: finalize.cursor,
+                        };
+                        Poll::Ready(Some(Ok(message)))
+                    }
                     Some(stream_data_response::Message::Heartbeat(_)) => {
                         debug!("received heartbeat");
                         cx.waker().wake_by_ref();
@@ -529,10 +731,53 @@ impl Interceptor for MetadataInterceptor {
             req_meta.insert(key, value);
         }
 
+        // propagate the current trace context to the server.
+        let context = tracing::Span::current().context();
+        global::get_text_map_propagator(|propagator| {
+            propagator.inject_context(&context, &mut MetadataInjector(req_meta))
+        });
+
         Ok(request)
     }
 }
 
+struct MetadataInjector<'a>(&'a mut MetadataMap);
+
+impl<'a> Injector for MetadataInjector<'a> {
+    fn set(&mut self, key: &str, value: String) {
+        let key = AsciiMetadataKey::from_bytes(key.as_bytes());
+        let value = AsciiMetadataValue::try_from(value);
+        if let (Ok(key), Ok(value)) = (key, value) {
+            self.0.insert(key, value);
+        }
+    }
+}
+
+/// Decodes the batch items, returning the items that failed to decode
+/// according to the policy.
+fn decode_batch<D: Message + Default>(
+    data: Vec<Vec<u8>>,
+    policy: DecodeErrorPolicy,
+) -> Result<(Vec<D>, Vec<BatchItemError>), ClientError> {
+    let mut batch = Vec::with_capacity(data.len());
+    let mut errors = Vec::default();
+    for (index, item) in data.into_iter().enumerate() {
+        match D::decode(item.as_slice()) {
+            Ok(item) => batch.push(item),
+            Err(err) if policy == DecodeErrorPolicy::FailFast => {
+                return Err(err)
+                    .change_context(ClientError)
+                    .attach_printable_lazy(|| format!("failed to decode batch item {index}"));
+            }
+            Err(err) => errors.push(BatchItemError {
+                index,
+                error: err.to_string(),
+            }),
+        }
+    }
+    Ok((batch, errors))
+}
+
 fn status_to_error<T>(status: tonic::Status) -> Result<T, ClientError> {
     use tonic::Code;
 
@@ -540,9 +785,41 @@ fn status_to_error<T>(status: tonic::Status) -> Result<T, ClientError> {
         Code::Unauthenticated => Err(status)
             .attach_printable("hint: did you forget to set the authentication token?")
             .change_context(ClientError),
+        Code::NotFound => Err(status)
+            .attach_printable("hint: is the starting cursor on the same chain as the node?")
+            .change_context(ClientError),
         Code::OutOfRange => Err(status)
             .attach_printable("hint: you should increase the maximum message size")
             .change_context(ClientError),
         _ => Err(status).change_context(ClientError),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use apibara_core::node::v1alpha2::Cursor;
+    use prost::Message;
+
+    use super::{decode_batch, DecodeErrorPolicy};
+
+    fn new_batch() -> Vec<Vec<u8>> {
+        let cursor = Cursor {
+            order_key: 1,
+            unique_key: vec![1, 2, 3],
+        };
+        vec![cursor.encode_to_vec(), vec![0xff], cursor.encode_to_vec()]
+    }
+
+    #[test]
+    fn test_decode_batch_skip() {
+        let (batch, errors) = decode_batch::<Cursor>(new_batch(), DecodeErrorPolicy::Skip).unwrap();
+        assert_eq!(batch.len(), 2);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].index, 1);
+    }
+
+    #[test]
+    fn test_decode_batch_fail_fast() {
+        assert!(decode_batch::<Cursor>(new_batch(), DecodeErrorPolicy::FailFast).is_err());
+    }
+}
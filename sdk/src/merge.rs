@@ -0,0 +1,309 @@
+//! Merge data streams from multiple networks.
+//!
+//! Indexers that correlate activity across networks need to process data in
+//! the order it happened. [MergedDataStream] combines several data streams
+//! into a single stream ordered by block timestamp, while keeping track of
+//! each stream's cursor independently.
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use apibara_core::node::v1alpha2::Cursor;
+use error_stack::Result;
+use futures::{Future, Stream, StreamExt};
+use prost::Message;
+use tokio::time::Sleep;
+
+use crate::{ClientError, DataMessage};
+
+/// A message produced by one of the merged streams.
+#[derive(Debug)]
+pub struct MergedMessage<K, T> {
+    /// The key of the stream that produced the message.
+    pub stream: K,
+    /// The message.
+    pub message: T,
+}
+
+/// A stream that merges data streams, ordering data by timestamp.
+///
+/// Data is only sent once all streams have data buffered, so that messages
+/// are strictly ordered. Since a stream at the chain head may not produce data
+/// for a while, use [MergedDataStream::with_max_wait] to send the buffered
+/// data after a delay.
+///
+/// Messages without a timestamp (invalidate and finalize messages, empty
+/// batches) are sent as soon as they're received.
+pub struct MergedDataStream<K, T> {
+    sources: Vec<Source<K, T>>,
+    cursors: HashMap<K, Cursor>,
+    max_wait: Option<Duration>,
+    wait: Option<Pin<Box<Sleep>>>,
+}
+
+type EntryStream<T> = Pin<Box<dyn Stream<Item = Result<Entry<T>, ClientError>> + Send>>;
+
+struct Source<K, T> {
+    key: K,
+    stream: EntryStream<T>,
+    buffered: Option<Entry<T>>,
+    finished: bool,
+}
+
+struct Entry<T> {
+    timestamp: Option<u64>,
+    cursor: Option<Cursor>,
+    message: T,
+}
+
+impl<K, T> MergedDataStream<K, T>
+where
+    K: Clone + Eq + Hash,
+    T: 'static,
+{
+    pub fn new() -> Self {
+        MergedDataStream {
+            sources: Vec::default(),
+            cursors: HashMap::default(),
+            max_wait: None,
+            wait: None,
+        }
+    }
+
+    /// Send buffered data if a stream doesn't produce data within this duration.
+    pub fn with_max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = Some(max_wait);
+        self
+    }
+
+    /// Adds a data stream identified by `key`.
+    ///
+    /// `timestamp` returns the timestamp of a block, used to order data
+    /// between streams. `into_message` converts the stream messages into the
+    /// merged message type, for example an enum with one variant per network.
+    pub fn add_stream<D, S>(
+        mut self,
+        key: K,
+        stream: S,
+        timestamp: impl Fn(&D) -> Option<u64> + Send + 'static,
+        into_message: impl Fn(DataMessage<D>) -> T + Send + 'static,
+    ) -> Self
+    where
+        D: Message + Default + 'static,
+        S: Stream<Item = Result<DataMessage<D>, ClientError>> + Send + 'static,
+    {
+        let stream = stream.map(move |message| {
+            let message = message?;
+            let (timestamp, cursor) = match &message {
+                DataMessage::Data {
+                    end_cursor, batch, ..
+                } => (batch.first().and_then(&timestamp), Some(end_cursor.clone())),
+                DataMessage::Invalidate { cursor } => (None, cursor.clone()),
+                DataMessage::Finalize { .. }
+                | DataMessage::DecodeError { .. }
+                | DataMessage::Heartbeat => (None, None),
+            };
+
+            Ok(Entry {
+                timestamp,
+                cursor,
+                message: into_message(message),
+            })
+        });
+
+        self.sources.push(Source {
+            key,
+            stream: Box::pin(stream),
+            buffered: None,
+            finished: false,
+        });
+        self
+    }
+
+    /// Returns the cursor of the last data sent by the given stream.
+    ///
+    /// Use it as the starting cursor of that stream when restarting.
+    pub fn cursor(&self, key: &K) -> Option<&Cursor> {
+        self.cursors.get(key)
+    }
+
+    fn send(&mut self, key: K, entry: Entry<T>) -> MergedMessage<K, T> {
+        if let Some(cursor) = entry.cursor {
+            self.cursors.insert(key.clone(), cursor);
+        }
+        self.wait = None;
+        MergedMessage {
+            stream: key,
+            message: entry.message,
+        }
+    }
+
+    /// Sends the buffered entry with the earliest timestamp.
+    fn send_earliest(&mut self) -> Option<MergedMessage<K, T>> {
+        let source = self
+            .sources
+            .iter_mut()
+            .filter(|source| source.buffered.is_some())
+            .min_by_key(|source| source.buffered.as_ref().and_then(|entry| entry.timestamp))?;
+        let key = source.key.clone();
+        let entry = source.buffered.take()?;
+        Some(self.send(key, entry))
+    }
+}
+
+impl<K, T> Default for MergedDataStream<K, T>
+where
+    K: Clone + Eq + Hash,
+    T: 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The sources are boxed, so the stream doesn't rely on pinning.
+impl<K, T> Unpin for MergedDataStream<K, T> {}
+
+impl<K, T> Stream for MergedDataStream<K, T>
+where
+    K: Clone + Eq + Hash,
+    T: 'static,
+{
+    type Item = Result<MergedMessage<K, T>, ClientError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        for index in 0..this.sources.len() {
+            let source = &mut this.sources[index];
+            if source.finished || source.buffered.is_some() {
+                continue;
+            }
+
+            match source.stream.poll_next_unpin(cx) {
+                Poll::Pending => {}
+                Poll::Ready(None) => source.finished = true,
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err))),
+                Poll::Ready(Some(Ok(entry))) => {
+                    if entry.timestamp.is_some() {
+                        source.buffered = Some(entry);
+                    } else {
+                        let key = source.key.clone();
+                        return Poll::Ready(Some(Ok(this.send(key, entry))));
+                    }
+                }
+            }
+        }
+
+        let has_buffered = this.sources.iter().any(|source| source.buffered.is_some());
+        if !has_buffered {
+            this.wait = None;
+            if this.sources.iter().all(|source| source.finished) {
+                return Poll::Ready(None);
+            }
+            return Poll::Pending;
+        }
+
+        let all_ready = this
+            .sources
+            .iter()
+            .all(|source| source.finished || source.buffered.is_some());
+        if all_ready {
+            return Poll::Ready(this.send_earliest().map(Ok));
+        }
+
+        let Some(max_wait) = this.max_wait else {
+            return Poll::Pending;
+        };
+
+        let wait = this
+            .wait
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(max_wait)));
+        match wait.as_mut().poll(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(_) => Poll::Ready(this.send_earliest().map(Ok)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use apibara_core::{
+        node::v1alpha2::{Cursor, DataFinality},
+        starknet::v1alpha2::{Block, BlockHeader},
+    };
+    use futures::{stream, StreamExt};
+
+    use super::MergedDataStream;
+    use crate::DataMessage;
+
+    fn new_data(block_number: u64, timestamp: i64) -> DataMessage<Block> {
+        let mut header = BlockHeader {
+            block_number,
+            timestamp: Some(Default::default()),
+            ..BlockHeader::default()
+        };
+        header.timestamp.as_mut().unwrap().seconds = timestamp;
+
+        DataMessage::Data {
+            cursor: None,
+            end_cursor: Cursor {
+                order_key: block_number,
+                unique_key: vec![],
+            },
+            finality: DataFinality::DataStatusFinalized,
+            batch: vec![Block {
+                header: Some(header),
+                ..Block::default()
+            }],
+        }
+    }
+
+    fn block_timestamp(block: &Block) -> Option<u64> {
+        block
+            .header
+            .as_ref()
+            .and_then(|header| header.timestamp.as_ref())
+            .map(|timestamp| timestamp.seconds as u64)
+    }
+
+    #[tokio::test]
+    async fn test_merge_streams_by_timestamp() {
+        let first = stream::iter(vec![Ok(new_data(1, 10)), Ok(new_data(2, 30))]);
+        let second = stream::iter(vec![
+            Ok(new_data(100, 5)),
+            Ok(new_data(101, 20)),
+            Ok(new_data(102, 40)),
+        ]);
+
+        let mut merged = MergedDataStream::new()
+            .add_stream("first", first, block_timestamp, |m| m)
+            .add_stream("second", second, block_timestamp, |m| m);
+
+        let mut order = Vec::new();
+        while let Some(message) = merged.next().await {
+            let message = message.unwrap();
+            let DataMessage::Data { end_cursor, .. } = message.message else {
+                panic!("expected data message");
+            };
+            order.push((message.stream, end_cursor.order_key));
+        }
+
+        assert_eq!(
+            order,
+            vec![
+                ("second", 100),
+                ("first", 1),
+                ("second", 101),
+                ("first", 2),
+                ("second", 102),
+            ]
+        );
+        assert_eq!(merged.cursor(&"first").unwrap().order_key, 2);
+        assert_eq!(merged.cursor(&"second").unwrap().order_key, 102);
+    }
+}
@@ -0,0 +1,127 @@
+//! TLS options used when connecting to the server.
+use std::{sync::Arc, time::SystemTime};
+
+use error_stack::{Result, ResultExt};
+use hyper::client::HttpConnector;
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use rustls::{
+    client::{ServerCertVerified, ServerCertVerifier},
+    ClientConfig, ServerName,
+};
+use rustls_pemfile::Item;
+use tonic::transport::{Certificate, ClientTlsConfig, Endpoint, Identity};
+
+use crate::ClientError;
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TlsOptions {
+    pub ca_certificate: Option<Vec<u8>>,
+    pub identity: Option<(Vec<u8>, Vec<u8>)>,
+    pub domain_name: Option<String>,
+    pub insecure_skip_verify: bool,
+}
+
+impl TlsOptions {
+    /// Configures TLS on the endpoint, if any option was set.
+    ///
+    /// When skipping verification, TLS is handled by [TlsOptions::insecure_connector]
+    /// instead.
+    pub fn configure_endpoint(&self, endpoint: Endpoint) -> Result<Endpoint, ClientError> {
+        let has_options =
+            self.ca_certificate.is_some() || self.identity.is_some() || self.domain_name.is_some();
+        if self.insecure_skip_verify || !has_options {
+            return Ok(endpoint);
+        }
+
+        let mut config = ClientTlsConfig::new();
+        if let Some(pem) = &self.ca_certificate {
+            config = config.ca_certificate(Certificate::from_pem(pem));
+        }
+        if let Some((cert, key)) = &self.identity {
+            config = config.identity(Identity::from_pem(cert, key));
+        }
+        if let Some(domain_name) = &self.domain_name {
+            config = config.domain_name(domain_name);
+        }
+
+        endpoint
+            .tls_config(config)
+            .change_context(ClientError)
+            .attach_printable("failed to configure tls")
+    }
+
+    /// Returns a connector that doesn't verify the server certificate.
+    pub fn insecure_connector(&self) -> Result<Option<HttpsConnector<HttpConnector>>, ClientError> {
+        if !self.insecure_skip_verify {
+            return Ok(None);
+        }
+
+        let builder = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(Arc::new(SkipServerVerification));
+
+        let mut config = if let Some((cert, key)) = &self.identity {
+            let certs = rustls_pemfile::certs(&mut cert.as_slice())
+                .change_context(ClientError)
+                .attach_printable("failed to parse client certificate")?
+                .into_iter()
+                .map(rustls::Certificate)
+                .collect();
+            let key = parse_private_key(key)?;
+            builder
+                .with_client_auth_cert(certs, key)
+                .change_context(ClientError)
+                .attach_printable("invalid client certificate")?
+        } else {
+            builder.with_no_client_auth()
+        };
+        config.alpn_protocols = vec![b"h2".to_vec()];
+
+        let mut http = HttpConnector::new();
+        http.enforce_http(false);
+
+        let builder = HttpsConnectorBuilder::new()
+            .with_tls_config(config)
+            .https_or_http();
+        let builder = match &self.domain_name {
+            Some(domain_name) => builder.with_server_name(domain_name.clone()),
+            None => builder,
+        };
+
+        Ok(Some(builder.enable_http2().wrap_connector(http)))
+    }
+}
+
+fn parse_private_key(pem: &[u8]) -> Result<rustls::PrivateKey, ClientError> {
+    let items = rustls_pemfile::read_all(&mut &pem[..])
+        .change_context(ClientError)
+        .attach_printable("failed to parse client key")?;
+
+    items
+        .into_iter()
+        .find_map(|item| match item {
+            Item::PKCS8Key(key) | Item::RSAKey(key) | Item::ECKey(key) => {
+                Some(rustls::PrivateKey(key))
+            }
+            _ => None,
+        })
+        .ok_or(ClientError)
+        .attach_printable("client key not found")
+}
+
+/// Accepts any server certificate.
+struct SkipServerVerification;
+
+impl ServerCertVerifier for SkipServerVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &rustls::Certificate,
+        _intermediates: &[rustls::Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
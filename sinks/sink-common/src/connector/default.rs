@@ -9,7 +9,7 @@ use serde::Serialize;
 use serde_json::Value;
 use tokio_stream::StreamExt;
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use crate::{
     error::SinkError, sink::Sink, Context, CursorAction, DisplayCursor, PersistedState,
@@ -161,6 +161,18 @@ where
                 info!(block = %DisplayCursor(&cursor), "handle invalidate");
                 self.handle_invalidate(cursor, state, ct).await
             }
+            DataMessage::Finalize { cursor } => {
+                debug!(block = %DisplayCursor(&cursor), "handle finalize");
+                Ok((CursorAction::Skip, StreamAction::Continue))
+            }
+            DataMessage::DecodeError { end_cursor, errors } => {
+                warn!(
+                    block = end_cursor.order_key,
+                    errors = ?errors,
+                    "failed to decode batch items"
+                );
+                Ok((CursorAction::Skip, StreamAction::Continue))
+            }
             DataMessage::Heartbeat => {
                 self.sink.handle_heartbeat().await?;
                 self.state_manager.heartbeat().await?;
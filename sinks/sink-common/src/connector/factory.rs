@@ -8,7 +8,7 @@ use prost::Message;
 use serde::Serialize;
 use tokio_stream::StreamExt;
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use crate::{
     error::SinkError, sink::Sink, Context, CursorAction, DisplayCursor, PersistedState,
@@ -230,6 +230,18 @@ where
                 info!(block = %DisplayCursor(&cursor), "handle invalidate");
                 self.handle_invalidate(cursor, state, ct).await
             }
+            DataMessage::Finalize { cursor } => {
+                debug!(block = %DisplayCursor(&cursor), "handle finalize");
+                Ok((CursorAction::Skip, StreamAction::Continue))
+            }
+            DataMessage::DecodeError { end_cursor, errors } => {
+                warn!(
+                    block = end_cursor.order_key,
+                    errors = ?errors,
+                    "failed to decode batch items"
+                );
+                Ok((CursorAction::Skip, StreamAction::Continue))
+            }
             DataMessage::Heartbeat => {
                 self.sink.handle_heartbeat().await?;
                 self.state_manager.heartbeat().await?;
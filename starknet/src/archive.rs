@@ -0,0 +1,443 @@
+//! Export stored blocks to a portable file, and import them back.
+//!
+//! The file contains a sequence of blocks, each encoded as a protobuf
+//! [ArchivedBlock] message prefixed by its length as a big-endian `u64`.
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::PathBuf,
+};
+
+use apibara_core::starknet::v1alpha2;
+use apibara_node::db::libmdbx::NoWriteMap;
+use byteorder::{BigEndian, WriteBytesExt};
+use clap::Args;
+use error_stack::{Result, ResultExt};
+use prost::Message;
+use tracing::info;
+
+use crate::{
+    core::GlobalBlockId,
+    db::{tables, BlockBody, DatabaseStorage, StorageReader, StorageWriter},
+    ingestion::verify_block,
+    inspect::{open_environment, open_storage},
+    DatabaseArgs, StarknetError,
+};
+
+/// Number of blocks written in a single database transaction on import.
+const IMPORT_BATCH_SIZE: usize = 1_000;
+
+/// Maximum size of an encoded block, larger sizes mean the file is corrupted.
+const MAX_ARCHIVED_BLOCK_SIZE: u64 = 256 * 1024 * 1024;
+
+/// A block with all the data needed to restore it.
+#[derive(Clone, PartialEq, Message)]
+pub struct ArchivedBlock {
+    #[prost(enumeration = "v1alpha2::BlockStatus", tag = "1")]
+    pub status: i32,
+    #[prost(message, optional, tag = "2")]
+    pub header: Option<v1alpha2::BlockHeader>,
+    #[prost(message, repeated, tag = "3")]
+    pub transactions: Vec<v1alpha2::Transaction>,
+    #[prost(message, repeated, tag = "4")]
+    pub receipts: Vec<v1alpha2::TransactionReceipt>,
+    #[prost(message, optional, tag = "5")]
+    pub state_update: Option<v1alpha2::StateUpdate>,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct ExportArgs {
+    /// Data directory. Defaults to `$XDG_DATA_HOME`.
+    #[arg(long, env)]
+    pub data: Option<PathBuf>,
+    /// Indexer name. Defaults to `starknet`.
+    #[arg(long, env)]
+    pub name: Option<String>,
+    #[command(flatten)]
+    pub database: DatabaseArgs,
+    /// Path of the exported file.
+    #[arg(long)]
+    pub output: PathBuf,
+    /// First block to export. Defaults to the earliest block available.
+    #[arg(long)]
+    pub from_block: Option<u64>,
+    /// Last block to export (inclusive). Defaults to the highest finalized block.
+    #[arg(long)]
+    pub to_block: Option<u64>,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct ImportArgs {
+    /// Data directory. Defaults to `$XDG_DATA_HOME`.
+    #[arg(long, env)]
+    pub data: Option<PathBuf>,
+    /// Indexer name. Defaults to `starknet`.
+    #[arg(long, env)]
+    pub name: Option<String>,
+    #[command(flatten)]
+    pub database: DatabaseArgs,
+    /// Path of the file created by `export`.
+    #[arg(long)]
+    pub input: PathBuf,
+}
+
+/// Exports the canonical chain to a file.
+///
+/// Only finalized blocks are exported by default, so the export is consistent
+/// even if the node is running.
+pub fn export(args: ExportArgs) -> Result<(), StarknetError> {
+    let storage = open_storage(
+        args.data,
+        args.name.as_deref(),
+        &args.database.to_database_config(),
+        true,
+    )?;
+
+    let from_block = match args.from_block {
+        Some(from_block) => from_block,
+        None => storage
+            .earliest_available_block()
+            .change_context(StarknetError)?
+            .ok_or(StarknetError)
+            .attach_printable("database is empty")?
+            .number(),
+    };
+    let to_block = match args.to_block {
+        Some(to_block) => to_block,
+        None => storage
+            .highest_finalized_block()
+            .change_context(StarknetError)?
+            .ok_or(StarknetError)
+            .attach_printable("no finalized block")?
+            .number(),
+    };
+
+    let file = File::create(&args.output)
+        .change_context(StarknetError)
+        .attach_printable_lazy(|| format!("failed to create {:?}", args.output))?;
+    let mut writer = BufWriter::new(file);
+
+    let mut count = 0;
+    for block_number in from_block..=to_block {
+        let block_id = storage
+            .canonical_block_id(block_number)
+            .change_context(StarknetError)?
+            .ok_or(StarknetError)
+            .attach_printable_lazy(|| format!("missing canonical block {block_number}"))?;
+
+        let block = read_block(&storage, &block_id)
+            .change_context(StarknetError)
+            .attach_printable_lazy(|| format!("failed to read block {block_id}"))?;
+
+        write_block(&mut writer, &block).change_context(StarknetError)?;
+        count += 1;
+    }
+
+    writer.flush().change_context(StarknetError)?;
+    info!(from_block, to_block, count, "export completed");
+    println!("exported {count} blocks to {:?}", args.output);
+    Ok(())
+}
+
+/// Imports blocks exported with [export] into an empty database.
+///
+/// Blocks are verified to form a chain, with each block parent hash matching
+/// the hash of the previous block.
+///
+/// Blocks are committed in batches of [IMPORT_BATCH_SIZE]. If the import fails,
+/// the batches already committed stay in the database, which must be deleted
+/// before importing again.
+pub fn import(args: ImportArgs) -> Result<(), StarknetError> {
+    let db = open_environment(
+        args.data,
+        args.name.as_deref(),
+        &args.database.to_database_config(),
+        false,
+    )?;
+    {
+        let txn = db.begin_rw_txn().change_context(StarknetError)?;
+        tables::ensure(&txn).change_context(StarknetError)?;
+        txn.commit().change_context(StarknetError)?;
+    }
+
+    let storage = DatabaseStorage::new(db);
+    if let Some(head) = storage
+        .highest_accepted_block()
+        .change_context(StarknetError)?
+    {
+        return Err(StarknetError)
+            .attach_printable_lazy(|| format!("database is not empty, head is {head}"));
+    }
+
+    let file = File::open(&args.input)
+        .change_context(StarknetError)
+        .attach_printable_lazy(|| format!("failed to open {:?}", args.input))?;
+    let mut reader = BufReader::new(file);
+
+    let mut count = 0;
+    if let Err(err) = import_blocks(&storage, &mut reader, &mut count) {
+        let imported = count - count % IMPORT_BATCH_SIZE;
+        return Err(err.attach_printable(format!(
+            "{imported} blocks were imported before the error, delete the database before importing again"
+        )));
+    }
+
+    println!("imported {count} blocks from {:?}", args.input);
+    Ok(())
+}
+
+/// Imports all blocks from `reader`, `count` is the number of blocks read so far.
+fn import_blocks<R: Read>(
+    storage: &DatabaseStorage<NoWriteMap>,
+    reader: &mut R,
+    count: &mut usize,
+) -> Result<(), StarknetError> {
+    let mut previous: Option<GlobalBlockId> = None;
+    let mut txn = storage.begin_txn().change_context(StarknetError)?;
+    while let Some(block) = read_archived_block(reader).change_context(StarknetError)? {
+        let header = block
+            .header
+            .ok_or(StarknetError)
+            .attach_printable("block is missing header")?;
+        let block_id = GlobalBlockId::from_block_header(&header).change_context(StarknetError)?;
+
+        if let Some(previous) = previous {
+            let parent_id =
+                GlobalBlockId::from_block_header_parent(&header).change_context(StarknetError)?;
+            if parent_id != previous {
+                return Err(StarknetError).attach_printable_lazy(|| {
+                    format!("block {block_id} parent is not previous block {previous}")
+                });
+            }
+        }
+
+        let body = BlockBody {
+            transactions: block.transactions,
+        };
+        verify_block(
+            &header,
+            &body,
+            &block.receipts,
+            block.state_update.as_ref(),
+            None,
+        )
+        .change_context(StarknetError)
+        .attach_printable_lazy(|| format!("invalid block {block_id}"))?;
+
+        let status = v1alpha2::BlockStatus::from_i32(block.status).unwrap_or_default();
+        txn.write_status(&block_id, status)
+            .change_context(StarknetError)?;
+        txn.write_header(&block_id, header)
+            .change_context(StarknetError)?;
+        txn.write_body(&block_id, body)
+            .change_context(StarknetError)?;
+        txn.write_receipts(&block_id, block.receipts)
+            .change_context(StarknetError)?;
+        if let Some(state_update) = block.state_update {
+            txn.write_state_update(&block_id, state_update)
+                .change_context(StarknetError)?;
+        }
+        txn.extend_canonical_chain(&block_id)
+            .change_context(StarknetError)?;
+
+        previous = Some(block_id);
+        *count += 1;
+
+        if *count % IMPORT_BATCH_SIZE == 0 {
+            txn.commit().change_context(StarknetError)?;
+            info!(block_id = %block_id, count = *count, "imported blocks");
+            txn = storage.begin_txn().change_context(StarknetError)?;
+        }
+    }
+    txn.commit().change_context(StarknetError)
+}
+
+fn read_block<R: StorageReader>(
+    storage: &R,
+    block_id: &GlobalBlockId,
+) -> std::result::Result<ArchivedBlock, R::Error> {
+    let status = storage.read_status(block_id)?.unwrap_or_default();
+    let header = storage.read_header(block_id)?;
+    let transactions = storage.read_body(block_id)?;
+    let receipts = storage.read_receipts(block_id)?;
+    let mut state_update = storage.read_state_update(block_id)?;
+
+    // storage diffs are stored in their own table.
+    if let Some(state_diff) = state_update
+        .as_mut()
+        .and_then(|state_update| state_update.state_diff.as_mut())
+    {
+        state_diff.storage_diffs = storage.read_all_storage_diff(block_id)?;
+    }
+
+    Ok(ArchivedBlock {
+        status: status as i32,
+        header,
+        transactions,
+        receipts,
+        state_update,
+    })
+}
+
+/// Writes a length-prefixed block.
+pub fn write_block<W: Write>(writer: &mut W, block: &ArchivedBlock) -> io::Result<()> {
+    let data = block.encode_to_vec();
+    writer.write_u64::<BigEndian>(data.len() as u64)?;
+    writer.write_all(&data)
+}
+
+/// Reads a length-prefixed block, returns `None` at the end of the file.
+///
+/// A file that ends in the middle of a block is an error.
+pub fn read_archived_block<R: Read>(reader: &mut R) -> io::Result<Option<ArchivedBlock>> {
+    let mut prefix = [0; 8];
+    let mut read = 0;
+    while read < prefix.len() {
+        match reader.read(&mut prefix[read..]) {
+            Ok(0) if read == 0 => return Ok(None),
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "truncated block length",
+                ))
+            }
+            Ok(n) => read += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    let len = u64::from_be_bytes(prefix);
+    if len > MAX_ARCHIVED_BLOCK_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("block size {len} exceeds maximum size {MAX_ARCHIVED_BLOCK_SIZE}"),
+        ));
+    }
+    let mut data = vec![0; len as usize];
+    reader.read_exact(&mut data)?;
+    let block = ArchivedBlock::decode(data.as_slice())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    Ok(Some(block))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Write};
+
+    use apibara_core::starknet::v1alpha2;
+
+    use crate::{db::StorageReader, inspect::open_storage};
+
+    use super::{import, read_archived_block, write_block, ArchivedBlock, ImportArgs};
+
+    fn transaction(hash: u64) -> v1alpha2::Transaction {
+        v1alpha2::Transaction {
+            meta: Some(v1alpha2::TransactionMeta {
+                hash: Some(v1alpha2::FieldElement::from_u64(hash)),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn receipt(index: u64, hash: u64) -> v1alpha2::TransactionReceipt {
+        v1alpha2::TransactionReceipt {
+            transaction_index: index,
+            transaction_hash: Some(v1alpha2::FieldElement::from_u64(hash)),
+            ..Default::default()
+        }
+    }
+
+    /// A block with `number + 1` as hash and two transactions.
+    fn block(number: u64) -> ArchivedBlock {
+        let tx_hash = 100 * (number + 1);
+        ArchivedBlock {
+            status: v1alpha2::BlockStatus::AcceptedOnL1 as i32,
+            header: Some(v1alpha2::BlockHeader {
+                block_hash: Some(v1alpha2::FieldElement::from_u64(number + 1)),
+                parent_block_hash: Some(v1alpha2::FieldElement::from_u64(number)),
+                block_number: number,
+                ..Default::default()
+            }),
+            transactions: vec![transaction(tx_hash), transaction(tx_hash + 1)],
+            receipts: vec![receipt(0, tx_hash), receipt(1, tx_hash + 1)],
+            state_update: None,
+        }
+    }
+
+    #[test]
+    fn test_write_and_read_blocks() {
+        let blocks: Vec<_> = (0..3)
+            .map(|number| ArchivedBlock {
+                status: v1alpha2::BlockStatus::AcceptedOnL1 as i32,
+                header: Some(v1alpha2::BlockHeader {
+                    block_number: number,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .collect();
+
+        let mut data = Vec::new();
+        for block in &blocks {
+            write_block(&mut data, block).unwrap();
+        }
+
+        let mut reader = data.as_slice();
+        let mut read = Vec::new();
+        while let Some(block) = read_archived_block(&mut reader).unwrap() {
+            read.push(block);
+        }
+        assert_eq!(read, blocks);
+    }
+
+    #[test]
+    fn test_read_truncated_block() {
+        let mut data = Vec::new();
+        write_block(&mut data, &block(0)).unwrap();
+
+        let err = read_archived_block(&mut &data[..4]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let err = read_archived_block(&mut &data[..data.len() - 1]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let err = read_archived_block(&mut &u64::MAX.to_be_bytes()[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_import_round_trip() {
+        let blocks: Vec<_> = (0..3).map(block).collect();
+
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("blocks.bin");
+        let mut file = std::fs::File::create(&input).unwrap();
+        for block in &blocks {
+            write_block(&mut file, block).unwrap();
+        }
+        file.flush().unwrap();
+
+        let data = dir.path().join("data");
+        import(ImportArgs {
+            data: Some(data.clone()),
+            name: None,
+            database: Default::default(),
+            input,
+        })
+        .unwrap();
+
+        let storage = open_storage(Some(data), None, &Default::default(), true).unwrap();
+        for block in blocks {
+            let header = block.header.unwrap();
+            let block_id = storage
+                .canonical_block_id(header.block_number)
+                .unwrap()
+                .unwrap();
+            assert_eq!(storage.read_header(&block_id).unwrap(), Some(header));
+            assert_eq!(storage.read_body(&block_id).unwrap(), block.transactions);
+            assert_eq!(storage.read_receipts(&block_id).unwrap(), block.receipts);
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! Measure how fast stored data is read and filtered.
+use std::{path::PathBuf, sync::Arc, time::Instant};
+
+use apibara_core::{node::v1alpha2::DataFinality, starknet::v1alpha2};
+use apibara_node::{
+    server::SimpleMeter,
+    stream::{BatchProducer, StreamConfiguration},
+};
+use clap::Args;
+use error_stack::{Result, ResultExt};
+use prost::Message;
+use tracing::info;
+
+use crate::{
+    db::StorageReader, inspect::open_storage, stream::DbBatchProducer, DatabaseArgs, StarknetError,
+};
+
+#[derive(Clone, Debug, Args)]
+pub struct BenchArgs {
+    /// Data directory. Defaults to `$XDG_DATA_HOME`.
+    #[arg(long, env)]
+    pub data: Option<PathBuf>,
+    /// Indexer name. Defaults to `starknet`.
+    #[arg(long, env)]
+    pub name: Option<String>,
+    #[command(flatten)]
+    pub database: DatabaseArgs,
+    /// Path to the json-encoded filter to apply.
+    #[arg(long)]
+    pub filter: PathBuf,
+    /// First block to stream.
+    #[arg(long)]
+    pub from_block: u64,
+    /// Last block to stream (inclusive).
+    #[arg(long)]
+    pub to_block: u64,
+    /// Number of blocks in each batch.
+    #[arg(long, default_value_t = 20, value_parser = clap::value_parser!(u64).range(1..))]
+    pub batch_size: u64,
+}
+
+/// Streams the block range through the filter, without sending data over the
+/// network, and reports throughput.
+pub async fn bench(args: BenchArgs) -> Result<(), StarknetError> {
+    let storage = open_storage(
+        args.data,
+        args.name.as_deref(),
+        &args.database.to_database_config(),
+        false,
+    )?;
+
+    let filter = std::fs::read(&args.filter)
+        .change_context(StarknetError)
+        .attach_printable_lazy(|| format!("failed to read filter {:?}", args.filter))?;
+    let filter: v1alpha2::Filter = serde_json::from_slice(&filter)
+        .change_context(StarknetError)
+        .attach_printable("failed to parse filter")?;
+
+    let batch_size = args.batch_size as usize;
+    let configuration = StreamConfiguration {
+        batch_size,
+        stream_id: 0,
+        finality: DataFinality::DataStatusFinalized,
+        starting_cursor: None,
+        filter: vec![filter],
+        max_unacknowledged_batches: None,
+    };
+
+    let storage = Arc::new(storage);
+    let mut producer = DbBatchProducer::new(storage.clone());
+    producer
+        .reconfigure(&configuration)
+        .change_context(StarknetError)?;
+
+    let meter = SimpleMeter::default();
+    let start = Instant::now();
+    let mut block_count = 0u64;
+    let mut data_count = 0u64;
+    let mut byte_count = 0u64;
+
+    let mut block_number = args.from_block;
+    'outer: while block_number <= args.to_block {
+        let mut cursors = Vec::with_capacity(batch_size);
+        while cursors.len() < batch_size && block_number <= args.to_block {
+            let Some(block_id) = storage
+                .canonical_block_id(block_number)
+                .change_context(StarknetError)?
+            else {
+                if cursors.is_empty() {
+                    break 'outer;
+                }
+                break;
+            };
+            cursors.push(block_id);
+            block_number += 1;
+        }
+
+        block_count += cursors.len() as u64;
+        let batch = producer
+            .next_batch(cursors.into_iter(), &meter)
+            .await
+            .change_context(StarknetError)?;
+        data_count += batch.len() as u64;
+        byte_count += batch.iter().map(|b| b.encoded_len() as u64).sum::<u64>();
+    }
+
+    let elapsed = start.elapsed().as_secs_f64();
+    info!(
+        blocks = block_count,
+        data = data_count,
+        bytes = byte_count,
+        elapsed_secs = elapsed,
+        "benchmark completed"
+    );
+    println!("blocks:       {block_count}");
+    println!("with data:    {data_count}");
+    println!("elapsed:      {elapsed:.3}s");
+    println!("blocks/sec:   {:.1}", block_count as f64 / elapsed);
+    println!("bytes/sec:    {:.1}", byte_count as f64 / elapsed);
+
+    Ok(())
+}
@@ -1,8 +1,11 @@
-use apibara_node::o11y::init_opentelemetry;
-use apibara_starknet::{set_ctrlc_handler, start_node, StarknetError, StartArgs};
-use clap::{Parser, Subcommand};
+use std::time::Duration;
+
+use apibara_node::o11y::{
+    flush_error_reports, init_opentelemetry_with_config, report_error, OpenTelemetryConfig,
+};
+use apibara_starknet::{cli, StarknetError};
+use clap::Parser;
 use error_stack::{Result, ResultExt};
-use tokio_util::sync::CancellationToken;
 
 #[cfg(not(windows))]
 #[global_allocator]
@@ -12,25 +15,22 @@ static ALLOC: jemallocator::Jemalloc = jemallocator::Jemalloc;
 #[command(author, version, about, long_about = None)]
 struct Cli {
     #[command(subcommand)]
-    command: CliCommand,
-}
-
-#[derive(Subcommand)]
-enum CliCommand {
-    /// Start the StarkNet source node.
-    Start(StartArgs),
+    command: cli::Command,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), StarknetError> {
-    init_opentelemetry()
-        .change_context(StarknetError)
-        .attach_printable("failed to initialize opentelemetry")?;
+    init_opentelemetry_with_config(
+        OpenTelemetryConfig::from_env().with_service_version(env!("CARGO_PKG_VERSION")),
+    )
+    .change_context(StarknetError)
+    .attach_printable("failed to initialize opentelemetry")?;
 
-    let cts = CancellationToken::new();
-    set_ctrlc_handler(cts.clone()).change_context(StarknetError)?;
-
-    match Cli::parse().command {
-        CliCommand::Start(args) => start_node(args, cts).await,
+    let result = cli::run(Cli::parse().command).await;
+    if let Err(err) = &result {
+        report_error(err);
+        flush_error_reports(Duration::from_secs(2));
     }
+
+    result
 }
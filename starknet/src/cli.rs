@@ -0,0 +1,45 @@
+//! Node commands, shared by the `apibara-starknet` and `apibara` binaries.
+use clap::Subcommand;
+use error_stack::{Result, ResultExt};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    archive::{export, import, ExportArgs, ImportArgs},
+    bench::{bench, BenchArgs},
+    inspect::{inspect, InspectArgs},
+    maintenance::{db, DbArgs},
+    set_ctrlc_handler, start_node, StarknetError, StartArgs,
+};
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Start the StarkNet source node.
+    Start(StartArgs),
+    /// Dump data stored by the node as json.
+    Inspect(InspectArgs),
+    /// Measure how fast stored data is read and filtered.
+    Bench(BenchArgs),
+    /// Database maintenance: stats and compaction.
+    Db(DbArgs),
+    /// Export the canonical chain to a portable file.
+    Export(ExportArgs),
+    /// Import blocks exported with `export` into an empty database.
+    Import(ImportArgs),
+}
+
+/// Runs the given command.
+///
+/// Stops the node on ctrl-c.
+pub async fn run(command: Command) -> Result<(), StarknetError> {
+    let cts = CancellationToken::new();
+    set_ctrlc_handler(cts.clone()).change_context(StarknetError)?;
+
+    match command {
+        Command::Start(args) => start_node(args, cts).await,
+        Command::Inspect(args) => inspect(args),
+        Command::Bench(args) => bench(args).await,
+        Command::Db(args) => db(args),
+        Command::Export(args) => export(args),
+        Command::Import(args) => import(args),
+    }
+}
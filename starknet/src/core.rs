@@ -110,10 +110,7 @@ impl GlobalBlockId {
 
     /// Returns a cursor corresponding to the block id.
     pub fn to_cursor(&self) -> Cursor {
-        Cursor {
-            order_key: self.number(),
-            unique_key: self.hash().as_bytes().to_vec(),
-        }
+        Cursor::new(self.number(), self.hash().as_bytes().to_vec())
     }
 }
 
@@ -145,8 +142,7 @@ impl From<&BlockHash> for v1alpha2::FieldElement {
 
 impl Display for GlobalBlockId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let hash = hex::encode(self.hash().as_bytes());
-        write!(f, "{}/0x{}", self.number(), hash)
+        write!(f, "{}", self.to_cursor().to_canonical_string())
     }
 }
 
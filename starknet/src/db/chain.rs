@@ -15,3 +15,18 @@ impl Table for CanonicalChainTable {
         "CanonicalChain"
     }
 }
+
+/// Store the id of the chain being ingested.
+///
+/// The table contains a single entry, with key `0`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChainIdTable {}
+
+impl Table for ChainIdTable {
+    type Key = u64;
+    type Value = v1alpha2::FieldElement;
+
+    fn db_name() -> &'static str {
+        "ChainId"
+    }
+}
@@ -0,0 +1,46 @@
+//! Contract class data.
+
+use apibara_core::starknet::v1alpha2;
+use apibara_node::db::{KeyDecodeError, Table, TableKey};
+use prost::Message;
+
+/// Hash of a declared class.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassHash(pub v1alpha2::FieldElement);
+
+/// Store contract classes by their class hash.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ContractClassTable {}
+
+/// A contract class definition (sierra program and abi), serialized as json.
+#[derive(Clone, PartialEq, Message)]
+pub struct ContractClass {
+    #[prost(bytes = "vec", tag = "1")]
+    pub definition: prost::alloc::vec::Vec<u8>,
+}
+
+impl TableKey for ClassHash {
+    type Encoded = [u8; 32];
+
+    fn encode(&self) -> Self::Encoded {
+        self.0.to_bytes()
+    }
+
+    fn decode(b: &[u8]) -> Result<Self, KeyDecodeError> {
+        let class_hash =
+            v1alpha2::FieldElement::from_slice(b).map_err(|_| KeyDecodeError::InvalidByteSize {
+                expected: 32,
+                actual: b.len(),
+            })?;
+        Ok(ClassHash(class_hash))
+    }
+}
+
+impl Table for ContractClassTable {
+    type Key = ClassHash;
+    type Value = ContractClass;
+
+    fn db_name() -> &'static str {
+        "ContractClass"
+    }
+}
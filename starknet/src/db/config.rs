@@ -0,0 +1,25 @@
+//! Database configuration.
+
+/// Database geometry and limits.
+#[derive(Debug, Clone)]
+pub struct DatabaseConfig {
+    /// Initial size of the database, in GiB.
+    pub min_size_gib: usize,
+    /// Maximum size of the database, in GiB.
+    pub max_size_gib: usize,
+    /// How much the database grows when full, in GiB.
+    pub growth_step_gib: isize,
+    /// Maximum number of concurrent readers. Defaults to the mdbx default.
+    pub max_readers: Option<u64>,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            min_size_gib: 10,
+            max_size_gib: 512,
+            growth_step_gib: 2,
+            max_readers: None,
+        }
+    }
+}
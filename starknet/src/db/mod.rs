@@ -1,21 +1,29 @@
 mod block;
 mod chain;
+mod class;
+mod config;
 mod state;
 mod storage;
+mod trace;
 mod transaction;
 
 pub use self::block::{BlockBody, BlockReceipts, BlockStatus};
+pub use self::class::{ClassHash, ContractClass};
+pub use self::config::DatabaseConfig;
 pub use self::storage::{
     DatabaseStorage, DatabaseStorageWriter, MockStorageReader, StorageReader, StorageWriter,
 };
+pub use self::trace::BlockTraces;
 
 pub mod tables {
     use apibara_node::db::libmdbx::{EnvironmentKind, Error as MdbxError, Transaction, RW};
-    use apibara_node::db::MdbxRWTransactionExt;
+    use apibara_node::db::{MdbxRWTransactionExt, Table};
 
     pub use super::block::{BlockHeaderTable, BlockStatusTable};
-    pub use super::chain::CanonicalChainTable;
+    pub use super::chain::{CanonicalChainTable, ChainIdTable};
+    pub use super::class::ContractClassTable;
     pub use super::state::{StateUpdateTable, StorageDiffTable};
+    pub use super::trace::BlockTracesTable;
     pub use super::transaction::{BlockBodyTable, BlockEventsTable, BlockReceiptsTable};
 
     /// Ensures all tables exist.
@@ -28,6 +36,26 @@ pub mod tables {
         txn.ensure_table::<self::BlockEventsTable>(None)?;
         txn.ensure_table::<self::StateUpdateTable>(None)?;
         txn.ensure_table::<self::StorageDiffTable>(None)?;
+        txn.ensure_table::<self::ContractClassTable>(None)?;
+        txn.ensure_table::<self::BlockTracesTable>(None)?;
+        txn.ensure_table::<self::ChainIdTable>(None)?;
         Ok(())
     }
+
+    /// Returns the names of all tables.
+    pub fn names() -> Vec<&'static str> {
+        vec![
+            self::BlockBodyTable::db_name(),
+            self::BlockHeaderTable::db_name(),
+            self::BlockStatusTable::db_name(),
+            self::CanonicalChainTable::db_name(),
+            self::BlockReceiptsTable::db_name(),
+            self::BlockEventsTable::db_name(),
+            self::StateUpdateTable::db_name(),
+            self::StorageDiffTable::db_name(),
+            self::ContractClassTable::db_name(),
+            self::BlockTracesTable::db_name(),
+            self::ChainIdTable::db_name(),
+        ]
+    }
 }
@@ -5,7 +5,7 @@ use std::sync::Arc;
 use apibara_core::starknet::v1alpha2;
 use apibara_node::db::{
     libmdbx::{self, Environment, EnvironmentKind, Transaction, RW},
-    MdbxErrorExt, MdbxTransactionExt, TableCursor,
+    MdbxErrorExt, MdbxTransactionExt, Table, TableCursor,
 };
 use mockall::automock;
 
@@ -13,7 +13,9 @@ use crate::core::GlobalBlockId;
 
 use super::{
     block::{BlockBody, BlockReceipts, ContractAtBlockId},
+    class::{ClassHash, ContractClass},
     tables,
+    trace::BlockTraces,
 };
 
 /// An empty error type. Use by [MockStorageReader].
@@ -31,6 +33,9 @@ pub trait StorageReader {
     /// Returns the highest finalized block that was indexed.
     fn highest_finalized_block(&self) -> Result<Option<GlobalBlockId>, Self::Error>;
 
+    /// Returns the earliest block available in storage.
+    fn earliest_available_block(&self) -> Result<Option<GlobalBlockId>, Self::Error>;
+
     /// Returns the block id for the block at the given height, or `None` if the
     /// canonical chain is shorter.
     fn canonical_block_id(&self, number: u64) -> Result<Option<GlobalBlockId>, Self::Error>;
@@ -80,6 +85,18 @@ pub trait StorageReader {
         &self,
         id: &GlobalBlockId,
     ) -> Result<Vec<v1alpha2::StorageDiff>, Self::Error>;
+
+    /// Returns the json definition of the class with the given hash.
+    fn read_contract_class(
+        &self,
+        class_hash: &v1alpha2::FieldElement,
+    ) -> Result<Option<Vec<u8>>, Self::Error>;
+
+    /// Returns the json transaction traces of the given block.
+    fn read_block_traces(&self, id: &GlobalBlockId) -> Result<Option<Vec<u8>>, Self::Error>;
+
+    /// Returns the id of the chain, if ingestion stored it.
+    fn chain_id(&self) -> Result<Option<v1alpha2::FieldElement>, Self::Error>;
 }
 
 /// An object to write chain data to storage in a single transaction.
@@ -125,6 +142,29 @@ pub trait StorageWriter {
         id: &GlobalBlockId,
         state_update: v1alpha2::StateUpdate,
     ) -> Result<(), Self::Error>;
+
+    /// Writes the json definition of a declared class.
+    fn write_contract_class(
+        &mut self,
+        class_hash: &v1alpha2::FieldElement,
+        definition: Vec<u8>,
+    ) -> Result<(), Self::Error>;
+
+    /// Writes the json transaction traces of a block.
+    fn write_block_traces(
+        &mut self,
+        id: &GlobalBlockId,
+        traces: Vec<u8>,
+    ) -> Result<(), Self::Error>;
+
+    /// Deletes the data of all blocks below the given block number.
+    ///
+    /// Block headers and statuses are kept, but the blocks are removed from the
+    /// canonical chain so they're no longer served.
+    fn prune_blocks_below(&mut self, number: u64) -> Result<(), Self::Error>;
+
+    /// Writes the id of the chain.
+    fn write_chain_id(&mut self, chain_id: &v1alpha2::FieldElement) -> Result<(), Self::Error>;
 }
 
 #[derive(Debug, Clone)]
@@ -141,6 +181,9 @@ pub struct DatabaseStorageWriter<'env, 'txn, E: EnvironmentKind> {
     state_update_cursor: TableCursor<'txn, tables::StateUpdateTable, RW>,
     storage_diff_cursor: TableCursor<'txn, tables::StorageDiffTable, RW>,
     canonical_chain_cursor: TableCursor<'txn, tables::CanonicalChainTable, RW>,
+    contract_class_cursor: TableCursor<'txn, tables::ContractClassTable, RW>,
+    block_traces_cursor: TableCursor<'txn, tables::BlockTracesTable, RW>,
+    chain_id_cursor: TableCursor<'txn, tables::ChainIdTable, RW>,
 }
 
 impl<E: EnvironmentKind> DatabaseStorage<E> {
@@ -157,6 +200,9 @@ impl<E: EnvironmentKind> DatabaseStorage<E> {
         let state_update_cursor = txn.open_cursor::<tables::StateUpdateTable>()?;
         let storage_diff_cursor = txn.open_cursor::<tables::StorageDiffTable>()?;
         let canonical_chain_cursor = txn.open_cursor::<tables::CanonicalChainTable>()?;
+        let contract_class_cursor = txn.open_cursor::<tables::ContractClassTable>()?;
+        let block_traces_cursor = txn.open_cursor::<tables::BlockTracesTable>()?;
+        let chain_id_cursor = txn.open_cursor::<tables::ChainIdTable>()?;
         let writer = DatabaseStorageWriter {
             txn,
             status_cursor,
@@ -166,6 +212,9 @@ impl<E: EnvironmentKind> DatabaseStorage<E> {
             state_update_cursor,
             storage_diff_cursor,
             canonical_chain_cursor,
+            contract_class_cursor,
+            block_traces_cursor,
+            chain_id_cursor,
         };
         Ok(writer)
     }
@@ -215,6 +264,21 @@ impl<E: EnvironmentKind> StorageReader for DatabaseStorage<E> {
         Ok(None)
     }
 
+    #[tracing::instrument(level = "debug", skip(self))]
+    fn earliest_available_block(&self) -> Result<Option<GlobalBlockId>, Self::Error> {
+        let txn = self.db.begin_ro_txn()?;
+        let mut cursor = txn.open_cursor::<tables::CanonicalChainTable>()?;
+        let block_id = match cursor.first()? {
+            None => None,
+            Some((number, hash)) => {
+                let hash = (&hash).try_into().map_err(libmdbx::Error::decode_error)?;
+                Some(GlobalBlockId::new(number, hash))
+            }
+        };
+        txn.commit()?;
+        Ok(block_id)
+    }
+
     #[tracing::instrument(level = "debug", skip(self))]
     fn canonical_block_id(&self, number: u64) -> Result<Option<GlobalBlockId>, Self::Error> {
         let txn = self.db.begin_ro_txn()?;
@@ -350,6 +414,37 @@ impl<E: EnvironmentKind> StorageReader for DatabaseStorage<E> {
         txn.commit()?;
         Ok(diffs)
     }
+
+    #[tracing::instrument(level = "debug", skip(self))]
+    fn read_contract_class(
+        &self,
+        class_hash: &v1alpha2::FieldElement,
+    ) -> Result<Option<Vec<u8>>, Self::Error> {
+        let txn = self.db.begin_ro_txn()?;
+        let mut cursor = txn.open_cursor::<tables::ContractClassTable>()?;
+        let key = ClassHash(class_hash.clone());
+        let definition = cursor.seek_exact(&key)?.map(|t| t.1.definition);
+        txn.commit()?;
+        Ok(definition)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self))]
+    fn read_block_traces(&self, id: &GlobalBlockId) -> Result<Option<Vec<u8>>, Self::Error> {
+        let txn = self.db.begin_ro_txn()?;
+        let mut cursor = txn.open_cursor::<tables::BlockTracesTable>()?;
+        let traces = cursor.seek_exact(id)?.map(|t| t.1.traces);
+        txn.commit()?;
+        Ok(traces)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self))]
+    fn chain_id(&self) -> Result<Option<v1alpha2::FieldElement>, Self::Error> {
+        let txn = self.db.begin_ro_txn()?;
+        let mut cursor = txn.open_cursor::<tables::ChainIdTable>()?;
+        let chain_id = cursor.seek_exact(&0)?.map(|t| t.1);
+        txn.commit()?;
+        Ok(chain_id)
+    }
 }
 
 impl<'env, 'txn, E: EnvironmentKind> StorageWriter for DatabaseStorageWriter<'env, 'txn, E> {
@@ -451,4 +546,176 @@ impl<'env, 'txn, E: EnvironmentKind> StorageWriter for DatabaseStorageWriter<'en
         self.state_update_cursor.put(id, &state_update)?;
         Ok(())
     }
+
+    #[tracing::instrument(level = "debug", skip(self, definition))]
+    fn write_contract_class(
+        &mut self,
+        class_hash: &v1alpha2::FieldElement,
+        definition: Vec<u8>,
+    ) -> Result<(), Self::Error> {
+        let key = ClassHash(class_hash.clone());
+        let value = ContractClass { definition };
+        self.contract_class_cursor.seek_exact(&key)?;
+        self.contract_class_cursor.put(&key, &value)?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, traces))]
+    fn write_block_traces(
+        &mut self,
+        id: &GlobalBlockId,
+        traces: Vec<u8>,
+    ) -> Result<(), Self::Error> {
+        let value = BlockTraces { traces };
+        self.block_traces_cursor.seek_exact(id)?;
+        self.block_traces_cursor.put(id, &value)?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self))]
+    fn prune_blocks_below(&mut self, number: u64) -> Result<(), Self::Error> {
+        delete_below(&mut self.canonical_chain_cursor, number, |n| *n)?;
+        delete_below(&mut self.body_cursor, number, |id| id.number())?;
+        delete_below(&mut self.receipts_cursor, number, |id| id.number())?;
+        delete_below(&mut self.state_update_cursor, number, |id| id.number())?;
+        delete_below(&mut self.storage_diff_cursor, number, |key| {
+            key.block_id.number()
+        })?;
+        delete_below(&mut self.block_traces_cursor, number, |id| id.number())?;
+        Ok(())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self))]
+    fn write_chain_id(&mut self, chain_id: &v1alpha2::FieldElement) -> Result<(), Self::Error> {
+        self.chain_id_cursor.seek_exact(&0)?;
+        self.chain_id_cursor.put(&0, chain_id)?;
+        Ok(())
+    }
+}
+
+/// Deletes all entries in a table keyed by block number whose block is below `number`.
+fn delete_below<T: Table>(
+    cursor: &mut TableCursor<'_, T, RW>,
+    number: u64,
+    block_number: impl Fn(&T::Key) -> u64,
+) -> Result<(), libmdbx::Error> {
+    while let Some((key, _)) = cursor.first()? {
+        if block_number(&key) >= number {
+            break;
+        }
+        cursor.del()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use apibara_core::starknet::v1alpha2;
+    use apibara_node::db::{
+        libmdbx::{Environment, NoWriteMap},
+        MdbxEnvironmentExt,
+    };
+
+    use crate::{
+        core::GlobalBlockId,
+        db::{tables, BlockBody},
+    };
+
+    use super::{DatabaseStorage, StorageReader, StorageWriter};
+
+    fn write_block(storage: &DatabaseStorage<NoWriteMap>, block_id: &GlobalBlockId) {
+        let contract_address = v1alpha2::FieldElement::from_u64(1);
+        let state_update = v1alpha2::StateUpdate {
+            state_diff: Some(v1alpha2::StateDiff {
+                storage_diffs: vec![v1alpha2::StorageDiff {
+                    contract_address: Some(contract_address),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let mut txn = storage.begin_txn().unwrap();
+        txn.write_status(block_id, v1alpha2::BlockStatus::AcceptedOnL1)
+            .unwrap();
+        txn.write_header(block_id, v1alpha2::BlockHeader::default())
+            .unwrap();
+        txn.write_body(
+            block_id,
+            BlockBody {
+                transactions: vec![v1alpha2::Transaction::default()],
+            },
+        )
+        .unwrap();
+        txn.write_receipts(block_id, vec![v1alpha2::TransactionReceipt::default()])
+            .unwrap();
+        txn.write_state_update(block_id, state_update).unwrap();
+        txn.write_block_traces(block_id, b"[]".to_vec()).unwrap();
+        txn.extend_canonical_chain(block_id).unwrap();
+        txn.commit().unwrap();
+    }
+
+    #[test]
+    fn test_prune_blocks_below() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Environment::<NoWriteMap>::builder()
+            .open(dir.path())
+            .unwrap();
+        {
+            let txn = db.begin_rw_txn().unwrap();
+            tables::ensure(&txn).unwrap();
+            txn.commit().unwrap();
+        }
+        let storage = DatabaseStorage::new(Arc::new(db));
+
+        let block_ids: Vec<_> = (0..5).map(GlobalBlockId::from_u64).collect();
+        for block_id in &block_ids {
+            write_block(&storage, block_id);
+        }
+
+        let mut txn = storage.begin_txn().unwrap();
+        txn.prune_blocks_below(3).unwrap();
+        txn.commit().unwrap();
+
+        let contract_address = v1alpha2::FieldElement::from_u64(1);
+        for block_id in &block_ids {
+            // headers and statuses are always kept.
+            assert!(storage.read_header(block_id).unwrap().is_some());
+            assert_eq!(
+                storage.read_status(block_id).unwrap(),
+                Some(v1alpha2::BlockStatus::AcceptedOnL1)
+            );
+
+            let pruned = block_id.number() < 3;
+            assert_eq!(
+                storage.canonical_block_id(block_id.number()).unwrap(),
+                if pruned { None } else { Some(*block_id) }
+            );
+            assert_eq!(storage.read_body(block_id).unwrap().is_empty(), pruned);
+            assert_eq!(storage.read_receipts(block_id).unwrap().is_empty(), pruned);
+            assert_eq!(
+                storage.read_state_update(block_id).unwrap().is_none(),
+                pruned
+            );
+            assert_eq!(
+                storage
+                    .read_storage_diff(block_id, &contract_address)
+                    .unwrap()
+                    .is_none(),
+                pruned
+            );
+            assert_eq!(
+                storage.read_block_traces(block_id).unwrap().is_none(),
+                pruned
+            );
+        }
+
+        assert_eq!(
+            storage.earliest_available_block().unwrap(),
+            Some(block_ids[3])
+        );
+    }
 }
@@ -0,0 +1,27 @@
+//! Transaction traces data.
+
+use apibara_node::db::Table;
+use prost::Message;
+
+use crate::core::GlobalBlockId;
+
+/// Store the execution traces of all transactions in a block.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BlockTracesTable {}
+
+/// Transaction traces (call hierarchy, events per call and execution resources),
+/// serialized as json.
+#[derive(Clone, PartialEq, Message)]
+pub struct BlockTraces {
+    #[prost(bytes = "vec", tag = "1")]
+    pub traces: prost::alloc::vec::Vec<u8>,
+}
+
+impl Table for BlockTracesTable {
+    type Key = GlobalBlockId;
+    type Value = BlockTraces;
+
+    fn db_name() -> &'static str {
+        "BlockTraces"
+    }
+}
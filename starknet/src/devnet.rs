@@ -0,0 +1,117 @@
+//! Run the node against a devnet, re-syncing when the devnet is reset.
+use std::time::Duration;
+
+use error_stack::{Result, ResultExt};
+use tempdir::TempDir;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::{
+    core::GlobalBlockId,
+    node_builder,
+    provider::{BlockId, HttpProvider, Provider},
+    StarknetError, StartArgs,
+};
+
+/// How often to check if the devnet was reset.
+const RESET_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Starts the node with a temporary database.
+///
+/// When the devnet is reset, the node is stopped, the database deleted and
+/// the node restarted from genesis.
+pub async fn start_devnet_node(
+    args: StartArgs,
+    cts: CancellationToken,
+) -> Result<(), StarknetError> {
+    let rpc_url = args
+        .rpc
+        .parse()
+        .change_context(StarknetError)
+        .attach_printable("failed to parse rpc url")?;
+    let provider = HttpProvider::new(rpc_url);
+
+    loop {
+        let tempdir = TempDir::new("apibara").change_context(StarknetError)?;
+        info!(datadir = ?tempdir.path(), "starting in devnet mode");
+
+        let mut node = node_builder(args.clone())?;
+        node.with_datadir(tempdir.path().to_path_buf());
+        let node = node
+            .build()
+            .change_context(StarknetError)
+            .attach_printable("failed to initialize node")?;
+
+        let ct = cts.child_token();
+        let node_handle = node.start(ct.clone(), args.wait_for_rpc);
+        tokio::pin!(node_handle);
+
+        let reset = tokio::select! {
+            ret = &mut node_handle => {
+                return ret
+                    .change_context(StarknetError)
+                    .attach_printable("error while running starknet node");
+            }
+            reset = wait_for_reset(&provider, ct.clone()) => reset,
+        };
+
+        // stop the node and wait for it to release the database.
+        ct.cancel();
+        let ret = node_handle.await;
+
+        if !reset || cts.is_cancelled() {
+            return ret
+                .change_context(StarknetError)
+                .attach_printable("error while running starknet node");
+        }
+
+        warn!("devnet was reset, restarting with an empty database");
+        if let Err(err) = tempdir.close() {
+            warn!(error = ?err, "failed to delete devnet database");
+        }
+    }
+}
+
+/// Waits until the devnet is reset, returns `false` if cancelled first.
+///
+/// A reset is detected when the genesis block hash changes or the chain head
+/// goes backwards.
+async fn wait_for_reset(provider: &HttpProvider, ct: CancellationToken) -> bool {
+    let mut genesis: Option<GlobalBlockId> = None;
+    let mut head: Option<GlobalBlockId> = None;
+
+    loop {
+        tokio::select! {
+            _ = ct.cancelled() => return false,
+            _ = tokio::time::sleep(RESET_CHECK_INTERVAL) => {},
+        }
+
+        // the devnet is unavailable while restarting, simply try again later.
+        let (new_genesis, new_head) = match chain_bounds(provider).await {
+            Some(bounds) => bounds,
+            None => continue,
+        };
+
+        debug!(genesis = %new_genesis, head = %new_head, "check devnet reset");
+
+        let genesis_changed = genesis.map(|g| g != new_genesis).unwrap_or(false);
+        let head_went_backwards = head
+            .map(|h| h.number() > new_head.number())
+            .unwrap_or(false);
+
+        if genesis_changed || head_went_backwards {
+            return true;
+        }
+
+        genesis = Some(new_genesis);
+        head = Some(new_head);
+    }
+}
+
+/// Returns the genesis block and the current head.
+async fn chain_bounds(provider: &HttpProvider) -> Option<(GlobalBlockId, GlobalBlockId)> {
+    let head = provider.get_head().await.ok()?;
+    let (_status, header, _body) = provider.get_block(&BlockId::Number(0)).await.ok()?;
+    let genesis = GlobalBlockId::from_block_header(&header).ok()?;
+    Some((genesis, head))
+}
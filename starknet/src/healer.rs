@@ -1,41 +1,40 @@
-use std::sync::Arc;
+use std::{ops::RangeInclusive, sync::Arc, time::Duration};
 
-use apibara_core::starknet::v1alpha2;
-use apibara_node::db::libmdbx::{Environment, EnvironmentKind, Error as MdxError};
-use tokio::sync::mpsc::{self, Receiver, Sender};
+use apibara_node::{
+    db::libmdbx::{Environment, EnvironmentKind, Error as MdxError},
+    o11y::{self, Counter, KeyValue},
+};
 use tokio_util::sync::CancellationToken;
 use tracing::{info, warn};
 
 use crate::{
     core::GlobalBlockId,
-    db::{DatabaseStorage, StorageWriter},
-    provider::Provider,
+    db::{DatabaseStorage, StorageReader, StorageWriter},
+    ingestion::{BlockIngestionConfig, BlockIngestionError, Downloader},
+    provider::{BlockId, Provider},
 };
 
 #[derive(Debug, thiserror::Error)]
 pub enum HealerError {
-    #[error("channel was closed")]
-    ChannelClosed,
     #[error("database error")]
     Database(#[from] MdxError),
+    #[error("failed to re-ingest block")]
+    Ingestion(#[from] BlockIngestionError),
 }
 
-#[derive(Debug, Clone)]
-pub enum HealerMessage {
-    /// The given block is expected to be finalized.
-    StatusFinalizedExpected(GlobalBlockId),
-}
-
-/// A service that receives broken blocks and heals them.
+/// A service that heals broken blocks.
+///
+/// If configured with an heal interval, it periodically scans the
+/// finalized canonical chain for missing blocks or blocks whose parent hash
+/// doesn't match the previous canonical block, and re-fetches them.
 pub struct Healer<G: Provider + Send, E: EnvironmentKind> {
-    _provider: Arc<G>,
+    provider: Arc<G>,
     storage: DatabaseStorage<E>,
-    rx: Receiver<HealerMessage>,
-}
-
-#[derive(Clone)]
-pub struct HealerClient {
-    tx: Sender<HealerMessage>,
+    downloader: Downloader<G>,
+    heal_interval: Option<Duration>,
+    /// All blocks up to (and including) this one were found consistent.
+    healthy_up_to: Option<u64>,
+    healed_blocks: Counter<u64>,
 }
 
 impl<G, E> Healer<G, E>
@@ -43,58 +42,197 @@ where
     G: Provider + Send,
     E: EnvironmentKind,
 {
-    pub fn new(provider: Arc<G>, db: Arc<Environment<E>>) -> (HealerClient, Self) {
+    pub fn new(provider: Arc<G>, db: Arc<Environment<E>>, config: &BlockIngestionConfig) -> Self {
         let storage = DatabaseStorage::new(db);
-        let (tx, rx) = mpsc::channel(64);
-        let healer = Healer {
-            _provider: provider,
+        let downloader = Downloader::new(
+            provider.clone(),
+            config.rpc_concurrency,
+            config.capture_contract_classes,
+            config.capture_traces,
+        );
+        Healer {
+            provider,
             storage,
-            rx,
-        };
-        let client = HealerClient { tx };
-        (client, healer)
+            downloader,
+            heal_interval: config.heal_interval,
+            healthy_up_to: None,
+            healed_blocks: new_healed_blocks_counter(),
+        }
     }
 
     pub async fn start(mut self, ct: CancellationToken) -> Result<(), HealerError> {
+        let mut scan_interval = self.heal_interval.map(tokio::time::interval);
         loop {
             tokio::select! {
                 _ = ct.cancelled() => {
                     return Ok(())
                 }
-                msg = self.rx.recv() => {
-                    let msg = msg.ok_or(HealerError::ChannelClosed)?;
-                    self.handle_message(msg)?;
+                _ = tick(scan_interval.as_mut()) => {
+                    // provider errors are transient, try again at the next tick.
+                    if let Err(err) = self.scan_and_heal(&ct).await {
+                        warn!(error = ?err, "failed to heal canonical chain");
+                    }
                 }
             }
         }
     }
 
-    fn handle_message(&self, message: HealerMessage) -> Result<(), HealerError> {
-        info!(message = ?message, "received healer message");
-        match message {
-            HealerMessage::StatusFinalizedExpected(cursor) => {
-                self.handle_status_finalized_expected(cursor)
+    /// Scans the finalized canonical chain and re-fetches broken blocks.
+    ///
+    /// Only finalized blocks are scanned to avoid racing with ingestion of
+    /// accepted blocks, which can still be reorged.
+    async fn scan_and_heal(&mut self, ct: &CancellationToken) -> Result<(), HealerError> {
+        let Some(finalized) = self.storage.highest_finalized_block()? else {
+            return Ok(());
+        };
+        let Some(earliest) = self.storage.earliest_available_block()? else {
+            return Ok(());
+        };
+
+        let start = self.healthy_up_to.unwrap_or(earliest.number());
+        let mut parent = None;
+        let mut healed: Vec<RangeInclusive<u64>> = Vec::new();
+
+        for number in start..=finalized.number() {
+            if ct.is_cancelled() {
+                return Ok(());
             }
+
+            if let Some(block_id) = self.consistent_block_id(number, parent.as_ref())? {
+                parent = Some(block_id);
+                continue;
+            }
+
+            let (block_id, block_parent) = self.heal_block(number).await?;
+            add_to_ranges(&mut healed, number);
+
+            // the mismatch can be caused by the parent, so heal it too.
+            if let Some(parent) = parent {
+                if parent != block_parent && number > earliest.number() {
+                    self.heal_block(number - 1).await?;
+                    add_to_ranges(&mut healed, number - 1);
+                }
+            }
+
+            parent = Some(block_id);
         }
+
+        self.healthy_up_to = Some(finalized.number());
+
+        for range in healed {
+            let count = range.end() - range.start() + 1;
+            info!(
+                start = range.start(),
+                end = range.end(),
+                "healed canonical chain range"
+            );
+            let cx = o11y::Context::current();
+            self.healed_blocks
+                .add(&cx, count, &[KeyValue::new("reason", "scan")]);
+        }
+
+        Ok(())
     }
 
-    fn handle_status_finalized_expected(&self, cursor: GlobalBlockId) -> Result<(), HealerError> {
+    /// Returns the canonical block id at the given height if the block is
+    /// stored and its parent hash matches `parent`.
+    fn consistent_block_id(
+        &self,
+        number: u64,
+        parent: Option<&GlobalBlockId>,
+    ) -> Result<Option<GlobalBlockId>, HealerError> {
+        let Some(block_id) = self.storage.canonical_block_id(number)? else {
+            return Ok(None);
+        };
+        let Some(header) = self.storage.read_header(&block_id)? else {
+            return Ok(None);
+        };
+
+        if let Some(parent) = parent {
+            let header_parent = GlobalBlockId::from_block_header_parent(&header)
+                .map_err(BlockIngestionError::from)?;
+            if header_parent != *parent {
+                return Ok(None);
+            }
+        }
+
+        Ok(Some(block_id))
+    }
+
+    /// Re-fetches the block at the given height and makes it canonical.
+    ///
+    /// Returns the block id and its parent id.
+    async fn heal_block(&self, number: u64) -> Result<(GlobalBlockId, GlobalBlockId), HealerError> {
+        warn!(block_number = number, "healing block");
+        let (status, header, body) = self
+            .provider
+            .get_block(&BlockId::Number(number))
+            .await
+            .map_err(BlockIngestionError::provider)?;
+
+        let global_id =
+            GlobalBlockId::from_block_header(&header).map_err(BlockIngestionError::from)?;
+        let parent_id =
+            GlobalBlockId::from_block_header_parent(&header).map_err(BlockIngestionError::from)?;
+
         let mut txn = self.storage.begin_txn()?;
-        txn.write_status(&cursor, v1alpha2::BlockStatus::AcceptedOnL1)?;
+        self.downloader
+            .finish_ingesting_block(&global_id, status, header, body, &mut txn)
+            .await?;
+        txn.extend_canonical_chain(&global_id)?;
         txn.commit()?;
-        Ok(())
+
+        Ok((global_id, parent_id))
     }
 }
 
-impl HealerClient {
-    pub fn status_finalized_expected(&self, cursor: GlobalBlockId) {
-        self.send_message(HealerMessage::StatusFinalizedExpected(cursor))
+/// Waits for the next tick, or forever if scanning is disabled.
+async fn tick(interval: Option<&mut tokio::time::Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => futures::future::pending().await,
     }
+}
 
-    fn send_message(&self, message: HealerMessage) {
-        // healer is not critical so don't fail if it cannot send
-        if let Err(err) = self.tx.try_send(message) {
-            warn!(error = ?err, "failed to send healer message");
+/// Adds the block number to the list of sorted ranges, merging it with the
+/// last range if adjacent.
+fn add_to_ranges(ranges: &mut Vec<RangeInclusive<u64>>, number: u64) {
+    if let Some(last) = ranges.last_mut() {
+        if last.contains(&number) {
+            return;
         }
+        if *last.end() + 1 == number {
+            *last = *last.start()..=number;
+            return;
+        }
+        if number + 1 == *last.start() {
+            *last = number..=*last.end();
+            return;
+        }
+    }
+    ranges.push(number..=number);
+}
+
+fn new_healed_blocks_counter() -> Counter<u64> {
+    let meter = o11y::meter("healer");
+    meter.u64_counter("healed_blocks").init()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::add_to_ranges;
+
+    #[test]
+    fn test_add_to_ranges() {
+        let mut ranges = Vec::new();
+        add_to_ranges(&mut ranges, 5);
+        add_to_ranges(&mut ranges, 6);
+        add_to_ranges(&mut ranges, 4);
+        add_to_ranges(&mut ranges, 6);
+        add_to_ranges(&mut ranges, 10);
+        add_to_ranges(&mut ranges, 9);
+        assert_eq!(ranges, vec![4..=6, 9..=10]);
     }
 }
@@ -0,0 +1,163 @@
+//! HTTP endpoints reporting the node sync status.
+//!
+//! `/healthz` returns 200 when the node is following the chain and 503
+//! otherwise, `/status` returns the sync status as json. Both include the
+//! last error that stopped block ingestion, until ingestion recovers.
+use std::net::SocketAddr;
+
+use apibara_core::node::v1alpha2::{Cursor, StatusResponse};
+use serde_json::{json, Value};
+use tracing::info;
+use warp::{http::StatusCode, Filter};
+
+use crate::{ingestion::LastIngestionError, status::StatusClient};
+
+/// Default number of blocks the node can be behind the chain head and still be healthy.
+pub const DEFAULT_MAX_BLOCKS_BEHIND: u64 = 10;
+
+pub struct HealthServer {
+    address: String,
+    status_client: StatusClient,
+    last_ingestion_error: LastIngestionError,
+    max_blocks_behind: u64,
+}
+
+impl HealthServer {
+    pub fn new(
+        address: String,
+        status_client: StatusClient,
+        last_ingestion_error: LastIngestionError,
+        max_blocks_behind: u64,
+    ) -> Self {
+        HealthServer {
+            address,
+            status_client,
+            last_ingestion_error,
+            max_blocks_behind,
+        }
+    }
+
+    pub async fn start(self) {
+        let socket_address: SocketAddr = self.address.parse().expect("valid socket address");
+        let status_client = self.status_client;
+        let last_ingestion_error = self.last_ingestion_error;
+        let max_blocks_behind = self.max_blocks_behind;
+
+        let healthz = warp::path("healthz").and(warp::get()).then({
+            let status_client = status_client.clone();
+            let last_ingestion_error = last_ingestion_error.clone();
+            move || {
+                let status_client = status_client.clone();
+                let last_ingestion_error = last_ingestion_error.clone();
+                async move {
+                    let report =
+                        health_report(&status_client, &last_ingestion_error, max_blocks_behind)
+                            .await;
+                    let status = if report.is_healthy() {
+                        StatusCode::OK
+                    } else {
+                        StatusCode::SERVICE_UNAVAILABLE
+                    };
+                    warp::reply::with_status(warp::reply::json(&report.to_json()), status)
+                }
+            }
+        });
+
+        let status = warp::path("status").and(warp::get()).then(move || {
+            let status_client = status_client.clone();
+            let last_ingestion_error = last_ingestion_error.clone();
+            async move {
+                let report =
+                    health_report(&status_client, &last_ingestion_error, max_blocks_behind).await;
+                warp::reply::json(&report.to_json())
+            }
+        });
+
+        info!("Running health server at {}", socket_address);
+
+        warp::serve(healthz.or(status)).run(socket_address).await
+    }
+}
+
+struct HealthReport {
+    status: Option<StatusResponse>,
+    last_ingestion_error: Option<String>,
+    errors: Vec<String>,
+}
+
+async fn health_report(
+    status_client: &StatusClient,
+    last_ingestion_error: &LastIngestionError,
+    max_blocks_behind: u64,
+) -> HealthReport {
+    let last_ingestion_error = last_ingestion_error.get();
+    let mut errors = Vec::new();
+    if let Some(err) = &last_ingestion_error {
+        errors.push(format!("block ingestion failed: {err}"));
+    }
+
+    let status = match status_client.get_status().await {
+        Ok(status) => status,
+        Err(err) => {
+            errors.push(format!("status service unavailable: {err}"));
+            return HealthReport {
+                status: None,
+                last_ingestion_error,
+                errors,
+            };
+        }
+    };
+
+    match (status.current_head.as_ref(), status.last_ingested.as_ref()) {
+        (None, _) => errors.push("failed to fetch chain head from provider".to_string()),
+        (_, None) => errors.push("no block ingested yet".to_string()),
+        (Some(head), Some(ingested)) => {
+            let blocks_behind = head.order_key.saturating_sub(ingested.order_key);
+            if blocks_behind > max_blocks_behind {
+                errors.push(format!(
+                    "node is {blocks_behind} blocks behind the chain head"
+                ));
+            }
+        }
+    }
+
+    HealthReport {
+        status: Some(status),
+        last_ingestion_error,
+        errors,
+    }
+}
+
+impl HealthReport {
+    fn is_healthy(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    fn blocks_behind(&self) -> Option<u64> {
+        let status = self.status.as_ref()?;
+        let head = status.current_head.as_ref()?;
+        let ingested = status.last_ingested.as_ref()?;
+        Some(head.order_key.saturating_sub(ingested.order_key))
+    }
+
+    fn to_json(&self) -> Value {
+        let status = self.status.as_ref();
+        json!({
+            "healthy": self.is_healthy(),
+            "provider_head": status.and_then(|s| s.current_head.as_ref()).map(cursor_to_json),
+            "last_ingested": status.and_then(|s| s.last_ingested.as_ref()).map(cursor_to_json),
+            "finalized": status.and_then(|s| s.finalized.as_ref()).map(cursor_to_json),
+            "earliest_available": status.and_then(|s| s.earliest_available.as_ref()).map(cursor_to_json),
+            "blocks_behind": self.blocks_behind(),
+            "last_ingestion_error": self.last_ingestion_error,
+            "errors": self.errors,
+        })
+    }
+}
+
+fn cursor_to_json(cursor: &Cursor) -> Value {
+    json!({
+        "number": cursor.order_key,
+        "hash": format!("0x{}", hex::encode(&cursor.unique_key)),
+    })
+}
@@ -1,6 +1,10 @@
 //! Ingest accepted block data.
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
+use apibara_core::starknet::v1alpha2;
 use apibara_node::db::libmdbx::EnvironmentKind;
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, info, trace, warn};
@@ -12,7 +16,9 @@ use crate::{
 };
 
 use super::{
-    config::BlockIngestionConfig, downloader::Downloader, error::BlockIngestionError,
+    config::{BlockIngestionConfig, FinalityStrategy},
+    downloader::Downloader,
+    error::BlockIngestionError,
     subscription::IngestionStreamPublisher,
 };
 
@@ -26,6 +32,7 @@ pub struct AcceptedBlockIngestion<G: Provider + Send, E: EnvironmentKind> {
 
 struct AcceptedBlockIngestionImpl<G: Provider + Send, E: EnvironmentKind> {
     finalized: Option<GlobalBlockId>,
+    last_finality_check: Option<Instant>,
     previous: GlobalBlockId,
     current_head: GlobalBlockId,
     previous_pending_body_size: usize,
@@ -57,7 +64,12 @@ where
         config: BlockIngestionConfig,
         publisher: IngestionStreamPublisher,
     ) -> Self {
-        let downloader = Downloader::new(provider.clone(), config.rpc_concurrency);
+        let downloader = Downloader::new(
+            provider.clone(),
+            config.rpc_concurrency,
+            config.capture_contract_classes,
+            config.capture_traces,
+        );
         AcceptedBlockIngestion {
             config,
             provider,
@@ -88,6 +100,7 @@ where
         let ingestion = AcceptedBlockIngestionImpl {
             current_head,
             finalized,
+            last_finality_check: None,
             previous: latest_indexed,
             previous_pending_body_size: 0,
             config: self.config,
@@ -117,6 +130,7 @@ where
                     // no need to do anything for now
                     tokio::select! {
                         _ = tokio::time::sleep(self.config.head_refresh_interval) => {},
+                        _ = self.provider.wait_for_new_head() => {},
                         _ = ct.cancelled() => {},
                     }
                 }
@@ -168,16 +182,16 @@ where
             "check head"
         );
 
+        // finality is refreshed on its own schedule, independently of the
+        // head changing.
+        self.maybe_advance_finalized().await?;
+
         // synced, so now keep polling pending block.
         if is_synced {
             self.ingest_pending().await?;
             return Ok(TickResult::FullySynced);
         }
 
-        // only advance finalized blocks state when head changes.
-        // this is to avoid fetching the same block too often.
-        self.advance_finalized().await?;
-
         // Reset the pending body size to 0, since we are now fetching a new block.
         self.previous_pending_body_size = 0;
         self.current_head = new_head;
@@ -214,6 +228,7 @@ where
             txn.extend_canonical_chain(&ingest_result.new_block_id)?;
             txn.commit()?;
 
+            self.publisher.record_ingested_blocks("accepted", 1);
             self.publisher
                 .publish_accepted(ingest_result.new_block_id)?;
             self.previous = ingest_result.new_block_id;
@@ -225,26 +240,103 @@ where
         }
     }
 
+    /// Advance the finalized block if the refresh interval elapsed since the last check.
+    async fn maybe_advance_finalized(&mut self) -> Result<(), BlockIngestionError> {
+        let should_refresh = self
+            .last_finality_check
+            .map(|last| last.elapsed() >= self.config.finality_refresh_interval)
+            .unwrap_or(true);
+
+        if !should_refresh {
+            return Ok(());
+        }
+
+        self.last_finality_check = Some(Instant::now());
+        self.advance_finalized().await
+    }
+
     #[tracing::instrument(skip(self))]
     async fn advance_finalized(&mut self) -> Result<(), BlockIngestionError> {
+        match self.config.finality_strategy {
+            FinalityStrategy::Sequential => self.advance_finalized_sequential().await?,
+            FinalityStrategy::Bisect => self.advance_finalized_bisect().await?,
+        }
+
+        if let Some(finalized) = self.finalized {
+            self.publisher.publish_finalized(finalized)?;
+        }
+
+        Ok(())
+    }
+
+    async fn advance_finalized_sequential(&mut self) -> Result<(), BlockIngestionError> {
         while let Some(new_finalized) = self
-            .refresh_finalized_block_status(self.finalized.map(|b| b.number() + 1).unwrap_or(0))
+            .refresh_finalized_block_status(self.next_finalized_candidate())
             .await?
         {
             self.finalized = Some(new_finalized);
+            self.publisher.record_ingested_blocks("finalized", 1);
             info!(
                 finalized = %new_finalized,
                 "updated finalized block"
             );
         }
 
-        if let Some(finalized) = self.finalized {
-            self.publisher.publish_finalized(finalized)?;
+        Ok(())
+    }
+
+    /// Finality only moves forward, so the highest finalized block can be found
+    /// by bisecting the range between the current finalized block and the
+    /// last ingested block.
+    async fn advance_finalized_bisect(&mut self) -> Result<(), BlockIngestionError> {
+        let first_candidate = self.next_finalized_candidate();
+        let mut low = first_candidate;
+        let mut high = self.previous.number();
+        let mut new_finalized = None;
+
+        while low <= high {
+            let number = low + (high - low) / 2;
+            let (global_id, status) = self.fetch_block_status(number).await?;
+            if status.is_finalized() {
+                new_finalized = Some(global_id);
+                low = number + 1;
+            } else if number == 0 {
+                break;
+            } else {
+                high = number - 1;
+            }
         }
 
+        let Some(new_finalized) = new_finalized else {
+            return Ok(());
+        };
+
+        // all blocks up to the new finalized block are now finalized.
+        let mut txn = self.storage.begin_txn()?;
+        for number in first_candidate..=new_finalized.number() {
+            let global_id = self
+                .storage
+                .canonical_block_id(number)?
+                .ok_or(BlockIngestionError::InconsistentDatabase)?;
+            txn.write_status(&global_id, v1alpha2::BlockStatus::AcceptedOnL1)?;
+        }
+        txn.commit()?;
+
+        self.finalized = Some(new_finalized);
+        self.publisher
+            .record_ingested_blocks("finalized", new_finalized.number() - first_candidate + 1);
+        info!(
+            finalized = %new_finalized,
+            "updated finalized block"
+        );
+
         Ok(())
     }
 
+    fn next_finalized_candidate(&self) -> u64 {
+        self.finalized.map(|b| b.number() + 1).unwrap_or(0)
+    }
+
     #[tracing::instrument(skip(self))]
     async fn ingest_pending(&mut self) -> Result<(), BlockIngestionError> {
         // some node configurations don't support pending data.
@@ -302,6 +394,27 @@ where
         &self,
         number: u64,
     ) -> Result<Option<GlobalBlockId>, BlockIngestionError> {
+        if number > self.previous.number() {
+            return Ok(None);
+        }
+
+        let (global_id, status) = self.fetch_block_status(number).await?;
+
+        if !status.is_finalized() {
+            return Ok(None);
+        }
+
+        let mut txn = self.storage.begin_txn()?;
+        txn.write_status(&global_id, status)?;
+        txn.commit()?;
+        Ok(Some(global_id))
+    }
+
+    /// Fetch the status of the canonical block with the given number.
+    async fn fetch_block_status(
+        &self,
+        number: u64,
+    ) -> Result<(GlobalBlockId, v1alpha2::BlockStatus), BlockIngestionError> {
         let global_id = self
             .storage
             .canonical_block_id(number)?
@@ -312,15 +425,7 @@ where
             .get_block(&block_id)
             .await
             .map_err(BlockIngestionError::provider)?;
-
-        if !status.is_finalized() {
-            return Ok(None);
-        }
-
-        let mut txn = self.storage.begin_txn()?;
-        txn.write_status(&global_id, status)?;
-        txn.commit()?;
-        Ok(Some(global_id))
+        Ok((global_id, status))
     }
 
     #[tracing::instrument(skip(self), err(Debug))]
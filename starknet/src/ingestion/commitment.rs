@@ -0,0 +1,218 @@
+//! Compute block commitments from block data.
+//!
+//! Commitments are the root of a Merkle-Patricia tree of height 64, with one
+//! leaf for each item in the block.
+
+use apibara_core::starknet::v1alpha2;
+use starknet::core::types::{FieldElement, FromByteArrayError};
+use starknet_crypto::{pedersen_hash, poseidon_hash, poseidon_hash_many};
+
+const TREE_HEIGHT: usize = 64;
+
+/// The first Starknet version hashing commitments with Poseidon.
+const POSEIDON_VERSION: [u64; 3] = [0, 13, 2];
+
+/// The hash function used by a commitment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommitmentHash {
+    Pedersen,
+    Poseidon,
+}
+
+impl CommitmentHash {
+    /// Returns the hash function used by blocks with the given Starknet version.
+    pub fn for_version(starknet_version: &str) -> Self {
+        let version = starknet_version
+            .split('.')
+            .map(|part| part.parse::<u64>().unwrap_or_default())
+            .collect::<Vec<_>>();
+        if version.as_slice() >= POSEIDON_VERSION.as_slice() {
+            CommitmentHash::Poseidon
+        } else {
+            CommitmentHash::Pedersen
+        }
+    }
+
+    fn hash(&self, a: &FieldElement, b: &FieldElement) -> FieldElement {
+        match self {
+            CommitmentHash::Pedersen => pedersen_hash(a, b),
+            CommitmentHash::Poseidon => poseidon_hash(*a, *b),
+        }
+    }
+}
+
+/// Computes the event commitment of the given receipts.
+///
+/// The receipts must be sorted by transaction index.
+pub fn event_commitment<'a>(
+    hash: CommitmentHash,
+    receipts: impl IntoIterator<Item = &'a v1alpha2::TransactionReceipt>,
+) -> Result<FieldElement, FromByteArrayError> {
+    let mut leaves = Vec::new();
+    for receipt in receipts {
+        let transaction_hash = felt(receipt.transaction_hash.as_ref())?;
+        for event in &receipt.events {
+            leaves.push(event_hash(hash, &transaction_hash, event)?);
+        }
+    }
+    Ok(tree_root(hash, &leaves))
+}
+
+fn event_hash(
+    hash: CommitmentHash,
+    transaction_hash: &FieldElement,
+    event: &v1alpha2::Event,
+) -> Result<FieldElement, FromByteArrayError> {
+    let from_address = felt(event.from_address.as_ref())?;
+    let keys = event
+        .keys
+        .iter()
+        .map(|key| felt(Some(key)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let data = event
+        .data
+        .iter()
+        .map(|data| felt(Some(data)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let event_hash = match hash {
+        CommitmentHash::Pedersen => {
+            pedersen_array(&[from_address, pedersen_array(&keys), pedersen_array(&data)])
+        }
+        CommitmentHash::Poseidon => {
+            let mut elements = Vec::with_capacity(keys.len() + data.len() + 4);
+            elements.push(from_address);
+            elements.push(*transaction_hash);
+            elements.push(FieldElement::from(keys.len() as u64));
+            elements.extend(keys);
+            elements.push(FieldElement::from(data.len() as u64));
+            elements.extend(data);
+            poseidon_hash_many(&elements)
+        }
+    };
+    Ok(event_hash)
+}
+
+/// Hashes an array of elements, including its length.
+fn pedersen_array(elements: &[FieldElement]) -> FieldElement {
+    let hash = elements.iter().fold(FieldElement::ZERO, |acc, element| {
+        pedersen_hash(&acc, element)
+    });
+    pedersen_hash(&hash, &FieldElement::from(elements.len() as u64))
+}
+
+fn felt(value: Option<&v1alpha2::FieldElement>) -> Result<FieldElement, FromByteArrayError> {
+    value
+        .map(FieldElement::try_from)
+        .unwrap_or(Ok(FieldElement::ZERO))
+}
+
+/// A node of the Merkle-Patricia tree.
+enum Node {
+    /// A leaf or binary node.
+    Hash(FieldElement),
+    /// A node with a single child, `length` levels above it.
+    Edge {
+        child: FieldElement,
+        path: u64,
+        length: usize,
+    },
+}
+
+impl Node {
+    fn hash(&self, hash: CommitmentHash) -> FieldElement {
+        match self {
+            Node::Hash(value) => *value,
+            Node::Edge {
+                child,
+                path,
+                length,
+            } => hash.hash(child, &FieldElement::from(*path)) + FieldElement::from(*length as u64),
+        }
+    }
+}
+
+/// Computes the root of the tree with `leaves` at keys `0..leaves.len()`.
+pub fn tree_root(hash: CommitmentHash, leaves: &[FieldElement]) -> FieldElement {
+    if leaves.is_empty() {
+        return FieldElement::ZERO;
+    }
+    subtree(hash, leaves, TREE_HEIGHT).hash(hash)
+}
+
+/// Builds the subtree of the given `height` containing `leaves`, which are
+/// stored from the leftmost key of the subtree.
+fn subtree(hash: CommitmentHash, leaves: &[FieldElement], height: usize) -> Node {
+    if height == 0 {
+        return Node::Hash(leaves[0]);
+    }
+
+    let half = 1usize
+        .checked_shl((height - 1) as u32)
+        .unwrap_or(usize::MAX);
+    if leaves.len() <= half {
+        // only the left child is not empty, extend the edge with a 0 bit.
+        return match subtree(hash, leaves, height - 1) {
+            Node::Edge {
+                child,
+                path,
+                length,
+            } => Node::Edge {
+                child,
+                path,
+                length: length + 1,
+            },
+            node => Node::Edge {
+                child: node.hash(hash),
+                path: 0,
+                length: 1,
+            },
+        };
+    }
+
+    let left = subtree(hash, &leaves[..half], height - 1).hash(hash);
+    let right = subtree(hash, &leaves[half..], height - 1).hash(hash);
+    Node::Hash(hash.hash(&left, &right))
+}
+
+#[cfg(test)]
+mod tests {
+    use starknet::core::types::FieldElement;
+
+    use super::{tree_root, CommitmentHash};
+
+    #[test]
+    fn test_commitment_hash_for_version() {
+        assert_eq!(
+            CommitmentHash::for_version("0.13.1.1"),
+            CommitmentHash::Pedersen
+        );
+        assert_eq!(
+            CommitmentHash::for_version("0.13.2"),
+            CommitmentHash::Poseidon
+        );
+        assert_eq!(
+            CommitmentHash::for_version("0.13.10"),
+            CommitmentHash::Poseidon
+        );
+    }
+
+    #[test]
+    fn test_tree_root() {
+        let hash = CommitmentHash::Poseidon;
+        assert_eq!(tree_root(hash, &[]), FieldElement::ZERO);
+
+        // a single leaf is the child of an edge spanning the whole tree.
+        let leaf = FieldElement::from(42u64);
+        let expected = hash.hash(&leaf, &FieldElement::ZERO) + FieldElement::from(64u64);
+        assert_eq!(tree_root(hash, &[leaf]), expected);
+
+        // three leaves: a binary node between a binary node and an edge.
+        let leaves = [1u64, 2, 3].map(FieldElement::from);
+        let left = hash.hash(&leaves[0], &leaves[1]);
+        let right = hash.hash(&leaves[2], &FieldElement::ZERO) + FieldElement::ONE;
+        let node = hash.hash(&left, &right);
+        let expected = hash.hash(&node, &FieldElement::ZERO) + FieldElement::from(62u64);
+        assert_eq!(tree_root(hash, &leaves), expected);
+    }
+}
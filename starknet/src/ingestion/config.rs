@@ -10,6 +10,31 @@ pub struct BlockIngestionConfig {
     pub head_refresh_interval: Duration,
     /// Override ingestion starting block.
     pub ingestion_starting_block: Option<u64>,
+    /// Fetch and store the definition of newly declared classes.
+    pub capture_contract_classes: bool,
+    /// Fetch and store the execution traces of transactions.
+    pub capture_traces: bool,
+    /// How often to scan the canonical chain for gaps. `None` disables it.
+    pub heal_interval: Option<Duration>,
+    /// How often to check if new blocks have been accepted on L1.
+    pub finality_refresh_interval: Duration,
+    /// How to find the highest block accepted on L1.
+    pub finality_strategy: FinalityStrategy,
+    /// Only keep the data of this many blocks below the finalized block.
+    /// `None` disables pruning.
+    pub pruning_keep_blocks: Option<u64>,
+}
+
+/// Strategy used to find the highest block accepted on L1.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum FinalityStrategy {
+    /// Check blocks one by one, starting from the current finalized block.
+    #[default]
+    Sequential,
+    /// Binary search between the current finalized block and the head.
+    ///
+    /// Uses fewer requests when many blocks are finalized at once.
+    Bisect,
 }
 
 impl Default for BlockIngestionConfig {
@@ -18,6 +43,12 @@ impl Default for BlockIngestionConfig {
             rpc_concurrency: 64,
             head_refresh_interval: Duration::from_secs(3),
             ingestion_starting_block: None,
+            capture_contract_classes: false,
+            capture_traces: false,
+            heal_interval: None,
+            finality_refresh_interval: Duration::from_secs(10),
+            finality_strategy: FinalityStrategy::default(),
+            pruning_keep_blocks: None,
         }
     }
 }
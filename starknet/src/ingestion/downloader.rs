@@ -4,6 +4,7 @@ use std::sync::Arc;
 
 use apibara_core::starknet::v1alpha2;
 use futures::{stream, StreamExt};
+use tracing::warn;
 
 use crate::{
     core::GlobalBlockId,
@@ -11,21 +12,30 @@ use crate::{
     provider::{BlockId, Provider},
 };
 
-use super::BlockIngestionError;
+use super::{verify::verify_block, BlockIngestionError};
 
 pub struct Downloader<G: Provider + Send> {
     provider: Arc<G>,
     receipt_concurrency: usize,
+    capture_contract_classes: bool,
+    capture_traces: bool,
 }
 
 impl<G> Downloader<G>
 where
     G: Provider + Send,
 {
-    pub fn new(provider: Arc<G>, receipt_concurrency: usize) -> Self {
+    pub fn new(
+        provider: Arc<G>,
+        receipt_concurrency: usize,
+        capture_contract_classes: bool,
+        capture_traces: bool,
+    ) -> Self {
         Downloader {
             provider,
             receipt_concurrency,
+            capture_contract_classes,
+            capture_traces,
         }
     }
 
@@ -75,28 +85,77 @@ where
             })
             .buffer_unordered(self.receipt_concurrency);
 
-        let receipts = receipts
-            .collect::<Vec<_>>()
-            .await
-            .into_iter()
-            .collect::<Result<Vec<_>, BlockIngestionError>>()?;
+        let receipts = receipts.collect::<Vec<_>>();
+
+        let block_id = {
+            // By convention, the global id of a pending block is all zeros.
+            if global_id.hash().is_zero() {
+                BlockId::Pending
+            } else {
+                BlockId::Hash(*global_id.hash())
+            }
+        };
 
         // Not all nodes support state updates for pending blocks.
-        let state_update = {
-            let block_id = {
-                // By convention, the global id of a pending block is all zeros.
-                if global_id.hash().is_zero() {
-                    BlockId::Pending
-                } else {
-                    BlockId::Hash(*global_id.hash())
-                }
-            };
+        let state_update = async {
             match self.provider.get_state_update(&block_id).await {
                 Ok(state_update) => Some(state_update),
                 Err(_) => None,
             }
         };
 
+        // Traces are not available for pending blocks.
+        let traces = async {
+            if !self.capture_traces || block_id.is_pending() {
+                return None;
+            }
+            match self.provider.get_block_traces(&block_id).await {
+                Ok(traces) => Some(traces),
+                Err(err) => {
+                    warn!(block_id = %global_id, error = ?err, "failed to fetch block traces");
+                    None
+                }
+            }
+        };
+
+        // Commitments are only used to verify the block, pending blocks don't have them.
+        let commitments = async {
+            if block_id.is_pending() {
+                return None;
+            }
+            match self.provider.get_block_commitments(&block_id).await {
+                Ok(commitments) => commitments,
+                Err(err) => {
+                    warn!(block_id = %global_id, error = ?err, "failed to fetch block commitments");
+                    None
+                }
+            }
+        };
+
+        // receipts, state update, traces and commitments are independent, so fetch them concurrently.
+        let (receipts, state_update, traces, commitments) =
+            futures::join!(receipts, state_update, traces, commitments);
+        let mut receipts = receipts
+            .into_iter()
+            .collect::<Result<Vec<_>, BlockIngestionError>>()?;
+        // receipts are downloaded concurrently and arrive in completion order.
+        receipts.sort_by_key(|receipt| receipt.transaction_index);
+
+        if self.capture_contract_classes {
+            if let Some(state_update) = state_update.as_ref() {
+                self.download_contract_classes(&block_id, state_update, writer)
+                    .await?;
+            }
+        }
+
+        verify_block(
+            &header,
+            &body,
+            &receipts,
+            state_update.as_ref(),
+            commitments.as_ref(),
+        )?;
+
         // write block status, header, body, receipts and state update to storage
         writer.write_status(global_id, status)?;
         writer.write_header(global_id, header)?;
@@ -107,6 +166,59 @@ where
             writer.write_state_update(global_id, state_update)?;
         }
 
+        if let Some(traces) = traces {
+            writer.write_block_traces(global_id, traces)?;
+        }
+
+        Ok(())
+    }
+
+    /// Download the definition of all classes declared in the state update.
+    ///
+    /// Classes are not critical to ingestion, so failing to fetch one only
+    /// logs a warning.
+    async fn download_contract_classes<W: StorageWriter>(
+        &self,
+        block_id: &BlockId,
+        state_update: &v1alpha2::StateUpdate,
+        writer: &mut W,
+    ) -> Result<(), BlockIngestionError>
+    where
+        BlockIngestionError: From<W::Error>,
+    {
+        let Some(state_diff) = state_update.state_diff.as_ref() else {
+            return Ok(());
+        };
+
+        let class_hashes = state_diff
+            .declared_classes
+            .iter()
+            .filter_map(|class| class.class_hash.as_ref())
+            .chain(
+                state_diff
+                    .declared_contracts
+                    .iter()
+                    .filter_map(|contract| contract.class_hash.as_ref()),
+            );
+
+        let classes = stream::iter(class_hashes)
+            .map(|class_hash| async move {
+                let definition = self.provider.get_class(block_id, class_hash).await;
+                (class_hash, definition)
+            })
+            .buffer_unordered(self.receipt_concurrency)
+            .collect::<Vec<_>>()
+            .await;
+
+        for (class_hash, definition) in classes {
+            match definition {
+                Ok(definition) => writer.write_contract_class(class_hash, definition)?,
+                Err(err) => {
+                    warn!(class_hash = %class_hash, error = ?err, "failed to fetch class");
+                }
+            }
+        }
+
         Ok(())
     }
 }
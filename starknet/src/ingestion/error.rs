@@ -1,9 +1,14 @@
 //! Ingestion error.
 use apibara_node::db::libmdbx;
-use std::error::Error;
+use std::{
+    error::Error,
+    sync::{Arc, Mutex},
+};
 
 use crate::core::{InvalidBlock, InvalidBlockHashSize};
 
+use super::verify::BlockVerificationError;
+
 #[derive(Debug, thiserror::Error)]
 pub enum BlockIngestionError {
     #[error("failed to fetch provider data")]
@@ -24,6 +29,8 @@ pub enum BlockIngestionError {
     InvalidBlockHash(#[from] InvalidBlockHashSize),
     #[error(transparent)]
     InvalidBlock(#[from] InvalidBlock),
+    #[error("provider returned inconsistent block data")]
+    InconsistentBlockData(#[from] BlockVerificationError),
     #[error("failed to publish an ingestion stream message")]
     IngestionStreamPublish,
 }
@@ -36,3 +43,33 @@ impl BlockIngestionError {
         BlockIngestionError::Provider(Box::new(err))
     }
 }
+
+/// The last error that stopped block ingestion, shared with the health server.
+///
+/// It's cleared once ingestion makes progress again.
+#[derive(Debug, Clone, Default)]
+pub struct LastIngestionError(Arc<Mutex<Option<String>>>);
+
+impl LastIngestionError {
+    /// Returns the error message, if ingestion is failing.
+    pub fn get(&self) -> Option<String> {
+        self.0
+            .lock()
+            .expect("last ingestion error lock poisoned")
+            .clone()
+    }
+
+    pub(crate) fn set(&self, err: &BlockIngestionError) {
+        let mut message = err.to_string();
+        let mut source = err.source();
+        while let Some(err) = source {
+            message.push_str(&format!(": {err}"));
+            source = err.source();
+        }
+        *self.0.lock().expect("last ingestion error lock poisoned") = Some(message);
+    }
+
+    pub(crate) fn clear(&self) {
+        *self.0.lock().expect("last ingestion error lock poisoned") = None;
+    }
+}
@@ -44,7 +44,12 @@ where
         config: BlockIngestionConfig,
         publisher: IngestionStreamPublisher,
     ) -> Self {
-        let downloader = Downloader::new(provider.clone(), config.rpc_concurrency);
+        let downloader = Downloader::new(
+            provider.clone(),
+            config.rpc_concurrency,
+            config.capture_contract_classes,
+            config.capture_traces,
+        );
         FinalizedBlockIngestion {
             config,
             provider,
@@ -81,6 +86,7 @@ where
             let next_block_number = current_block.number() + 1;
             match self.ingest_block_by_number(next_block_number).await? {
                 IngestResult::Ingested(global_id) => {
+                    self.publisher.record_ingested_blocks("finalized", 1);
                     self.publisher.publish_finalized(global_id)?;
                     current_block = global_id;
                 }
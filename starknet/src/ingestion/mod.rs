@@ -1,10 +1,13 @@
 mod accepted;
+mod commitment;
 mod config;
 mod downloader;
 mod error;
 mod finalized;
+mod read_only;
 mod started;
 mod subscription;
+mod verify;
 
 use std::sync::Arc;
 
@@ -17,9 +20,12 @@ use crate::{db::DatabaseStorage, provider::Provider};
 
 use self::{started::StartedBlockIngestion, subscription::IngestionStreamPublisher};
 
+pub(crate) use self::{downloader::Downloader, verify::verify_block};
+
 pub use self::{
-    config::BlockIngestionConfig,
-    error::BlockIngestionError,
+    config::{BlockIngestionConfig, FinalityStrategy},
+    error::{BlockIngestionError, LastIngestionError},
+    read_only::ReadOnlyIngestion,
     subscription::{IngestionStream, IngestionStreamClient},
 };
 
@@ -52,6 +58,11 @@ where
         (sub_client, ingestion)
     }
 
+    /// Returns the last error that stopped ingestion.
+    pub fn last_error(&self) -> LastIngestionError {
+        self.publisher.last_error()
+    }
+
     /// Start ingesting blocks.
     pub async fn start(self, ct: CancellationToken) -> Result<(), BlockIngestionError> {
         loop {
@@ -74,11 +85,15 @@ where
                 }
                 Err(err) => {
                     error!(error = ?err, "block ingestion terminated with error");
+                    self.publisher.record_error(&err);
                 }
             }
 
             // TODO: would be better if we exponentially backed off.
-            tokio::time::sleep(Duration::from_secs(10)).await;
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(10)) => {},
+                _ = ct.cancelled() => return Ok(()),
+            }
         }
     }
 }
@@ -0,0 +1,127 @@
+//! Follow blocks ingested by another node sharing the same database.
+use std::{collections::VecDeque, sync::Arc};
+
+use apibara_node::db::libmdbx::{Environment, EnvironmentKind};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::{
+    core::GlobalBlockId,
+    db::{DatabaseStorage, StorageReader},
+};
+
+use super::{
+    config::BlockIngestionConfig,
+    error::BlockIngestionError,
+    subscription::{IngestionStreamClient, IngestionStreamPublisher},
+};
+
+/// Number of accepted blocks remembered to find the common ancestor on reorgs.
+const ACCEPTED_HISTORY_SIZE: usize = 256;
+
+/// Publishes ingestion messages by polling a database written by another
+/// process, instead of ingesting blocks from the provider.
+pub struct ReadOnlyIngestion<E: EnvironmentKind> {
+    config: BlockIngestionConfig,
+    storage: DatabaseStorage<E>,
+    publisher: IngestionStreamPublisher,
+}
+
+impl<E> ReadOnlyIngestion<E>
+where
+    E: EnvironmentKind,
+{
+    pub fn new(
+        db: Arc<Environment<E>>,
+        config: BlockIngestionConfig,
+    ) -> (IngestionStreamClient, Self) {
+        let (sub_client, publisher) = IngestionStreamPublisher::new();
+        let ingestion = ReadOnlyIngestion {
+            config,
+            storage: DatabaseStorage::new(db),
+            publisher,
+        };
+        (sub_client, ingestion)
+    }
+
+    /// Start polling the database for new blocks.
+    pub async fn start(self, ct: CancellationToken) -> Result<(), BlockIngestionError> {
+        info!("following database in read-only mode");
+        let mut finalized = self.storage.highest_finalized_block()?;
+        let mut accepted: VecDeque<GlobalBlockId> = VecDeque::new();
+        if let Some(head) = self.storage.highest_accepted_block()? {
+            accepted.push_back(head);
+        }
+
+        loop {
+            tokio::select! {
+                _ = ct.cancelled() => return Ok(()),
+                _ = tokio::time::sleep(self.config.head_refresh_interval) => {},
+            }
+
+            let new_finalized = self.storage.highest_finalized_block()?;
+            if new_finalized != finalized {
+                if let Some(new_finalized) = new_finalized {
+                    self.publisher.publish_finalized(new_finalized)?;
+                }
+                finalized = new_finalized;
+            }
+
+            let Some(head) = self.storage.highest_accepted_block()? else {
+                continue;
+            };
+
+            let mut next_block_number = match accepted.back() {
+                None => head.number(),
+                Some(previous) if *previous == head => continue,
+                Some(previous) => {
+                    if self.is_canonical(previous)? {
+                        previous.number() + 1
+                    } else {
+                        let root = self.find_common_ancestor(&mut accepted)?;
+                        warn!(root = ?root, "chain reorganization in followed database");
+                        match root {
+                            Some(root) => {
+                                self.publisher.publish_invalidate(root)?;
+                                root.number() + 1
+                            }
+                            None => head.number(),
+                        }
+                    }
+                }
+            };
+
+            while next_block_number <= head.number() {
+                let Some(block_id) = self.storage.canonical_block_id(next_block_number)? else {
+                    break;
+                };
+                self.publisher.publish_accepted(block_id)?;
+                accepted.push_back(block_id);
+                if accepted.len() > ACCEPTED_HISTORY_SIZE {
+                    accepted.pop_front();
+                }
+                next_block_number += 1;
+            }
+        }
+    }
+
+    fn is_canonical(&self, block_id: &GlobalBlockId) -> Result<bool, BlockIngestionError> {
+        let canonical = self.storage.canonical_block_id(block_id.number())?;
+        Ok(canonical.as_ref() == Some(block_id))
+    }
+
+    /// Removes non-canonical blocks from the history and returns the most
+    /// recent block that's still canonical.
+    fn find_common_ancestor(
+        &self,
+        accepted: &mut VecDeque<GlobalBlockId>,
+    ) -> Result<Option<GlobalBlockId>, BlockIngestionError> {
+        while let Some(block_id) = accepted.back() {
+            if self.is_canonical(block_id)? {
+                return Ok(Some(*block_id));
+            }
+            accepted.pop_back();
+        }
+        Ok(None)
+    }
+}
@@ -37,7 +37,12 @@ where
         config: BlockIngestionConfig,
         publisher: IngestionStreamPublisher,
     ) -> Self {
-        let downloader = Downloader::new(provider.clone(), config.rpc_concurrency);
+        let downloader = Downloader::new(
+            provider.clone(),
+            config.rpc_concurrency,
+            config.capture_contract_classes,
+            config.capture_traces,
+        );
         StartedBlockIngestion {
             config,
             provider,
@@ -48,6 +53,20 @@ where
     }
 
     pub async fn start(self, ct: CancellationToken) -> Result<(), BlockIngestionError> {
+        self.store_chain_id().await?;
+
+        if let Some(starting_block) = self.config.ingestion_starting_block {
+            if let Some(earliest) = self.storage.earliest_available_block()? {
+                if earliest.number() != starting_block {
+                    warn!(
+                        starting_block = starting_block,
+                        earliest = %earliest,
+                        "database is not empty, ignoring starting block"
+                    );
+                }
+            }
+        }
+
         loop {
             let latest_indexed = match self.storage.highest_accepted_block()? {
                 Some(block) => block,
@@ -137,6 +156,22 @@ where
         }
     }
 
+    /// Stores the chain id, so that streams can report it even if the genesis
+    /// block was pruned or never ingested.
+    async fn store_chain_id(&self) -> Result<(), BlockIngestionError> {
+        let chain_id = self
+            .provider
+            .get_chain_id()
+            .await
+            .map_err(BlockIngestionError::provider)?;
+        info!(chain_id = %chain_id, "chain id");
+
+        let mut txn = self.storage.begin_txn()?;
+        txn.write_chain_id(&chain_id)?;
+        txn.commit()?;
+        Ok(())
+    }
+
     #[tracing::instrument(skip(self))]
     async fn ingest_genesis_block(
         &self,
@@ -1,18 +1,21 @@
 use std::sync::Arc;
 
+use apibara_node::o11y::{self, Counter, KeyValue};
 use tokio::sync::broadcast;
 use tokio_stream::wrappers::BroadcastStream;
 use tracing::debug;
 
 use crate::core::{GlobalBlockId, IngestionMessage};
 
-use super::error::BlockIngestionError;
+use super::error::{BlockIngestionError, LastIngestionError};
 
 pub type IngestionStream = BroadcastStream<IngestionMessage>;
 
 #[derive(Clone)]
 pub struct IngestionStreamPublisher {
     tx: Arc<broadcast::Sender<IngestionMessage>>,
+    ingested_blocks: Counter<u64>,
+    last_error: LastIngestionError,
 }
 
 #[derive(Clone)]
@@ -25,7 +28,11 @@ impl IngestionStreamPublisher {
         let (tx, _rx) = broadcast::channel(128);
         let tx = Arc::new(tx);
 
-        let manager = IngestionStreamPublisher { tx: tx.clone() };
+        let manager = IngestionStreamPublisher {
+            tx: tx.clone(),
+            ingested_blocks: new_ingested_blocks_counter(),
+            last_error: LastIngestionError::default(),
+        };
         let client = IngestionStreamClient { tx };
         (client, manager)
     }
@@ -46,6 +53,30 @@ impl IngestionStreamPublisher {
         self.publish(IngestionMessage::Invalidate(id))
     }
 
+    /// Counts blocks written to storage (`accepted`) or marked as finalized
+    /// (`finalized`).
+    ///
+    /// Called by ingestion and not when publishing, since the same block can
+    /// be published more than once.
+    ///
+    /// Ingesting blocks clears the last ingestion error.
+    pub fn record_ingested_blocks(&self, status: &'static str, count: u64) {
+        self.last_error.clear();
+        let cx = o11y::Context::current();
+        self.ingested_blocks
+            .add(&cx, count, &[KeyValue::new("status", status)]);
+    }
+
+    /// Records the error that stopped ingestion.
+    pub fn record_error(&self, err: &BlockIngestionError) {
+        self.last_error.set(err);
+    }
+
+    /// Returns the last error recorded by ingestion.
+    pub fn last_error(&self) -> LastIngestionError {
+        self.last_error.clone()
+    }
+
     fn publish(&self, message: IngestionMessage) -> Result<(), BlockIngestionError> {
         if self.tx.receiver_count() == 0 {
             debug!("no subscribers, skipping ingestion message");
@@ -72,3 +103,8 @@ impl IngestionStreamClient {
         BroadcastStream::new(self.tx.subscribe())
     }
 }
+
+fn new_ingested_blocks_counter() -> Counter<u64> {
+    let meter = o11y::meter("ingestion");
+    meter.u64_counter("ingested_blocks").init()
+}
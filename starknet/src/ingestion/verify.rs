@@ -0,0 +1,302 @@
+//! Verify block data returned by the provider.
+//!
+//! Nodes sometimes return data that doesn't belong together (for example,
+//! receipts for a block on a different fork). These checks catch such
+//! inconsistencies before the block is written to storage.
+
+use apibara_core::starknet::v1alpha2;
+use starknet::core::types::{FieldElement, FromByteArrayError};
+
+use crate::{db::BlockBody, provider::BlockCommitments};
+
+use super::commitment::{event_commitment, CommitmentHash};
+
+#[derive(Debug, thiserror::Error)]
+pub enum BlockVerificationError {
+    #[error("block has {transactions} transactions but {receipts} receipts")]
+    ReceiptCountMismatch {
+        transactions: usize,
+        receipts: usize,
+    },
+    #[error("receipt has invalid transaction index {index}")]
+    InvalidReceiptIndex { index: u64 },
+    #[error("more than one receipt for transaction at index {index}")]
+    DuplicateReceipt { index: usize },
+    #[error("receipt at index {index} does not match its transaction hash")]
+    ReceiptHashMismatch { index: usize },
+    #[error("state update root does not match block header root")]
+    StateRootMismatch,
+    #[error("block has {actual} transactions but commitment has {expected}")]
+    TransactionCountMismatch { expected: usize, actual: usize },
+    #[error("block has {actual} events but commitment has {expected}")]
+    EventCountMismatch { expected: usize, actual: usize },
+    #[error("event commitment does not match block events")]
+    EventCommitmentMismatch,
+    #[error("failed to parse field element")]
+    InvalidFieldElement(#[from] FromByteArrayError),
+}
+
+/// Checks that the block body, receipts and state update are consistent
+/// with each other and with the block header.
+///
+/// Receipts are matched to transactions by their transaction index, so they
+/// can be in any order. If the block `commitments` are known, the number of
+/// transactions and events and the event commitment are checked too.
+pub fn verify_block(
+    header: &v1alpha2::BlockHeader,
+    body: &BlockBody,
+    receipts: &[v1alpha2::TransactionReceipt],
+    state_update: Option<&v1alpha2::StateUpdate>,
+    commitments: Option<&BlockCommitments>,
+) -> Result<(), BlockVerificationError> {
+    if body.transactions.len() != receipts.len() {
+        return Err(BlockVerificationError::ReceiptCountMismatch {
+            transactions: body.transactions.len(),
+            receipts: receipts.len(),
+        });
+    }
+
+    let mut sorted_receipts = vec![None; receipts.len()];
+    for receipt in receipts {
+        let index = receipt.transaction_index;
+        let slot = sorted_receipts
+            .get_mut(index as usize)
+            .ok_or(BlockVerificationError::InvalidReceiptIndex { index })?;
+        if slot.replace(receipt).is_some() {
+            return Err(BlockVerificationError::DuplicateReceipt {
+                index: index as usize,
+            });
+        }
+    }
+    // All indices are in range and unique, so every transaction has a receipt.
+    let sorted_receipts = sorted_receipts.into_iter().flatten().collect::<Vec<_>>();
+
+    for (index, (tx, receipt)) in body.transactions.iter().zip(&sorted_receipts).enumerate() {
+        let tx_hash = tx.meta.as_ref().and_then(|meta| meta.hash.as_ref());
+        if tx_hash != receipt.transaction_hash.as_ref() {
+            return Err(BlockVerificationError::ReceiptHashMismatch { index });
+        }
+    }
+
+    // Pending blocks don't have a state root yet.
+    if let Some(state_update) = state_update {
+        if let (Some(header_root), Some(state_root)) =
+            (header.new_root.as_ref(), state_update.new_root.as_ref())
+        {
+            if header_root != state_root {
+                return Err(BlockVerificationError::StateRootMismatch);
+            }
+        }
+    }
+
+    if let Some(commitments) = commitments {
+        verify_commitments(header, body, &sorted_receipts, commitments)?;
+    }
+
+    Ok(())
+}
+
+fn verify_commitments(
+    header: &v1alpha2::BlockHeader,
+    body: &BlockBody,
+    receipts: &[&v1alpha2::TransactionReceipt],
+    commitments: &BlockCommitments,
+) -> Result<(), BlockVerificationError> {
+    if body.transactions.len() != commitments.transaction_count {
+        return Err(BlockVerificationError::TransactionCountMismatch {
+            expected: commitments.transaction_count,
+            actual: body.transactions.len(),
+        });
+    }
+
+    let event_count = receipts.iter().map(|receipt| receipt.events.len()).sum();
+    if event_count != commitments.event_count {
+        return Err(BlockVerificationError::EventCountMismatch {
+            expected: commitments.event_count,
+            actual: event_count,
+        });
+    }
+
+    // Old blocks don't have an event commitment.
+    if let Some(expected) = commitments.event_commitment.as_ref() {
+        let expected = FieldElement::try_from(expected)?;
+        let hash = CommitmentHash::for_version(&header.starknet_version);
+        if event_commitment(hash, receipts.iter().copied())? != expected {
+            return Err(BlockVerificationError::EventCommitmentMismatch);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use apibara_core::starknet::v1alpha2;
+
+    use crate::{
+        db::BlockBody,
+        ingestion::commitment::{event_commitment, CommitmentHash},
+        provider::BlockCommitments,
+    };
+
+    use super::{verify_block, BlockVerificationError};
+
+    fn transaction(hash: u64) -> v1alpha2::Transaction {
+        v1alpha2::Transaction {
+            meta: Some(v1alpha2::TransactionMeta {
+                hash: Some(v1alpha2::FieldElement::from_u64(hash)),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn receipt(index: u64, hash: u64) -> v1alpha2::TransactionReceipt {
+        v1alpha2::TransactionReceipt {
+            transaction_index: index,
+            transaction_hash: Some(v1alpha2::FieldElement::from_u64(hash)),
+            ..Default::default()
+        }
+    }
+
+    fn event(from_address: u64, key: u64) -> v1alpha2::Event {
+        v1alpha2::Event {
+            from_address: Some(v1alpha2::FieldElement::from_u64(from_address)),
+            keys: vec![v1alpha2::FieldElement::from_u64(key)],
+            data: vec![v1alpha2::FieldElement::from_u64(key + 1)],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_consistent_block() {
+        let header = v1alpha2::BlockHeader {
+            new_root: Some(v1alpha2::FieldElement::from_u64(42)),
+            ..Default::default()
+        };
+        let body = BlockBody {
+            transactions: vec![transaction(1), transaction(2)],
+        };
+        let receipts = vec![receipt(0, 1), receipt(1, 2)];
+        let state_update = v1alpha2::StateUpdate {
+            new_root: Some(v1alpha2::FieldElement::from_u64(42)),
+            ..Default::default()
+        };
+        assert!(verify_block(&header, &body, &receipts, Some(&state_update), None).is_ok());
+    }
+
+    #[test]
+    fn test_receipts_out_of_order() {
+        let header = v1alpha2::BlockHeader::default();
+        let body = BlockBody {
+            transactions: vec![transaction(1), transaction(2), transaction(3)],
+        };
+        let receipts = vec![receipt(2, 3), receipt(0, 1), receipt(1, 2)];
+        assert!(verify_block(&header, &body, &receipts, None, None).is_ok());
+    }
+
+    #[test]
+    fn test_receipt_mismatch() {
+        let header = v1alpha2::BlockHeader::default();
+        let body = BlockBody {
+            transactions: vec![transaction(1), transaction(2)],
+        };
+
+        let receipts = vec![receipt(0, 1)];
+        assert!(matches!(
+            verify_block(&header, &body, &receipts, None, None),
+            Err(BlockVerificationError::ReceiptCountMismatch { .. })
+        ));
+
+        let receipts = vec![receipt(1, 3), receipt(0, 1)];
+        assert!(matches!(
+            verify_block(&header, &body, &receipts, None, None),
+            Err(BlockVerificationError::ReceiptHashMismatch { index: 1 })
+        ));
+
+        let receipts = vec![receipt(0, 1), receipt(0, 1)];
+        assert!(matches!(
+            verify_block(&header, &body, &receipts, None, None),
+            Err(BlockVerificationError::DuplicateReceipt { index: 0 })
+        ));
+
+        let receipts = vec![receipt(0, 1), receipt(2, 2)];
+        assert!(matches!(
+            verify_block(&header, &body, &receipts, None, None),
+            Err(BlockVerificationError::InvalidReceiptIndex { index: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_state_root_mismatch() {
+        let header = v1alpha2::BlockHeader {
+            new_root: Some(v1alpha2::FieldElement::from_u64(42)),
+            ..Default::default()
+        };
+        let state_update = v1alpha2::StateUpdate {
+            new_root: Some(v1alpha2::FieldElement::from_u64(43)),
+            ..Default::default()
+        };
+        assert!(matches!(
+            verify_block(
+                &header,
+                &BlockBody::default(),
+                &[],
+                Some(&state_update),
+                None
+            ),
+            Err(BlockVerificationError::StateRootMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_commitments() {
+        let header = v1alpha2::BlockHeader {
+            starknet_version: "0.13.2".to_string(),
+            ..Default::default()
+        };
+        let body = BlockBody {
+            transactions: vec![transaction(1), transaction(2)],
+        };
+        let mut first = receipt(0, 1);
+        first.events = vec![event(10, 100), event(11, 110)];
+        let mut second = receipt(1, 2);
+        second.events = vec![event(12, 120)];
+        let receipts = vec![second.clone(), first.clone()];
+
+        let commitment = event_commitment(CommitmentHash::Poseidon, [&first, &second]).unwrap();
+        let commitments = BlockCommitments {
+            transaction_count: 2,
+            event_count: 3,
+            event_commitment: Some(commitment.into()),
+        };
+        assert!(verify_block(&header, &body, &receipts, None, Some(&commitments)).is_ok());
+
+        let wrong_count = BlockCommitments {
+            transaction_count: 3,
+            ..commitments.clone()
+        };
+        assert!(matches!(
+            verify_block(&header, &body, &receipts, None, Some(&wrong_count)),
+            Err(BlockVerificationError::TransactionCountMismatch { .. })
+        ));
+
+        let wrong_count = BlockCommitments {
+            event_count: 2,
+            ..commitments.clone()
+        };
+        assert!(matches!(
+            verify_block(&header, &body, &receipts, None, Some(&wrong_count)),
+            Err(BlockVerificationError::EventCountMismatch { .. })
+        ));
+
+        // swapping events changes the commitment.
+        second.events.push(first.events.remove(0));
+        first.events.push(second.events.remove(0));
+        let receipts = vec![first, second];
+        assert!(matches!(
+            verify_block(&header, &body, &receipts, None, Some(&commitments)),
+            Err(BlockVerificationError::EventCommitmentMismatch)
+        ));
+    }
+}
@@ -0,0 +1,221 @@
+//! Inspect data stored by the node.
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use apibara_core::starknet::v1alpha2;
+use apibara_node::db::{
+    default_data_dir,
+    libmdbx::{Environment, NoWriteMap},
+    MdbxEnvironmentExt,
+};
+use clap::{Args, ValueEnum};
+use error_stack::{Result, ResultExt};
+use serde_json::{json, Value};
+use tracing::warn;
+
+use crate::{
+    core::GlobalBlockId,
+    db::{DatabaseConfig, DatabaseStorage, StorageReader},
+    DatabaseArgs, StarknetError,
+};
+
+#[derive(Clone, Debug, Args)]
+pub struct InspectArgs {
+    /// Data directory. Defaults to `$XDG_DATA_HOME`.
+    #[arg(long, env)]
+    pub data: Option<PathBuf>,
+    /// Indexer name. Defaults to `starknet`.
+    #[arg(long, env)]
+    pub name: Option<String>,
+    #[command(flatten)]
+    pub database: DatabaseArgs,
+    /// The data to dump.
+    #[arg(long, value_enum, default_value_t = InspectData::Header)]
+    pub data_type: InspectData,
+    /// First block to dump.
+    #[arg(long)]
+    pub from_block: u64,
+    /// Last block to dump (inclusive). Defaults to `from_block`.
+    #[arg(long)]
+    pub to_block: Option<u64>,
+    /// Only dump events and storage diffs of this contract.
+    ///
+    /// Only supported with `events` and `state-update` data.
+    #[arg(long)]
+    pub contract: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum InspectData {
+    /// Block header.
+    Header,
+    /// Block transactions.
+    Transactions,
+    /// Transaction receipts.
+    Receipts,
+    /// Events emitted in the block.
+    Events,
+    /// Block state update, including storage diffs.
+    StateUpdate,
+    /// Transaction traces, if captured during ingestion.
+    Traces,
+}
+
+/// Dumps the requested data to stdout, one json object per block.
+///
+/// Blocks missing from the canonical chain are skipped and reported as a warning.
+pub fn inspect(args: InspectArgs) -> Result<(), StarknetError> {
+    let contract = args
+        .contract
+        .as_deref()
+        .map(v1alpha2::FieldElement::from_hex)
+        .transpose()
+        .change_context(StarknetError)
+        .attach_printable("failed to parse contract address")?;
+
+    if contract.is_some()
+        && !matches!(
+            args.data_type,
+            InspectData::Events | InspectData::StateUpdate
+        )
+    {
+        return Err(StarknetError)
+            .attach_printable("--contract is only supported with events and state-update data");
+    }
+
+    let storage = open_storage(
+        args.data,
+        args.name.as_deref(),
+        &args.database.to_database_config(),
+        true,
+    )?;
+
+    let to_block = args.to_block.unwrap_or(args.from_block);
+    let mut stdout = io::stdout().lock();
+    let mut gap_start = None;
+    for block_number in args.from_block..=to_block {
+        let Some(block_id) = storage
+            .canonical_block_id(block_number)
+            .change_context(StarknetError)?
+        else {
+            gap_start.get_or_insert(block_number);
+            continue;
+        };
+
+        if let Some(gap_start) = gap_start.take() {
+            warn_gap(gap_start, block_number - 1);
+        }
+
+        let data = inspect_block(&storage, &block_id, args.data_type, contract.as_ref())
+            .change_context(StarknetError)
+            .attach_printable_lazy(|| format!("failed to read block {block_id}"))?;
+
+        let line = json!({
+            "blockNumber": block_id.number(),
+            "blockHash": v1alpha2::FieldElement::from(block_id.hash()),
+            "data": data,
+        });
+
+        writeln!(stdout, "{line}").change_context(StarknetError)?;
+    }
+
+    if let Some(gap_start) = gap_start {
+        warn_gap(gap_start, to_block);
+    }
+
+    Ok(())
+}
+
+fn warn_gap(from_block: u64, to_block: u64) {
+    warn!(from_block, to_block, "canonical blocks missing");
+}
+
+/// Opens the node database in the given (or default) data directory.
+///
+/// A read-only database can be opened while the node is running.
+pub(crate) fn open_storage(
+    data: Option<PathBuf>,
+    name: Option<&str>,
+    config: &DatabaseConfig,
+    read_only: bool,
+) -> Result<DatabaseStorage<NoWriteMap>, StarknetError> {
+    let db = open_environment(data, name, config, read_only)?;
+    Ok(DatabaseStorage::new(db))
+}
+
+/// Opens the node database environment with the given geometry.
+pub(crate) fn open_environment(
+    data: Option<PathBuf>,
+    name: Option<&str>,
+    config: &DatabaseConfig,
+    read_only: bool,
+) -> Result<Arc<Environment<NoWriteMap>>, StarknetError> {
+    let datadir = match data {
+        Some(datadir) => datadir,
+        None => default_data_dir()
+            .map(|p| p.join(name.unwrap_or("starknet")))
+            .ok_or(StarknetError)
+            .attach_printable("no datadir")?,
+    };
+
+    let mut builder = Environment::<NoWriteMap>::builder()
+        .with_size_gib(config.min_size_gib, config.max_size_gib)
+        .with_growth_step_gib(config.growth_step_gib);
+    if let Some(max_readers) = config.max_readers {
+        builder = builder.with_max_readers(max_readers);
+    }
+    if read_only {
+        builder = builder.with_read_only();
+    }
+    let db = builder
+        .open(&datadir)
+        .change_context(StarknetError)
+        .attach_printable_lazy(|| format!("failed to open database at {datadir:?}"))?;
+
+    Ok(Arc::new(db))
+}
+
+fn inspect_block<R: StorageReader>(
+    storage: &R,
+    block_id: &GlobalBlockId,
+    data_type: InspectData,
+    contract: Option<&v1alpha2::FieldElement>,
+) -> std::result::Result<Value, R::Error> {
+    let value = match data_type {
+        InspectData::Header => json!(storage.read_header(block_id)?),
+        InspectData::Transactions => json!(storage.read_body(block_id)?),
+        InspectData::Receipts => json!(storage.read_receipts(block_id)?),
+        InspectData::Events => {
+            let events = storage
+                .read_receipts(block_id)?
+                .into_iter()
+                .flat_map(|receipt| receipt.events)
+                .filter(|event| contract.is_none() || event.from_address.as_ref() == contract)
+                .collect::<Vec<_>>();
+            json!(events)
+        }
+        InspectData::StateUpdate => {
+            let state_update = storage.read_state_update(block_id)?;
+            let storage_diffs = match contract {
+                None => storage.read_all_storage_diff(block_id)?,
+                Some(contract) => storage
+                    .read_storage_diff(block_id, contract)?
+                    .into_iter()
+                    .collect(),
+            };
+            json!({
+                "stateUpdate": state_update,
+                "storageDiffs": storage_diffs,
+            })
+        }
+        InspectData::Traces => match storage.read_block_traces(block_id)? {
+            None => Value::Null,
+            Some(traces) => serde_json::from_slice(&traces).unwrap_or(Value::Null),
+        },
+    };
+
+    Ok(value)
+}
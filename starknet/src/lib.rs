@@ -1,9 +1,18 @@
+pub mod archive;
+pub mod bench;
+pub mod cli;
 pub mod core;
 pub mod db;
+mod devnet;
 pub mod healer;
+pub mod health;
 pub mod ingestion;
+pub mod inspect;
+pub mod maintenance;
+pub mod metrics;
 pub mod node;
 pub mod provider;
+pub mod pruner;
 pub mod server;
 pub mod status;
 pub mod stream;
@@ -14,18 +23,32 @@ pub use crate::provider::HttpProvider;
 
 pub use apibara_node::{
     db::libmdbx::NoWriteMap,
-    server::{MetadataKeyRequestObserver, SimpleRequestObserver},
+    server::{MetadataKeyRequestObserver, SimpleRequestObserver, TenantConfiguration},
 };
 use apibara_sdk::Uri;
-use ingestion::BlockIngestionConfig;
+use db::DatabaseConfig;
+use ingestion::{BlockIngestionConfig, FinalityStrategy};
+use node::WaitForRpcConfig;
+use provider::RetryConfig;
 
-use std::{fmt, path::PathBuf, time::Duration};
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
-use apibara_node::{db::default_data_dir, server::QuotaConfiguration};
+use apibara_node::{
+    db::default_data_dir,
+    server::{AuthConfiguration, LocalQuotaLimits, QuotaConfiguration, StaticQuotaPolicy},
+};
 use clap::Args;
 use error_stack::{Result, ResultExt};
-use tempdir::TempDir;
+use serde::Deserialize;
 use tokio_util::sync::CancellationToken;
+use tonic::transport::{Certificate, Identity, ServerTlsConfig};
 use tracing::info;
 
 #[derive(Clone, Debug, Args)]
@@ -33,35 +56,160 @@ pub struct StartArgs {
     /// StarkNet RPC address.
     #[arg(long, env)]
     pub rpc: String,
+    /// Fallback StarkNet RPC addresses, used when the main RPC is not available.
+    #[arg(long, env, value_delimiter = ',')]
+    pub fallback_rpc: Vec<String>,
+    /// Set an upper bound on the number of RPC requests per second.
+    #[arg(long, env)]
+    pub rpc_rate_limit: Option<NonZeroU32>,
+    /// Number of times a failed RPC request is retried before giving up.
+    #[arg(long, env)]
+    pub rpc_max_retries: Option<usize>,
+    /// Delay before retrying a failed RPC request (in milliseconds), doubled
+    /// after each retry.
+    #[arg(long, env)]
+    pub rpc_initial_backoff_ms: Option<u64>,
+    /// Upper bound on the delay between RPC retries (in milliseconds).
+    #[arg(long, env)]
+    pub rpc_max_backoff_ms: Option<u64>,
+    /// How long a failing RPC endpoint is skipped before being tried again (in seconds).
+    #[arg(long, env)]
+    pub rpc_unhealthy_cooldown_secs: Option<u64>,
+    /// Check the health of all RPC endpoints every given number of seconds.
+    ///
+    /// By default, endpoints are only marked as unhealthy when a request fails.
+    #[arg(long, env)]
+    pub rpc_health_check_interval_secs: Option<u64>,
+    /// Sequencer feeder gateway address, used when the RPC is lagging or not available.
+    #[arg(long, env)]
+    pub feeder_gateway: Option<String>,
+    /// StarkNet RPC websocket address, used to subscribe to new heads.
+    ///
+    /// Only pathfinder subscriptions are supported. The head is still polled
+    /// in case notifications are missed.
+    #[arg(long, env)]
+    pub rpc_ws: Option<String>,
     /// Data directory. Defaults to `$XDG_DATA_HOME`.
     #[arg(long, env)]
     pub data: Option<PathBuf>,
     /// Indexer name. Defaults to `starknet`.
     #[arg(long, env)]
     pub name: Option<String>,
+    /// Maximum number of concurrent RPC requests while ingesting a block.
+    #[arg(long, env)]
+    pub rpc_concurrency: Option<usize>,
     /// Head refresh interval (in milliseconds).
     #[arg(long, env)]
     pub head_refresh_interval_ms: Option<u64>,
+    /// How often to check for blocks accepted on L1 (in seconds).
+    #[arg(long, env)]
+    pub finality_refresh_interval_secs: Option<u64>,
+    /// How to find the highest block accepted on L1.
+    #[arg(long, env, value_enum, default_value_t = FinalityStrategy::Sequential)]
+    pub finality_strategy: FinalityStrategy,
     /// Wait for RPC to be available before starting.
     #[arg(long, env)]
     pub wait_for_rpc: bool,
+    /// Stop waiting for RPC after this many seconds. Waits forever by default.
+    #[arg(long, env, requires = "wait_for_rpc")]
+    pub wait_for_rpc_timeout_secs: Option<u64>,
+    /// Delay before retrying to connect to RPC, doubled after each attempt. Defaults to 1.
+    #[arg(long, env, requires = "wait_for_rpc")]
+    pub wait_for_rpc_initial_backoff_secs: Option<u64>,
+    /// Maximum delay between attempts to connect to RPC. Defaults to 30.
+    #[arg(long, env, requires = "wait_for_rpc")]
+    pub wait_for_rpc_max_backoff_secs: Option<u64>,
     /// Set an upper bound on the number of blocks per second clients can stream.
     #[arg(long, env)]
     pub blocks_per_second_limit: Option<u32>,
+    /// Limit the number of streams served concurrently.
+    ///
+    /// Streams over the limit are rejected, clients should retry later.
+    #[arg(long, env)]
+    pub max_concurrent_streams: Option<usize>,
+    /// Open the database read-only and only serve data, without ingesting blocks.
+    ///
+    /// Use this to run multiple servers sharing the database written by a
+    /// single node.
+    #[arg(long, env, conflicts_with = "devnet")]
+    pub read_only: bool,
     /// Create a temporary directory for data, deleted when devnet is closed.
     #[arg(long, env)]
     pub devnet: bool,
     /// Use the specified metadata key for tracing and metering.
     #[arg(long, env)]
     pub use_metadata: Vec<String>,
+    /// Load the tenants configuration from this json file.
+    ///
+    /// Requests are routed to tenants based on a metadata value, each tenant
+    /// has its own rate limit and is added to traces and metrics.
+    #[arg(long, env)]
+    pub tenants_file: Option<PathBuf>,
     #[command(flatten)]
     pub quota_server: Option<QuotaServerArgs>,
+    #[command(flatten)]
+    pub auth: Option<AuthArgs>,
+    #[command(flatten)]
+    pub tls: Option<TlsArgs>,
+    #[command(flatten)]
+    pub database: DatabaseArgs,
     /// Bind the DNA server to this address, defaults to `0.0.0.0:7171`.
     #[arg(long, env)]
     pub address: Option<String>,
-    // Websocket address
-    #[arg(long, env)]
+    /// Bind the DNA server to this port on all interfaces.
+    #[arg(long, env, conflicts_with = "address")]
+    pub port: Option<u16>,
+    /// Serve data over websocket (`/ws`) and server-sent events (`/stream`) at this address.
+    ///
+    /// The gateway doesn't authenticate or meter clients, so it can't be used
+    /// together with authentication, quotas, tenants or stream limits.
+    #[arg(
+        long,
+        env,
+        conflicts_with_all = [
+            "auth_token",
+            "auth_jwt_secret",
+            "auth_jwks_url",
+            "quota_server_address",
+            "local_quota_blocks",
+            "local_quota_max_streams",
+            "local_quota_file",
+            "tenants_file",
+            "max_concurrent_streams",
+        ]
+    )]
     pub websocket_address: Option<String>,
+    /// Serve the `/healthz` and `/status` http endpoints at this address.
+    #[arg(long, env)]
+    pub health_address: Option<String>,
+    /// Report the node as unhealthy when it's more than this many blocks behind the chain head.
+    #[arg(long, env)]
+    pub health_max_blocks_behind: Option<u64>,
+    /// Fetch and store the definition of newly declared classes.
+    #[arg(long, env)]
+    pub capture_contract_classes: bool,
+    /// Fetch and store the execution traces of transactions.
+    #[arg(long, env)]
+    pub capture_traces: bool,
+    /// Periodically scan the finalized chain for missing or inconsistent
+    /// blocks and re-fetch them, every given number of seconds.
+    #[arg(long, env)]
+    pub heal_interval_secs: Option<u64>,
+    /// Delete the data of finalized blocks older than this many blocks.
+    ///
+    /// Block headers are kept. Pruned blocks are not available to clients.
+    #[arg(long, env)]
+    pub prune_keep_blocks: Option<u64>,
+    /// Start ingesting from this block instead of genesis.
+    ///
+    /// Only used when the database is empty. Blocks before it are not available
+    /// to clients.
+    #[arg(
+        long,
+        env,
+        conflicts_with = "dangerously_override_ingestion_start_block"
+    )]
+    pub starting_block: Option<u64>,
     /// Override the ingestion starting block.
     ///
     /// This should be used only for testing and never in production.
@@ -80,6 +228,197 @@ pub struct QuotaServerArgs {
     /// Metadata key used to identify the client.
     #[arg(long, env)]
     pub client_metadata_key: Option<String>,
+    /// Track quota in memory, allowing each client to stream this many blocks per period.
+    ///
+    /// Clients are identified by the client metadata key.
+    #[arg(long, env, conflicts_with = "quota_server_address")]
+    pub local_quota_blocks: Option<u64>,
+    /// Track concurrent streams in memory, allowing each client to open this many streams.
+    ///
+    /// Clients are identified by the client metadata key.
+    #[arg(long, env, conflicts_with = "quota_server_address")]
+    pub local_quota_max_streams: Option<usize>,
+    /// Load per-client local quota limits from this json file.
+    ///
+    /// The `--local-quota-*` flags override the file's default limits.
+    #[arg(long, env, conflicts_with = "quota_server_address")]
+    pub local_quota_file: Option<PathBuf>,
+    /// Length of the local quota period, in seconds. Defaults to one day.
+    #[arg(long, env)]
+    pub local_quota_period_secs: Option<u64>,
+}
+
+/// Local quota limits, loaded from the `--local-quota-file` file.
+#[derive(Debug, Default, Deserialize)]
+struct LocalQuotaFile {
+    /// Limits applied to clients not listed in `clients`.
+    #[serde(default)]
+    default: LocalQuotaLimits,
+    /// Limits for specific clients, by client name.
+    #[serde(default)]
+    clients: HashMap<String, LocalQuotaLimits>,
+}
+
+impl QuotaServerArgs {
+    /// Returns the local quota configuration, if any local quota option is set.
+    pub fn to_local_quota_configuration(
+        &self,
+    ) -> Result<Option<QuotaConfiguration>, StarknetError> {
+        if self.local_quota_blocks.is_none()
+            && self.local_quota_max_streams.is_none()
+            && self.local_quota_file.is_none()
+        {
+            return Ok(None);
+        }
+
+        let client_metadata_key = self
+            .client_metadata_key
+            .clone()
+            .ok_or(StarknetError)
+            .attach_printable("local quota requires a client metadata key")?;
+
+        let mut limits = if let Some(path) = &self.local_quota_file {
+            let content = fs::read_to_string(path)
+                .change_context(StarknetError)
+                .attach_printable_lazy(|| format!("failed to read quota file {path:?}"))?;
+            serde_json::from_str::<LocalQuotaFile>(&content)
+                .change_context(StarknetError)
+                .attach_printable("failed to parse quota file")?
+        } else {
+            LocalQuotaFile::default()
+        };
+
+        if let Some(blocks) = self.local_quota_blocks {
+            limits.default.data_units_per_period = Some(blocks);
+        }
+
+        if let Some(max_streams) = self.local_quota_max_streams {
+            limits.default.max_concurrent_streams = Some(max_streams);
+        }
+
+        let period = Duration::from_secs(self.local_quota_period_secs.unwrap_or(86_400).max(1));
+        let policy = StaticQuotaPolicy {
+            default_limits: limits.default,
+            client_limits: limits.clients,
+        };
+        Ok(Some(QuotaConfiguration::LocalQuota {
+            client_metadata_key,
+            period,
+            policy: Arc::new(policy),
+            meter: None,
+        }))
+    }
+}
+
+#[derive(Default, Clone, Debug, Args)]
+pub struct DatabaseArgs {
+    /// Initial size of the database, in GiB. Defaults to 10.
+    #[arg(long, env)]
+    pub db_min_size_gib: Option<usize>,
+    /// Maximum size of the database, in GiB. Defaults to 512.
+    #[arg(long, env)]
+    pub db_max_size_gib: Option<usize>,
+    /// How much the database grows when full, in GiB. Defaults to 2.
+    #[arg(long, env)]
+    pub db_growth_step_gib: Option<isize>,
+    /// Maximum number of concurrent database readers.
+    #[arg(long, env)]
+    pub db_max_readers: Option<u64>,
+}
+
+impl DatabaseArgs {
+    pub fn to_database_config(&self) -> DatabaseConfig {
+        let mut config = DatabaseConfig::default();
+        if let Some(min_size) = self.db_min_size_gib {
+            config.min_size_gib = min_size;
+        }
+        if let Some(max_size) = self.db_max_size_gib {
+            config.max_size_gib = max_size;
+        }
+        if let Some(growth_step) = self.db_growth_step_gib {
+            config.growth_step_gib = growth_step;
+        }
+        config.max_readers = self.db_max_readers;
+        config
+    }
+}
+
+#[derive(Default, Clone, Debug, Args)]
+pub struct AuthArgs {
+    /// Accept the given bearer tokens, formatted as `identity:token`.
+    #[arg(long, env, value_delimiter = ',')]
+    pub auth_token: Vec<String>,
+    /// Accept JWTs signed with this HMAC secret.
+    #[arg(long, env, conflicts_with_all = ["auth_token", "auth_jwks_url"])]
+    pub auth_jwt_secret: Option<String>,
+    /// Accept JWTs signed with the keys published at this url.
+    #[arg(long, env, conflicts_with_all = ["auth_token", "auth_jwt_secret"])]
+    pub auth_jwks_url: Option<String>,
+}
+
+impl AuthArgs {
+    pub fn to_auth_configuration(&self) -> Result<AuthConfiguration, StarknetError> {
+        if let Some(url) = &self.auth_jwks_url {
+            return Ok(AuthConfiguration::Jwks(url.clone()));
+        }
+
+        if let Some(secret) = &self.auth_jwt_secret {
+            return Ok(AuthConfiguration::JwtSecret(secret.clone()));
+        }
+
+        if self.auth_token.is_empty() {
+            return Ok(AuthConfiguration::NoAuth);
+        }
+
+        let mut tokens = HashMap::new();
+        for token in &self.auth_token {
+            let (identity, token) = token
+                .split_once(':')
+                .ok_or(StarknetError)
+                .attach_printable("auth token must be formatted as identity:token")?;
+            tokens.insert(token.to_string(), identity.to_string());
+        }
+
+        Ok(AuthConfiguration::StaticTokens(tokens))
+    }
+}
+
+#[derive(Default, Clone, Debug, Args)]
+pub struct TlsArgs {
+    /// Path to the PEM-encoded certificate used to serve the DNA stream over TLS.
+    #[arg(long, env, requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+    /// Path to the PEM-encoded private key of the TLS certificate.
+    #[arg(long, env, requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+    /// Path to the PEM-encoded CA certificate used to verify clients (mTLS).
+    #[arg(long, env, requires = "tls_cert")]
+    pub tls_client_ca: Option<PathBuf>,
+}
+
+impl TlsArgs {
+    pub fn to_tls_config(&self) -> Result<Option<ServerTlsConfig>, StarknetError> {
+        let (Some(cert_path), Some(key_path)) = (&self.tls_cert, &self.tls_key) else {
+            return Ok(None);
+        };
+
+        let cert = read_pem(cert_path)?;
+        let key = read_pem(key_path)?;
+        let mut config = ServerTlsConfig::new().identity(Identity::from_pem(cert, key));
+
+        if let Some(client_ca_path) = &self.tls_client_ca {
+            let client_ca = read_pem(client_ca_path)?;
+            config = config.client_ca_root(Certificate::from_pem(client_ca));
+        }
+
+        Ok(Some(config))
+    }
+}
+
+fn read_pem(path: &Path) -> Result<Vec<u8>, StarknetError> {
+    fs::read(path)
+        .change_context(StarknetError)
+        .attach_printable_lazy(|| format!("failed to read {path:?}"))
 }
 
 /// Connect the cancellation token to the ctrl-c handler.
@@ -106,17 +445,95 @@ impl fmt::Display for StarknetError {
 }
 
 pub async fn start_node(args: StartArgs, cts: CancellationToken) -> Result<(), StarknetError> {
+    if args.devnet {
+        return devnet::start_devnet_node(args, cts).await;
+    }
+
+    let wait_for_rpc = args.wait_for_rpc;
+    node_builder(args)?
+        .build()
+        .change_context(StarknetError)
+        .attach_printable("failed to initialize node")?
+        .start(cts, wait_for_rpc)
+        .await
+        .change_context(StarknetError)
+        .attach_printable("error while running starknet node")?;
+
+    Ok(())
+}
+
+/// Creates the request observer, routing requests to tenants if configured.
+fn request_observer(args: &StartArgs) -> Result<MetadataKeyRequestObserver, StarknetError> {
+    let observer = MetadataKeyRequestObserver::new(args.use_metadata.clone());
+    let Some(path) = &args.tenants_file else {
+        return Ok(observer);
+    };
+
+    let content = fs::read_to_string(path)
+        .change_context(StarknetError)
+        .attach_printable_lazy(|| format!("failed to read tenants file {path:?}"))?;
+    let configuration = serde_json::from_str::<TenantConfiguration>(&content)
+        .change_context(StarknetError)
+        .attach_printable("failed to parse tenants file")?;
+    Ok(observer.with_tenants(configuration))
+}
+
+/// Creates a node builder configured from the command line arguments.
+///
+/// In devnet mode, the caller is responsible for setting the datadir.
+pub(crate) fn node_builder(
+    args: StartArgs,
+) -> Result<node::StarkNetNodeBuilder<MetadataKeyRequestObserver, NoWriteMap>, StarknetError> {
     let mut node =
         StarkNetNode::<HttpProvider, SimpleRequestObserver, NoWriteMap>::builder(&args.rpc)
             .change_context(StarknetError)
             .attach_printable("failed to create server")?
-            .with_request_observer(MetadataKeyRequestObserver::new(args.use_metadata));
+            .with_request_observer(request_observer(&args)?);
 
-    if args.devnet {
-        let tempdir = TempDir::new("apibara").change_context(StarknetError)?;
-        info!("starting in devnet mode");
-        node.with_datadir(tempdir.path().to_path_buf());
-    } else if let Some(datadir) = args.data {
+    for fallback_rpc in &args.fallback_rpc {
+        node.with_fallback_rpc(fallback_rpc)
+            .change_context(StarknetError)
+            .attach_printable_lazy(|| format!("failed to parse fallback rpc url {fallback_rpc}"))?;
+    }
+
+    if let Some(rate_limit) = args.rpc_rate_limit {
+        node.with_rpc_rate_limit(rate_limit);
+    }
+
+    if let Some(feeder_gateway) = &args.feeder_gateway {
+        node.with_feeder_gateway(feeder_gateway)
+            .change_context(StarknetError)
+            .attach_printable_lazy(|| {
+                format!("failed to parse feeder gateway url {feeder_gateway}")
+            })?;
+    }
+
+    let mut retry_config = RetryConfig::default();
+    if let Some(max_retries) = args.rpc_max_retries {
+        retry_config.max_retries = max_retries;
+    }
+    if let Some(initial_backoff) = args.rpc_initial_backoff_ms {
+        retry_config.initial_backoff = Duration::from_millis(initial_backoff);
+    }
+    if let Some(max_backoff) = args.rpc_max_backoff_ms {
+        retry_config.max_backoff = Duration::from_millis(max_backoff);
+    }
+    if let Some(cooldown) = args.rpc_unhealthy_cooldown_secs {
+        retry_config.unhealthy_cooldown = Duration::from_secs(cooldown);
+    }
+    if let Some(interval) = args.rpc_health_check_interval_secs {
+        retry_config.health_check_interval = Some(Duration::from_secs(interval.max(1)));
+    }
+    node.with_rpc_retry_config(retry_config);
+
+    if let Some(rpc_ws) = &args.rpc_ws {
+        node.with_rpc_ws(rpc_ws)
+            .change_context(StarknetError)
+            .attach_printable_lazy(|| format!("failed to parse rpc websocket url {rpc_ws}"))?;
+    }
+
+    // in devnet mode the caller replaces the datadir with a temporary directory.
+    if let Some(datadir) = args.data {
         info!("using user-provided datadir");
         node.with_datadir(datadir);
     } else if let Some(name) = &args.name {
@@ -126,8 +543,14 @@ pub async fn start_node(args: StartArgs, cts: CancellationToken) -> Result<(), S
         node.with_datadir(datadir);
     }
 
+    if args.read_only {
+        node.with_read_only();
+    }
+
     if let Some(address) = args.address {
         node.with_address(address);
+    } else if let Some(port) = args.port {
+        node.with_address(format!("0.0.0.0:{port}"));
     }
 
     let quota_args = args.quota_server.unwrap_or_default();
@@ -146,16 +569,37 @@ pub async fn start_node(args: StartArgs, cts: CancellationToken) -> Result<(), S
             client_metadata_key: quota_args.client_metadata_key,
         };
         node.with_quota_configuration(quota_configuration);
+    } else if let Some(quota_configuration) = quota_args.to_local_quota_configuration()? {
+        node.with_quota_configuration(quota_configuration);
+    }
+
+    let auth_configuration = args.auth.unwrap_or_default().to_auth_configuration()?;
+    node.with_auth_configuration(auth_configuration);
+
+    if let Some(tls_config) = args.tls.unwrap_or_default().to_tls_config()? {
+        node.with_tls_config(tls_config);
     }
 
     if let Some(websocket_address) = args.websocket_address {
         node.with_websocket_address(websocket_address);
     }
 
+    if let Some(health_address) = args.health_address {
+        node.with_health_address(health_address);
+    }
+
+    if let Some(max_blocks_behind) = args.health_max_blocks_behind {
+        node.with_health_max_blocks_behind(max_blocks_behind);
+    }
+
     if let Some(limit) = args.blocks_per_second_limit {
         node.with_blocks_per_second_limit(limit);
     }
 
+    if let Some(max_streams) = args.max_concurrent_streams {
+        node.with_max_concurrent_streams(max_streams);
+    }
+
     let mut block_ingestion_config = BlockIngestionConfig::default();
 
     if let Some(head_refresh_interval_free) = args.head_refresh_interval_ms {
@@ -164,19 +608,43 @@ pub async fn start_node(args: StartArgs, cts: CancellationToken) -> Result<(), S
         block_ingestion_config.head_refresh_interval = Duration::from_millis(head_refresh_interval);
     }
 
-    if let Some(starting_block) = args.dangerously_override_ingestion_start_block {
+    if let Some(finality_refresh_interval) = args.finality_refresh_interval_secs {
+        block_ingestion_config.finality_refresh_interval =
+            Duration::from_secs(finality_refresh_interval.max(1));
+    }
+
+    block_ingestion_config.finality_strategy = args.finality_strategy;
+
+    if let Some(starting_block) = args
+        .starting_block
+        .or(args.dangerously_override_ingestion_start_block)
+    {
         block_ingestion_config.ingestion_starting_block = Some(starting_block);
     }
 
-    node.with_block_ingestion_config(block_ingestion_config);
+    if let Some(rpc_concurrency) = args.rpc_concurrency {
+        block_ingestion_config.rpc_concurrency = rpc_concurrency.max(1);
+    }
 
-    node.build()
-        .change_context(StarknetError)
-        .attach_printable("failed to initialize node")?
-        .start(cts.clone(), args.wait_for_rpc)
-        .await
-        .change_context(StarknetError)
-        .attach_printable("error while running starknet node")?;
+    block_ingestion_config.capture_contract_classes = args.capture_contract_classes;
+    block_ingestion_config.capture_traces = args.capture_traces;
+    block_ingestion_config.heal_interval = args
+        .heal_interval_secs
+        .map(|interval| Duration::from_secs(interval.max(1)));
+    block_ingestion_config.pruning_keep_blocks = args.prune_keep_blocks;
 
-    Ok(())
+    let mut wait_for_rpc_config = WaitForRpcConfig::default();
+    if let Some(initial_backoff) = args.wait_for_rpc_initial_backoff_secs {
+        wait_for_rpc_config.initial_backoff = Duration::from_secs(initial_backoff.max(1));
+    }
+    if let Some(max_backoff) = args.wait_for_rpc_max_backoff_secs {
+        wait_for_rpc_config.max_backoff = Duration::from_secs(max_backoff.max(1));
+    }
+    wait_for_rpc_config.timeout = args.wait_for_rpc_timeout_secs.map(Duration::from_secs);
+    node.with_wait_for_rpc_config(wait_for_rpc_config);
+
+    node.with_block_ingestion_config(block_ingestion_config);
+    node.with_database_config(args.database.to_database_config());
+
+    Ok(node)
 }
@@ -0,0 +1,140 @@
+//! Database maintenance commands.
+use std::path::PathBuf;
+
+use apibara_node::db::MdbxEnvironmentExt;
+use clap::{Args, Subcommand};
+use error_stack::{Result, ResultExt};
+
+use crate::{
+    db::{tables, DatabaseConfig},
+    inspect::open_environment,
+    DatabaseArgs, StarknetError,
+};
+
+#[derive(Clone, Debug, Args)]
+pub struct DbArgs {
+    /// Data directory. Defaults to `$XDG_DATA_HOME`.
+    #[arg(long, env)]
+    pub data: Option<PathBuf>,
+    /// Indexer name. Defaults to `starknet`.
+    #[arg(long, env)]
+    pub name: Option<String>,
+    #[command(flatten)]
+    pub database: DatabaseArgs,
+    #[command(subcommand)]
+    pub command: DbCommand,
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub enum DbCommand {
+    /// Print per-table sizes, page utilization and freelist stats.
+    Stats,
+    /// Copy the database to a new directory, omitting free pages.
+    ///
+    /// The node must be stopped while compacting.
+    Compact {
+        /// Destination directory. Must not exist.
+        #[arg(long)]
+        output: PathBuf,
+    },
+}
+
+pub fn db(args: DbArgs) -> Result<(), StarknetError> {
+    let config = args.database.to_database_config();
+    match args.command {
+        DbCommand::Stats => stats(args.data, args.name.as_deref(), &config),
+        DbCommand::Compact { output } => compact(args.data, args.name.as_deref(), &config, output),
+    }
+}
+
+fn stats(
+    data: Option<PathBuf>,
+    name: Option<&str>,
+    config: &DatabaseConfig,
+) -> Result<(), StarknetError> {
+    let db = open_environment(data, name, config, false)?;
+
+    let stat = db.stat().change_context(StarknetError)?;
+    let info = db.info().change_context(StarknetError)?;
+    let freelist = db.freelist().change_context(StarknetError)?;
+
+    let page_size = stat.page_size() as usize;
+    let mapped_pages = info.map_size() / page_size;
+    let used_pages = info.last_pgno() + 1;
+
+    println!(
+        "{:<16} {:>12} {:>8} {:>14}",
+        "table", "entries", "depth", "size"
+    );
+    let txn = db.begin_ro_txn().change_context(StarknetError)?;
+    for table in tables::names() {
+        let table_db = txn
+            .open_db(Some(table))
+            .change_context(StarknetError)
+            .attach_printable_lazy(|| format!("failed to open table {table}"))?;
+        let table_stat = txn.db_stat(&table_db).change_context(StarknetError)?;
+        let pages =
+            table_stat.branch_pages() + table_stat.leaf_pages() + table_stat.overflow_pages();
+        println!(
+            "{:<16} {:>12} {:>8} {:>14}",
+            table,
+            table_stat.entries(),
+            table_stat.depth(),
+            format_bytes(pages * page_size)
+        );
+    }
+    txn.commit().change_context(StarknetError)?;
+
+    println!();
+    println!("page size:      {}", page_size);
+    println!("map size:       {}", format_bytes(info.map_size()));
+    println!(
+        "used pages:     {used_pages} / {mapped_pages} ({:.1}%)",
+        100.0 * used_pages as f64 / mapped_pages as f64
+    );
+    println!(
+        "free pages:     {freelist} ({})",
+        format_bytes(freelist * page_size)
+    );
+    println!(
+        "readers:        {} / {}",
+        info.num_readers(),
+        info.max_readers()
+    );
+
+    Ok(())
+}
+
+fn compact(
+    data: Option<PathBuf>,
+    name: Option<&str>,
+    config: &DatabaseConfig,
+    output: PathBuf,
+) -> Result<(), StarknetError> {
+    if output.exists() {
+        return Err(StarknetError)
+            .attach_printable_lazy(|| format!("output {output:?} already exists"));
+    }
+
+    let db = open_environment(data, name, config, false)?;
+
+    std::fs::create_dir_all(&output)
+        .change_context(StarknetError)
+        .attach_printable_lazy(|| format!("failed to create output directory {output:?}"))?;
+
+    db.copy_compact(&output.join("mdbx.dat"))
+        .change_context(StarknetError)
+        .attach_printable("failed to compact database")?;
+
+    // check the copy can be opened.
+    open_environment(Some(output.clone()), None, config, false)?;
+
+    println!("compacted database written to {output:?}");
+    Ok(())
+}
+
+fn format_bytes(bytes: usize) -> String {
+    byte_unit::Byte::from_bytes(bytes as u128)
+        .get_appropriate_unit(true)
+        .to_string()
+}
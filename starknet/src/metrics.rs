@@ -0,0 +1,90 @@
+//! Node-level operational metrics.
+//!
+//! Metrics that are not tied to a single request (database size, how far
+//! behind the chain head the node is) are sampled periodically and exported
+//! as gauges.
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use apibara_node::o11y;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+use crate::status::StatusClient;
+
+/// How often the gauges are refreshed.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+pub struct NodeMetrics {
+    datadir: PathBuf,
+    status_client: StatusClient,
+    db_size: Arc<AtomicU64>,
+    blocks_behind: Arc<AtomicU64>,
+}
+
+impl NodeMetrics {
+    pub fn new(datadir: PathBuf, status_client: StatusClient) -> Self {
+        NodeMetrics {
+            datadir,
+            status_client,
+            db_size: Arc::default(),
+            blocks_behind: Arc::default(),
+        }
+    }
+
+    pub async fn start(self, ct: CancellationToken) {
+        self.register_gauges();
+
+        loop {
+            self.refresh().await;
+
+            tokio::select! {
+                _ = tokio::time::sleep(REFRESH_INTERVAL) => {},
+                _ = ct.cancelled() => return,
+            }
+        }
+    }
+
+    fn register_gauges(&self) {
+        let meter = o11y::meter("starknet_node");
+        let db_size_gauge = meter.u64_observable_gauge("db_size_bytes").init();
+        let blocks_behind_gauge = meter.u64_observable_gauge("blocks_behind_head").init();
+
+        let db_size = self.db_size.clone();
+        let blocks_behind = self.blocks_behind.clone();
+        let result = meter.register_callback(move |cx| {
+            db_size_gauge.observe(cx, db_size.load(Ordering::Relaxed), &[]);
+            blocks_behind_gauge.observe(cx, blocks_behind.load(Ordering::Relaxed), &[]);
+        });
+
+        if let Err(err) = result {
+            warn!(error = ?err, "failed to register node metrics");
+        }
+    }
+
+    async fn refresh(&self) {
+        match tokio::fs::metadata(self.datadir.join("mdbx.dat")).await {
+            Ok(metadata) => self.db_size.store(metadata.len(), Ordering::Relaxed),
+            Err(err) => warn!(error = ?err, "failed to read database size"),
+        }
+
+        let status = match self.status_client.get_status().await {
+            Ok(status) => status,
+            Err(err) => {
+                warn!(error = ?err, "failed to fetch node status");
+                return;
+            }
+        };
+
+        if let (Some(head), Some(ingested)) = (status.current_head, status.last_ingested) {
+            let blocks_behind = head.order_key.saturating_sub(ingested.order_key);
+            self.blocks_behind.store(blocks_behind, Ordering::Relaxed);
+        }
+    }
+}
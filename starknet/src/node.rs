@@ -2,9 +2,10 @@ use std::{
     fs, future,
     marker::PhantomData,
     net::{AddrParseError, SocketAddr},
+    num::NonZeroU32,
     path::PathBuf,
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use apibara_node::{
@@ -13,15 +14,25 @@ use apibara_node::{
         libmdbx::{self, Environment, EnvironmentKind},
         MdbxEnvironmentExt,
     },
-    server::{QuotaConfiguration, RequestObserver, SimpleRequestObserver},
+    server::{AuthConfiguration, QuotaConfiguration, RequestObserver, SimpleRequestObserver},
 };
+use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
+use tonic::transport::ServerTlsConfig;
 use tracing::{info, warn};
+use url::Url;
 
 use crate::{
-    db::{tables, DatabaseStorage},
-    ingestion::{BlockIngestion, BlockIngestionConfig, BlockIngestionError},
-    provider::{HttpProviderError, Provider},
+    db::{tables, DatabaseConfig, DatabaseStorage},
+    healer::{Healer, HealerError},
+    health::{HealthServer, DEFAULT_MAX_BLOCKS_BEHIND},
+    ingestion::{
+        BlockIngestion, BlockIngestionConfig, BlockIngestionError, IngestionStreamClient,
+        LastIngestionError, ReadOnlyIngestion,
+    },
+    metrics::NodeMetrics,
+    provider::{HttpProviderError, Provider, RetryConfig},
+    pruner::Pruner,
     server::{Server, ServerError},
     status::{StatusService, StatusServiceError},
     websocket::WebsocketStreamServer,
@@ -35,13 +46,45 @@ where
     E: EnvironmentKind,
 {
     db: Arc<Environment<E>>,
+    datadir: PathBuf,
     sequencer_provider: Arc<G>,
     request_span: O,
     address: Option<String>,
     websocket_address: Option<String>,
+    health_address: Option<String>,
+    health_max_blocks_behind: u64,
     block_ingestion_config: BlockIngestionConfig,
     blocks_per_second_quota: u32,
     quota_configuration: QuotaConfiguration,
+    auth_configuration: AuthConfiguration,
+    tls_config: Option<ServerTlsConfig>,
+    max_concurrent_streams: Option<usize>,
+    read_only: bool,
+    wait_for_rpc_config: WaitForRpcConfig,
+}
+
+/// How long to wait for ingestion and the server to stop on shutdown.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How to wait for the RPC server to be available before starting.
+#[derive(Debug, Clone)]
+pub struct WaitForRpcConfig {
+    /// Give up after this long. `None` waits forever.
+    pub timeout: Option<Duration>,
+    /// Delay before the first retry.
+    pub initial_backoff: Duration,
+    /// Maximum delay between retries. The delay doubles after each attempt.
+    pub max_backoff: Duration,
+}
+
+impl Default for WaitForRpcConfig {
+    fn default() -> Self {
+        WaitForRpcConfig {
+            timeout: None,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -54,8 +97,14 @@ pub enum StarkNetNodeError {
     Server(#[from] ServerError),
     #[error("status service error: {0}")]
     StatusServer(#[from] StatusServiceError),
+    #[error("healer error: {0}")]
+    Healer(#[from] HealerError),
     #[error("error parsing server address: {0}")]
     AddressParseError(#[from] AddrParseError),
+    #[error("rpc server not available after {0:?}")]
+    RpcUnavailable(Duration),
+    #[error("the websocket gateway cannot be used with authentication, quotas or stream limits")]
+    UnprotectedWebsocketGateway,
 }
 
 impl<G, O, E> StarkNetNode<G, O, E>
@@ -74,25 +123,41 @@ where
     #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         db: Environment<E>,
+        datadir: PathBuf,
         sequencer_provider: G,
         request_span: O,
         address: Option<String>,
         websocket_address: Option<String>,
+        health_address: Option<String>,
+        health_max_blocks_behind: u64,
         block_ingestion_config: BlockIngestionConfig,
         blocks_per_second_quota: Option<u32>,
         quota_configuration: QuotaConfiguration,
+        auth_configuration: AuthConfiguration,
+        tls_config: Option<ServerTlsConfig>,
+        max_concurrent_streams: Option<usize>,
+        read_only: bool,
+        wait_for_rpc_config: WaitForRpcConfig,
     ) -> Self {
         let db = Arc::new(db);
         let sequencer_provider = Arc::new(sequencer_provider);
         StarkNetNode {
             db,
+            datadir,
             sequencer_provider,
             request_span,
             address,
             websocket_address,
+            health_address,
+            health_max_blocks_behind,
             block_ingestion_config,
             blocks_per_second_quota: blocks_per_second_quota.unwrap_or(10_000),
             quota_configuration,
+            auth_configuration,
+            tls_config,
+            max_concurrent_streams,
+            read_only,
+            wait_for_rpc_config,
         }
     }
 
@@ -102,31 +167,57 @@ where
         ct: CancellationToken,
         wait_for_rpc: bool,
     ) -> Result<(), StarkNetNodeError> {
-        info!("starting starknet node");
-        self.ensure_tables()?;
-
-        if wait_for_rpc {
-            self.wait_for_rpc(ct.clone()).await?;
+        info!(read_only = self.read_only, "starting starknet node");
+
+        // the websocket gateway doesn't check credentials or quotas, serving it
+        // would let clients bypass them.
+        let is_protected = !matches!(self.auth_configuration, AuthConfiguration::NoAuth)
+            || !matches!(self.quota_configuration, QuotaConfiguration::NoQuota)
+            || self.max_concurrent_streams.is_some();
+        if self.websocket_address.is_some() && is_protected {
+            return Err(StarkNetNodeError::UnprotectedWebsocketGateway);
         }
 
-        let (block_ingestion_client, block_ingestion) = BlockIngestion::new(
-            self.sequencer_provider.clone(),
-            self.db.clone(),
-            self.block_ingestion_config,
-        );
-
-        let mut block_ingestion_handle = tokio::spawn({
-            let ct = ct.clone();
-            async move {
-                block_ingestion
-                    .start(ct)
-                    .await
-                    .map_err(StarkNetNodeError::BlockIngestion)
-            }
-        });
+        // In read-only mode, another node ingests blocks into the shared
+        // database and this node only serves them.
+        let (
+            block_ingestion_client,
+            mut block_ingestion_handle,
+            mut healer_handle,
+            last_ingestion_error,
+        ) = if self.read_only {
+            let (block_ingestion_client, block_ingestion) =
+                ReadOnlyIngestion::new(self.db.clone(), self.block_ingestion_config.clone());
+            let block_ingestion_handle = tokio::spawn({
+                let ct = ct.clone();
+                async move {
+                    block_ingestion
+                        .start(ct)
+                        .await
+                        .map_err(StarkNetNodeError::BlockIngestion)
+                }
+            });
+            // there is no healer, but the handle should still stop on shutdown.
+            let healer_handle = tokio::spawn({
+                let ct = ct.clone();
+                async move {
+                    ct.cancelled().await;
+                    Ok(())
+                }
+            });
+            (
+                block_ingestion_client,
+                block_ingestion_handle,
+                healer_handle,
+                LastIngestionError::default(),
+            )
+        } else {
+            self.start_ingestion(ct.clone(), wait_for_rpc).await?
+        };
 
         let (status_service, status_client) = StatusService::new(
             self.sequencer_provider.clone(),
+            DatabaseStorage::new(self.db.clone()),
             block_ingestion_client.clone(),
         );
 
@@ -144,14 +235,23 @@ where
             .address
             .unwrap_or_else(|| "0.0.0.0:7171".to_string())
             .parse()?;
-        let server = Server::<E, O>::new(
+        let mut server = Server::<E, O>::new(
             self.db.clone(),
             block_ingestion_client.clone(),
-            status_client,
+            status_client.clone(),
             self.blocks_per_second_quota,
         )
         .with_request_observer(self.request_span)
-        .with_quota_configuration(self.quota_configuration);
+        .with_quota_configuration(self.quota_configuration)
+        .with_auth_configuration(self.auth_configuration);
+
+        if let Some(tls_config) = self.tls_config {
+            server = server.with_tls_config(tls_config);
+        }
+
+        if let Some(max_streams) = self.max_concurrent_streams {
+            server = server.with_max_concurrent_streams(max_streams);
+        }
 
         let mut server_handle = tokio::spawn({
             let ct = ct.clone();
@@ -179,8 +279,23 @@ where
             None => tokio::spawn(future::pending()),
         };
 
-        // TODO: based on which handles terminates first, it needs to wait
-        // for the other handle to terminate too.
+        let metrics = NodeMetrics::new(self.datadir, status_client.clone());
+        tokio::spawn(metrics.start(ct.clone()));
+
+        let mut health_handle = match self.health_address {
+            Some(health_address) => {
+                info!("Starting health server");
+                let health_server = HealthServer::new(
+                    health_address,
+                    status_client,
+                    last_ingestion_error,
+                    self.health_max_blocks_behind,
+                );
+                tokio::spawn(health_server.start())
+            }
+            None => tokio::spawn(future::pending()),
+        };
+
         tokio::select! {
             ret = &mut block_ingestion_handle => {
                 warn!(result = ?ret, "block ingestion terminated");
@@ -188,18 +303,117 @@ where
             ret = &mut status_service_handle => {
                 warn!(result = ?ret, "status service terminated");
             }
+            ret = &mut healer_handle => {
+                warn!(result = ?ret, "healer terminated");
+            }
             ret = &mut server_handle => {
                 warn!(result = ?ret, "server terminated");
             }
             ret = &mut websocket_handle => {
                 warn!(resul = ?ret, "websocket server terminated");
             }
+            ret = &mut health_handle => {
+                warn!(result = ?ret, "health server terminated");
+            }
         }
 
+        // stop all other tasks. ingestion checks the token between blocks, so
+        // waiting for it ensures the block being written is committed.
+        ct.cancel();
+        info!("waiting for ingestion and server to stop");
+        let shutdown = async {
+            for handle in [
+                block_ingestion_handle,
+                server_handle,
+                status_service_handle,
+                healer_handle,
+            ] {
+                if !handle.is_finished() {
+                    let _ = handle.await;
+                }
+            }
+        };
+
+        if tokio::time::timeout(SHUTDOWN_TIMEOUT, shutdown)
+            .await
+            .is_err()
+        {
+            warn!("timeout waiting for tasks to stop");
+        }
+
+        // these servers don't support graceful shutdown.
+        websocket_handle.abort();
+        health_handle.abort();
+
         info!("terminated. bye");
         Ok(())
     }
 
+    #[allow(clippy::type_complexity)]
+    async fn start_ingestion(
+        &self,
+        ct: CancellationToken,
+        wait_for_rpc: bool,
+    ) -> Result<
+        (
+            IngestionStreamClient,
+            JoinHandle<Result<(), StarkNetNodeError>>,
+            JoinHandle<Result<(), StarkNetNodeError>>,
+            LastIngestionError,
+        ),
+        StarkNetNodeError,
+    > {
+        self.ensure_tables()?;
+
+        if wait_for_rpc {
+            self.wait_for_rpc(ct.clone()).await?;
+        }
+
+        let healer = Healer::new(
+            self.sequencer_provider.clone(),
+            self.db.clone(),
+            &self.block_ingestion_config,
+        );
+
+        let healer_handle = tokio::spawn({
+            let ct = ct.clone();
+            async move { healer.start(ct).await.map_err(StarkNetNodeError::Healer) }
+        });
+
+        if let Some(keep_blocks) = self.block_ingestion_config.pruning_keep_blocks {
+            let pruner = Pruner::new(self.db.clone(), keep_blocks);
+            tokio::spawn({
+                let ct = ct.clone();
+                async move {
+                    if let Err(err) = pruner.start(ct).await {
+                        warn!(error = ?err, "pruner terminated with error");
+                    }
+                }
+            });
+        }
+
+        let (block_ingestion_client, block_ingestion) = BlockIngestion::new(
+            self.sequencer_provider.clone(),
+            self.db.clone(),
+            self.block_ingestion_config.clone(),
+        );
+        let last_ingestion_error = block_ingestion.last_error();
+
+        let block_ingestion_handle = tokio::spawn(async move {
+            block_ingestion
+                .start(ct)
+                .await
+                .map_err(StarkNetNodeError::BlockIngestion)
+        });
+
+        Ok((
+            block_ingestion_client,
+            block_ingestion_handle,
+            healer_handle,
+            last_ingestion_error,
+        ))
+    }
+
     fn ensure_tables(&self) -> Result<(), StarkNetNodeError> {
         let txn = self.db.begin_rw_txn()?;
         tables::ensure(&txn)?;
@@ -208,22 +422,49 @@ where
     }
 
     async fn wait_for_rpc(&self, ct: CancellationToken) -> Result<(), StarkNetNodeError> {
-        let mut timeout_seconds = 1;
+        let config = &self.wait_for_rpc_config;
+        let started_at = Instant::now();
+        let mut backoff = config.initial_backoff;
+        let mut attempt = 0;
         loop {
             if ct.is_cancelled() {
                 return Ok(());
             }
 
+            attempt += 1;
             match self.sequencer_provider.get_head().await {
-                Ok(_) => return Ok(()),
-                Err(_) => {
-                    info!("waiting for RPC server to be available");
+                Ok(head) => {
+                    info!(
+                        attempt,
+                        elapsed_secs = started_at.elapsed().as_secs(),
+                        head = %head,
+                        "RPC server is available"
+                    );
+                    return Ok(());
+                }
+                Err(err) => {
+                    let elapsed = started_at.elapsed();
+                    if let Some(timeout) = config.timeout {
+                        if elapsed >= timeout {
+                            return Err(StarkNetNodeError::RpcUnavailable(elapsed));
+                        }
+                        // don't sleep past the timeout.
+                        backoff = backoff.min(timeout - elapsed);
+                    }
+
+                    info!(
+                        attempt,
+                        elapsed_secs = elapsed.as_secs(),
+                        retry_in_secs = backoff.as_secs_f64(),
+                        error = ?err,
+                        "waiting for RPC server to be available"
+                    );
                     tokio::select! {
                         _ = ct.cancelled() => {
                         },
-                        _ = tokio::time::sleep(Duration::from_secs(timeout_seconds)) => {},
+                        _ = tokio::time::sleep(backoff) => {},
                     };
-                    timeout_seconds = u64::min(timeout_seconds * 2, 30);
+                    backoff = (backoff * 2).min(config.max_backoff);
                 }
             }
         }
@@ -232,13 +473,25 @@ where
 
 pub struct StarkNetNodeBuilder<O: RequestObserver, E: EnvironmentKind> {
     datadir: PathBuf,
-    provider: HttpProvider,
+    rpc_urls: Vec<Url>,
+    rpc_rate_limit: Option<NonZeroU32>,
+    rpc_retry_config: RetryConfig,
+    feeder_gateway_url: Option<Url>,
+    rpc_ws_url: Option<Url>,
     request_observer: O,
     address: Option<String>,
     websocket_address: Option<String>,
+    health_address: Option<String>,
+    health_max_blocks_behind: u64,
     blocks_per_second_quota: Option<u32>,
     quota_configuration: QuotaConfiguration,
+    auth_configuration: AuthConfiguration,
+    tls_config: Option<ServerTlsConfig>,
+    max_concurrent_streams: Option<usize>,
+    read_only: bool,
+    wait_for_rpc_config: WaitForRpcConfig,
     block_ingestion_config: BlockIngestionConfig,
+    database_config: DatabaseConfig,
     _phantom: PhantomData<E>,
 }
 
@@ -266,17 +519,28 @@ where
             .map(|d| d.join("starknet"))
             .expect("no datadir");
         let url = url.parse()?;
-        let sequencer = HttpProvider::new(url);
         let request_observer = SimpleRequestObserver::default();
         let builder = StarkNetNodeBuilder {
             datadir,
-            provider: sequencer,
+            rpc_urls: vec![url],
+            rpc_rate_limit: None,
+            rpc_retry_config: RetryConfig::default(),
+            feeder_gateway_url: None,
+            rpc_ws_url: None,
             request_observer,
             block_ingestion_config: BlockIngestionConfig::default(),
+            database_config: DatabaseConfig::default(),
             quota_configuration: QuotaConfiguration::NoQuota,
+            auth_configuration: AuthConfiguration::NoAuth,
+            tls_config: None,
+            max_concurrent_streams: None,
+            read_only: false,
+            wait_for_rpc_config: WaitForRpcConfig::default(),
             blocks_per_second_quota: None,
             address: None,
             websocket_address: None,
+            health_address: None,
+            health_max_blocks_behind: DEFAULT_MAX_BLOCKS_BEHIND,
             _phantom: Default::default(),
         };
         Ok(builder)
@@ -292,43 +556,141 @@ where
     ) -> StarkNetNodeBuilder<N, E> {
         StarkNetNodeBuilder {
             datadir: self.datadir,
-            provider: self.provider,
+            rpc_urls: self.rpc_urls,
+            rpc_rate_limit: self.rpc_rate_limit,
+            rpc_retry_config: self.rpc_retry_config,
+            feeder_gateway_url: self.feeder_gateway_url,
+            rpc_ws_url: self.rpc_ws_url,
             request_observer,
             address: self.address,
             websocket_address: self.websocket_address,
+            health_address: self.health_address,
+            health_max_blocks_behind: self.health_max_blocks_behind,
             blocks_per_second_quota: self.blocks_per_second_quota,
             quota_configuration: self.quota_configuration,
+            auth_configuration: self.auth_configuration,
+            tls_config: self.tls_config,
+            max_concurrent_streams: self.max_concurrent_streams,
+            read_only: self.read_only,
+            wait_for_rpc_config: self.wait_for_rpc_config,
             block_ingestion_config: self.block_ingestion_config,
+            database_config: self.database_config,
             _phantom: self._phantom,
         }
     }
 
+    /// Add a fallback RPC url, used when the previous urls are not available.
+    pub fn with_fallback_rpc(&mut self, url: &str) -> Result<(), StarkNetNodeBuilderError> {
+        self.rpc_urls.push(url.parse()?);
+        Ok(())
+    }
+
+    /// Limit the number of RPC requests per second.
+    pub fn with_rpc_rate_limit(&mut self, requests_per_second: NonZeroU32) {
+        self.rpc_rate_limit = Some(requests_per_second);
+    }
+
+    /// Change how failed RPC requests are retried.
+    pub fn with_rpc_retry_config(&mut self, retry_config: RetryConfig) {
+        self.rpc_retry_config = retry_config;
+    }
+
+    /// Use the sequencer feeder gateway when the RPC is lagging or not available.
+    pub fn with_feeder_gateway(&mut self, url: &str) -> Result<(), StarkNetNodeBuilderError> {
+        self.feeder_gateway_url = Some(url.parse()?);
+        Ok(())
+    }
+
+    /// Subscribe to new heads over websocket, falling back to polling.
+    pub fn with_rpc_ws(&mut self, url: &str) -> Result<(), StarkNetNodeBuilderError> {
+        self.rpc_ws_url = Some(url.parse()?);
+        Ok(())
+    }
+
     pub fn with_block_ingestion_config(&mut self, block_ingestion_config: BlockIngestionConfig) {
         self.block_ingestion_config = block_ingestion_config;
     }
 
+    pub fn with_database_config(&mut self, database_config: DatabaseConfig) {
+        self.database_config = database_config;
+    }
+
     pub fn with_quota_configuration(&mut self, configuration: QuotaConfiguration) {
         self.quota_configuration = configuration;
     }
 
+    pub fn with_auth_configuration(&mut self, configuration: AuthConfiguration) {
+        self.auth_configuration = configuration;
+    }
+
+    /// Limit the number of streams served concurrently.
+    pub fn with_max_concurrent_streams(&mut self, max_streams: usize) {
+        self.max_concurrent_streams = Some(max_streams);
+    }
+
+    /// Only serve data ingested by another node sharing the same database.
+    pub fn with_read_only(&mut self) {
+        self.read_only = true;
+    }
+
+    /// Configure how long to wait for the RPC server when starting with `wait_for_rpc`.
+    pub fn with_wait_for_rpc_config(&mut self, config: WaitForRpcConfig) {
+        self.wait_for_rpc_config = config;
+    }
+
+    /// Serve the DNA stream over TLS.
+    pub fn with_tls_config(&mut self, tls_config: ServerTlsConfig) {
+        self.tls_config = Some(tls_config);
+    }
+
     pub fn build(self) -> Result<StarkNetNode<HttpProvider, O, E>, StarkNetNodeBuilderError> {
         fs::create_dir_all(&self.datadir).map_err(StarkNetNodeBuilderError::CreateDatadir)?;
 
-        let db = Environment::<E>::builder()
-            .with_size_gib(10, 512)
-            .with_growth_step_gib(2)
+        let mut db_builder = Environment::<E>::builder()
+            .with_size_gib(
+                self.database_config.min_size_gib,
+                self.database_config.max_size_gib,
+            )
+            .with_growth_step_gib(self.database_config.growth_step_gib);
+        if let Some(max_readers) = self.database_config.max_readers {
+            db_builder = db_builder.with_max_readers(max_readers);
+        }
+        if self.read_only {
+            db_builder = db_builder.with_read_only();
+        }
+        let db = db_builder
             .open(&self.datadir)
             .map_err(StarkNetNodeBuilderError::DatabaseOpen)?;
 
+        let mut provider =
+            HttpProvider::with_endpoints(self.rpc_urls).with_retry_config(self.rpc_retry_config);
+        if let Some(rate_limit) = self.rpc_rate_limit {
+            provider = provider.with_rate_limit(rate_limit);
+        }
+        if let Some(feeder_gateway_url) = self.feeder_gateway_url {
+            provider = provider.with_feeder_gateway(feeder_gateway_url);
+        }
+        if let Some(rpc_ws_url) = self.rpc_ws_url {
+            provider = provider.with_head_subscription(rpc_ws_url);
+        }
+
         Ok(StarkNetNode::new(
             db,
-            self.provider,
+            self.datadir,
+            provider,
             self.request_observer,
             self.address,
             self.websocket_address,
+            self.health_address,
+            self.health_max_blocks_behind,
             self.block_ingestion_config,
             self.blocks_per_second_quota,
             self.quota_configuration,
+            self.auth_configuration,
+            self.tls_config,
+            self.max_concurrent_streams,
+            self.read_only,
+            self.wait_for_rpc_config,
         ))
     }
 
@@ -340,6 +702,14 @@ where
         self.websocket_address = Some(websocket_address);
     }
 
+    pub(crate) fn with_health_address(&mut self, health_address: String) {
+        self.health_address = Some(health_address);
+    }
+
+    pub(crate) fn with_health_max_blocks_behind(&mut self, max_blocks_behind: u64) {
+        self.health_max_blocks_behind = max_blocks_behind;
+    }
+
     pub(crate) fn with_blocks_per_second_limit(&mut self, limit: u32) {
         self.blocks_per_second_quota = Some(limit);
     }
@@ -1,12 +1,31 @@
 //! Connect to the sequencer gateway.
+mod ws;
+
+use std::{
+    future::Future,
+    num::NonZeroU32,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
 use apibara_core::starknet::v1alpha2;
+use apibara_node::o11y::{self, Histogram, KeyValue};
+use governor::{DefaultDirectRateLimiter, Quota, RateLimiter};
 use starknet::{
+    core::chain_id,
     core::types::{self as models, FieldElement, FromByteArrayError, StarknetError},
     providers::{
-        jsonrpc::{HttpTransport, JsonRpcClient},
-        Provider as StarknetProvider, ProviderError as StarknetProviderError,
+        jsonrpc::{HttpTransport, HttpTransportError, JsonRpcClient, JsonRpcClientError},
+        sequencer::{models::BlockId as GatewayBlockId, GatewayClientError},
+        AnyProvider, Provider as StarknetProvider, ProviderError as StarknetProviderError,
+        SequencerGatewayProvider,
     },
 };
+use tokio_util::sync::{CancellationToken, DropGuard};
+use tracing::{debug, info, warn};
 use url::Url;
 
 use crate::{
@@ -14,6 +33,8 @@ use crate::{
     db::BlockBody,
 };
 
+use self::ws::HeadSubscription;
+
 #[derive(Debug, Clone)]
 pub enum BlockId {
     Latest,
@@ -22,6 +43,15 @@ pub enum BlockId {
     Number(u64),
 }
 
+/// Values committed to by a block, used to verify the block data.
+#[derive(Debug, Clone)]
+pub struct BlockCommitments {
+    pub transaction_count: usize,
+    pub event_count: usize,
+    /// Root of the events tree, missing for old blocks.
+    pub event_commitment: Option<v1alpha2::FieldElement>,
+}
+
 pub trait ProviderError: std::error::Error + Send + Sync + 'static {
     fn is_block_not_found(&self) -> bool;
 }
@@ -30,6 +60,9 @@ pub trait ProviderError: std::error::Error + Send + Sync + 'static {
 pub trait Provider {
     type Error: ProviderError;
 
+    /// Get the id of the chain.
+    async fn get_chain_id(&self) -> Result<v1alpha2::FieldElement, Self::Error>;
+
     /// Get the most recent accepted block number and hash.
     async fn get_head(&self) -> Result<GlobalBlockId, Self::Error>;
 
@@ -53,11 +86,77 @@ pub trait Provider {
         &self,
         hash: &v1alpha2::FieldElement,
     ) -> Result<v1alpha2::TransactionReceipt, Self::Error>;
+
+    /// Get the definition of the class with the given hash, serialized as json.
+    async fn get_class(
+        &self,
+        id: &BlockId,
+        class_hash: &v1alpha2::FieldElement,
+    ) -> Result<Vec<u8>, Self::Error>;
+
+    /// Get the execution traces of all transactions in a block, serialized as json.
+    async fn get_block_traces(&self, id: &BlockId) -> Result<Vec<u8>, Self::Error>;
+
+    /// Get the transaction and event commitments of a block.
+    ///
+    /// Returns `None` if the provider doesn't have access to them.
+    async fn get_block_commitments(
+        &self,
+        _id: &BlockId,
+    ) -> Result<Option<BlockCommitments>, Self::Error> {
+        Ok(None)
+    }
+
+    /// Waits until the provider is notified of a new head.
+    ///
+    /// Providers that don't support push notifications never return, callers
+    /// should poll [Provider::get_head] too.
+    async fn wait_for_new_head(&self) {
+        futures::future::pending().await
+    }
 }
 
 /// StarkNet RPC provider over HTTP.
+///
+/// The provider can be configured with multiple endpoints. Requests are sent
+/// to the first healthy endpoint and, on transport errors, retried with
+/// exponential backoff on the next endpoint.
+///
+/// Optionally, requests that fail on all endpoints or that reference blocks
+/// or transactions not yet known to the RPC node are sent to the sequencer
+/// feeder gateway.
 pub struct HttpProvider {
-    provider: JsonRpcClient<HttpTransport>,
+    endpoints: Arc<Vec<Endpoint>>,
+    feeder_gateway: Option<Endpoint>,
+    gateway_head: Mutex<Option<(Instant, Option<models::BlockHashAndNumber>)>>,
+    current: AtomicUsize,
+    rate_limiter: Option<DefaultDirectRateLimiter>,
+    retry: RetryConfig,
+    head_subscription: Option<HeadSubscription>,
+    _health_check: Option<DropGuard>,
+    call_latency: Histogram<f64>,
+}
+
+/// Configure how failed requests are retried.
+#[derive(Debug, Clone)]
+pub struct RetryConfig {
+    /// Maximum number of retries before giving up.
+    pub max_retries: usize,
+    /// Delay before the first retry.
+    pub initial_backoff: Duration,
+    /// Upper bound on the delay between retries.
+    pub max_backoff: Duration,
+    /// How long a failing endpoint is skipped before being tried again.
+    pub unhealthy_cooldown: Duration,
+    /// Probe all endpoints at this interval, instead of waiting for requests
+    /// to fail. Disabled by default.
+    pub health_check_interval: Option<Duration>,
+}
+
+struct Endpoint {
+    url: Url,
+    client: Arc<AnyProvider>,
+    unhealthy_until: Mutex<Option<Instant>>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -82,9 +181,240 @@ pub enum HttpProviderError {
 
 impl HttpProvider {
     pub fn new(rpc_url: Url) -> Self {
-        let http = HttpTransport::new(rpc_url);
-        let provider = JsonRpcClient::new(http);
-        HttpProvider { provider }
+        Self::with_endpoints(vec![rpc_url])
+    }
+
+    /// Creates a new provider that fails over between the given endpoints.
+    ///
+    /// Endpoints are tried in order, the first one is the primary endpoint.
+    ///
+    /// Panics if `rpc_urls` is empty.
+    pub fn with_endpoints(rpc_urls: Vec<Url>) -> Self {
+        assert!(!rpc_urls.is_empty(), "at least one rpc url is required");
+        let endpoints = rpc_urls.into_iter().map(Endpoint::new).collect();
+        HttpProvider {
+            endpoints: Arc::new(endpoints),
+            feeder_gateway: None,
+            gateway_head: Mutex::new(None),
+            current: AtomicUsize::new(0),
+            rate_limiter: None,
+            retry: RetryConfig::default(),
+            head_subscription: None,
+            _health_check: None,
+            call_latency: new_rpc_call_latency_histogram(),
+        }
+    }
+
+    /// Limit the number of requests per second sent to the RPC endpoints.
+    pub fn with_rate_limit(mut self, requests_per_second: NonZeroU32) -> Self {
+        let quota = Quota::per_second(requests_per_second);
+        self.rate_limiter = Some(RateLimiter::direct(quota));
+        self
+    }
+
+    /// Fall back to the sequencer feeder gateway when the RPC endpoints are
+    /// lagging or unavailable.
+    pub fn with_feeder_gateway(mut self, feeder_gateway_url: Url) -> Self {
+        self.feeder_gateway = Some(Endpoint::feeder_gateway(feeder_gateway_url));
+        self
+    }
+
+    /// Change how failed requests are retried.
+    ///
+    /// If the configuration has a health check interval, all endpoints are
+    /// checked periodically in the background. In that case, it must be called
+    /// from within a tokio runtime.
+    pub fn with_retry_config(mut self, retry: RetryConfig) -> Self {
+        self._health_check = retry
+            .health_check_interval
+            .map(|interval| self.start_health_check(interval, retry.unhealthy_cooldown));
+        self.retry = retry;
+        self
+    }
+
+    /// Subscribe to new heads over websocket, to refresh the head as soon as
+    /// a new block is produced instead of waiting for the next poll.
+    ///
+    /// Must be called from within a tokio runtime.
+    pub fn with_head_subscription(mut self, ws_url: Url) -> Self {
+        self.head_subscription = Some(HeadSubscription::start(ws_url));
+        self
+    }
+
+    /// Periodically checks the health of all endpoints in the background.
+    ///
+    /// Endpoints that don't respond are marked as unhealthy for the given
+    /// cooldown, endpoints that respond are marked as healthy again.
+    fn start_health_check(&self, interval: Duration, cooldown: Duration) -> DropGuard {
+        let ct = CancellationToken::new();
+        tokio::spawn(run_health_check(
+            self.endpoints.clone(),
+            interval,
+            cooldown,
+            ct.clone(),
+        ));
+        ct.drop_guard()
+    }
+
+    /// Returns the url of the endpoint currently used to send requests.
+    pub fn active_endpoint(&self) -> &Url {
+        &self.endpoints[self.current.load(Ordering::Relaxed) % self.endpoints.len()].url
+    }
+
+    /// Returns the index of the endpoint to use for the next request.
+    ///
+    /// Endpoints marked as unhealthy are skipped until their cooldown expires,
+    /// after which they're tried again. If all endpoints are unhealthy, the
+    /// current endpoint is used anyway.
+    fn select_endpoint(&self) -> usize {
+        let current = self.current.load(Ordering::Relaxed);
+        let now = Instant::now();
+        for offset in 0..self.endpoints.len() {
+            let index = (current + offset) % self.endpoints.len();
+            if self.endpoints[index].is_healthy(now) {
+                if offset != 0 {
+                    self.current.store(index, Ordering::Relaxed);
+                }
+                return index;
+            }
+        }
+        current % self.endpoints.len()
+    }
+
+    /// Sends a request, retrying transport errors with backoff on the next endpoint.
+    ///
+    /// The latency of each attempt is recorded under the given rpc `method`.
+    async fn request<T, F, Fut>(&self, method: &'static str, f: F) -> Result<T, HttpProviderError>
+    where
+        F: Fn(Arc<AnyProvider>) -> Fut,
+        Fut: Future<Output = Result<T, StarknetProviderError>>,
+    {
+        let mut backoff = self.retry.initial_backoff;
+        let mut attempt = 0;
+        loop {
+            if let Some(rate_limiter) = &self.rate_limiter {
+                rate_limiter.until_ready().await;
+            }
+
+            let index = self.select_endpoint();
+            let endpoint = &self.endpoints[index];
+            let start = Instant::now();
+            let result = f(endpoint.client.clone()).await;
+            self.record_call_latency(method, start, result.is_ok());
+            let error = match result {
+                Ok(value) => return Ok(value),
+                Err(error) => error,
+            };
+
+            // Errors returned by the node are not caused by the transport,
+            // retrying them on another endpoint won't help.
+            if !is_retryable(&error) || attempt >= self.retry.max_retries {
+                return self.request_feeder_gateway(&f, error).await;
+            }
+
+            warn!(
+                url = %endpoint.url,
+                attempt = attempt,
+                error = ?error,
+                "rpc request failed, retrying"
+            );
+
+            endpoint.mark_unhealthy(self.retry.unhealthy_cooldown);
+            if self.endpoints.len() > 1 {
+                self.current
+                    .store((index + 1) % self.endpoints.len(), Ordering::Relaxed);
+            }
+
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(self.retry.max_backoff);
+            attempt += 1;
+        }
+    }
+
+    fn record_call_latency(&self, method: &'static str, start: Instant, success: bool) {
+        let cx = o11y::Context::current();
+        let status = if success { "ok" } else { "error" };
+        self.call_latency.record(
+            &cx,
+            start.elapsed().as_secs_f64(),
+            &[
+                KeyValue::new("method", method),
+                KeyValue::new("status", status),
+            ],
+        );
+    }
+
+    /// Sends the request to the feeder gateway, if the rpc error allows it.
+    ///
+    /// Returns the original error if no feeder gateway is configured.
+    async fn request_feeder_gateway<T, F, Fut>(
+        &self,
+        f: &F,
+        error: StarknetProviderError,
+    ) -> Result<T, HttpProviderError>
+    where
+        F: Fn(Arc<AnyProvider>) -> Fut,
+        Fut: Future<Output = Result<T, StarknetProviderError>>,
+    {
+        let Some(feeder_gateway) = &self.feeder_gateway else {
+            return Err(HttpProviderError::from_provider_error(error));
+        };
+
+        if !is_retryable(&error) && !is_block_not_found(&error) && !is_transaction_not_found(&error)
+        {
+            return Err(HttpProviderError::from_provider_error(error));
+        }
+
+        debug!(
+            url = %feeder_gateway.url,
+            error = ?error,
+            "rpc request failed, using feeder gateway"
+        );
+
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.until_ready().await;
+        }
+
+        f(feeder_gateway.client.clone())
+            .await
+            .map_err(HttpProviderError::from_provider_error)
+    }
+
+    /// Returns the feeder gateway head.
+    ///
+    /// The gateway is queried at most once every [GATEWAY_HEAD_INTERVAL],
+    /// in between the last head is returned.
+    async fn feeder_gateway_head(&self) -> Option<models::BlockHashAndNumber> {
+        let feeder_gateway = self.feeder_gateway.as_ref()?;
+
+        if let Some((checked_at, head)) = self
+            .gateway_head
+            .lock()
+            .expect("gateway head lock poisoned")
+            .as_ref()
+        {
+            if checked_at.elapsed() < GATEWAY_HEAD_INTERVAL {
+                return head.clone();
+            }
+        }
+
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.until_ready().await;
+        }
+
+        let head = match feeder_gateway.client.block_hash_and_number().await {
+            Ok(head) => Some(head),
+            Err(err) => {
+                warn!(error = ?err, "failed to fetch feeder gateway head");
+                None
+            }
+        };
+
+        *self
+            .gateway_head
+            .lock()
+            .expect("gateway head lock poisoned") = Some((Instant::now(), head.clone()));
+        head
     }
 
     async fn get_block_by_id(
@@ -93,10 +423,10 @@ impl HttpProvider {
     ) -> Result<(v1alpha2::BlockStatus, v1alpha2::BlockHeader, BlockBody), HttpProviderError> {
         let block_id: models::BlockId = id.try_into()?;
         let block = self
-            .provider
-            .get_block_with_txs(block_id)
-            .await
-            .map_err(HttpProviderError::from_provider_error)?;
+            .request("starknet_getBlockWithTxs", |client| async move {
+                client.get_block_with_txs(block_id).await
+            })
+            .await?;
 
         match block {
             models::MaybePendingBlockWithTxs::Block(ref block) => {
@@ -121,6 +451,157 @@ impl HttpProvider {
     }
 }
 
+impl Endpoint {
+    fn new(url: Url) -> Self {
+        let http = HttpTransport::new(url.clone());
+        let client = AnyProvider::JsonRpcHttp(JsonRpcClient::new(http));
+        Endpoint {
+            url,
+            client: Arc::new(client),
+            unhealthy_until: Mutex::new(None),
+        }
+    }
+
+    fn feeder_gateway(feeder_gateway_url: Url) -> Self {
+        // The gateway is only used to submit transactions, which the node never does.
+        let mut gateway_url = feeder_gateway_url.clone();
+        gateway_url.set_path(
+            &feeder_gateway_url
+                .path()
+                .replace("feeder_gateway", "gateway"),
+        );
+        // Same for the chain id, it's only used when signing transactions.
+        let gateway = SequencerGatewayProvider::new(
+            gateway_url,
+            feeder_gateway_url.clone(),
+            chain_id::MAINNET,
+        );
+        Endpoint {
+            url: feeder_gateway_url,
+            client: Arc::new(AnyProvider::SequencerGateway(gateway)),
+            unhealthy_until: Mutex::new(None),
+        }
+    }
+
+    fn is_healthy(&self, now: Instant) -> bool {
+        let unhealthy_until = self.unhealthy_until.lock().expect("endpoint lock poisoned");
+        unhealthy_until.map(|until| until <= now).unwrap_or(true)
+    }
+
+    fn mark_unhealthy(&self, cooldown: Duration) {
+        let mut unhealthy_until = self.unhealthy_until.lock().expect("endpoint lock poisoned");
+        *unhealthy_until = Some(Instant::now() + cooldown);
+    }
+
+    fn mark_healthy(&self) {
+        let mut unhealthy_until = self.unhealthy_until.lock().expect("endpoint lock poisoned");
+        *unhealthy_until = None;
+    }
+}
+
+/// How long a health check waits for an endpoint to respond.
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+async fn run_health_check(
+    endpoints: Arc<Vec<Endpoint>>,
+    interval: Duration,
+    cooldown: Duration,
+    ct: CancellationToken,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = ct.cancelled() => return,
+            _ = ticker.tick() => {},
+        }
+        check_endpoints(&endpoints, cooldown).await;
+    }
+}
+
+/// Requests the latest block number from each endpoint and updates its health.
+async fn check_endpoints(endpoints: &[Endpoint], cooldown: Duration) {
+    for endpoint in endpoints {
+        let was_healthy = endpoint.is_healthy(Instant::now());
+        match tokio::time::timeout(HEALTH_CHECK_TIMEOUT, endpoint.client.block_number()).await {
+            Ok(Ok(_)) => {
+                if !was_healthy {
+                    info!(url = %endpoint.url, "rpc endpoint is healthy again");
+                }
+                endpoint.mark_healthy();
+            }
+            Ok(Err(error)) => {
+                warn!(url = %endpoint.url, error = ?error, "rpc health check failed");
+                endpoint.mark_unhealthy(cooldown);
+            }
+            Err(_) => {
+                warn!(url = %endpoint.url, "rpc health check timed out");
+                endpoint.mark_unhealthy(cooldown);
+            }
+        }
+    }
+}
+
+/// How often the feeder gateway head is compared with the rpc head.
+const GATEWAY_HEAD_INTERVAL: Duration = Duration::from_secs(10);
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            unhealthy_cooldown: Duration::from_secs(30),
+            health_check_interval: None,
+        }
+    }
+}
+
+/// Returns true if the error is caused by the transport and not by the node.
+///
+/// Only connection errors and timeouts are retried. Errors returned by the
+/// node, rate limits and responses that can't be deserialized will fail again
+/// on retry.
+fn is_retryable(error: &StarknetProviderError) -> bool {
+    let StarknetProviderError::Other(error) = error else {
+        return false;
+    };
+
+    let error = error.as_any();
+    if let Some(JsonRpcClientError::TransportError(HttpTransportError::Reqwest(error))) =
+        error.downcast_ref::<JsonRpcClientError<HttpTransportError>>()
+    {
+        return is_transport_error(error);
+    }
+    if let Some(GatewayClientError::Network(error)) = error.downcast_ref::<GatewayClientError>() {
+        return is_transport_error(error);
+    }
+    false
+}
+
+fn is_transport_error(error: &reqwest::Error) -> bool {
+    error.is_connect() || error.is_timeout() || error.is_request()
+}
+
+fn is_block_not_found(error: &StarknetProviderError) -> bool {
+    matches!(
+        error,
+        StarknetProviderError::StarknetError(StarknetError::BlockNotFound)
+    )
+}
+
+fn is_transaction_not_found(error: &StarknetProviderError) -> bool {
+    matches!(
+        error,
+        StarknetProviderError::StarknetError(StarknetError::TransactionHashNotFound)
+    )
+}
+
+fn new_rpc_call_latency_histogram() -> Histogram<f64> {
+    let meter = o11y::meter("starknet_provider");
+    meter.f64_histogram("rpc_call_latency_seconds").init()
+}
+
 impl ProviderError for HttpProviderError {
     fn is_block_not_found(&self) -> bool {
         matches!(self, HttpProviderError::BlockNotFound)
@@ -155,13 +636,37 @@ trait TryToProto<T> {
 impl Provider for HttpProvider {
     type Error = HttpProviderError;
 
+    #[tracing::instrument(skip(self), err(Debug), level = "DEBUG")]
+    async fn get_chain_id(&self) -> Result<v1alpha2::FieldElement, Self::Error> {
+        let chain_id = self
+            .request("starknet_chainId", |client| async move {
+                client.chain_id().await
+            })
+            .await?;
+        Ok(chain_id.into())
+    }
+
     #[tracing::instrument(skip(self), err(Debug), level = "DEBUG")]
     async fn get_head(&self) -> Result<GlobalBlockId, Self::Error> {
-        let hash_and_number = self
-            .provider
-            .block_hash_and_number()
-            .await
-            .map_err(HttpProviderError::from_provider_error)?;
+        let mut hash_and_number = self
+            .request("starknet_blockHashAndNumber", |client| async move {
+                client.block_hash_and_number().await
+            })
+            .await?;
+
+        // If the rpc node is lagging behind, follow the feeder gateway head.
+        // Blocks and receipts not yet available on the rpc node are fetched from the gateway.
+        if let Some(gateway_head) = self.feeder_gateway_head().await {
+            if gateway_head.block_number > hash_and_number.block_number {
+                debug!(
+                    rpc_head = hash_and_number.block_number,
+                    gateway_head = gateway_head.block_number,
+                    "rpc node is lagging behind feeder gateway"
+                );
+                hash_and_number = gateway_head;
+            }
+        }
+
         let hash: v1alpha2::FieldElement = hash_and_number.block_hash.into();
         Ok(GlobalBlockId::new(
             hash_and_number.block_number,
@@ -189,10 +694,10 @@ impl Provider for HttpProvider {
     async fn get_state_update(&self, id: &BlockId) -> Result<v1alpha2::StateUpdate, Self::Error> {
         let block_id: models::BlockId = id.try_into()?;
         let state_update = self
-            .provider
-            .get_state_update(block_id)
-            .await
-            .map_err(HttpProviderError::from_provider_error)?
+            .request("starknet_getStateUpdate", |client| async move {
+                client.get_state_update(block_id).await
+            })
+            .await?
             .to_proto();
         Ok(state_update)
     }
@@ -206,13 +711,92 @@ impl Provider for HttpProvider {
             .try_into()
             .map_err(|err| HttpProviderError::Provider(Box::new(err)))?;
         let receipt = self
-            .provider
-            .get_transaction_receipt(hash)
-            .await
-            .map_err(HttpProviderError::from_provider_error)?
+            .request("starknet_getTransactionReceipt", |client| async move {
+                client.get_transaction_receipt(hash).await
+            })
+            .await?
             .to_proto();
         Ok(receipt)
     }
+
+    #[tracing::instrument(skip(self), fields(class_hash = %class_hash), err(Debug), level = "DEBUG")]
+    async fn get_class(
+        &self,
+        id: &BlockId,
+        class_hash: &v1alpha2::FieldElement,
+    ) -> Result<Vec<u8>, Self::Error> {
+        let block_id: models::BlockId = id.try_into()?;
+        let class_hash: FieldElement = class_hash
+            .try_into()
+            .map_err(|err| HttpProviderError::Provider(Box::new(err)))?;
+        let class = self
+            .request("starknet_getClass", |client| async move {
+                client.get_class(block_id, class_hash).await
+            })
+            .await?;
+        serde_json::to_vec(&class).map_err(|err| HttpProviderError::Provider(Box::new(err)))
+    }
+
+    #[tracing::instrument(skip(self), err(Debug), level = "DEBUG")]
+    async fn get_block_traces(&self, id: &BlockId) -> Result<Vec<u8>, Self::Error> {
+        let block_id: models::BlockId = id.try_into()?;
+        let traces = self
+            .request("starknet_traceBlockTransactions", |client| async move {
+                client.trace_block_transactions(block_id).await
+            })
+            .await?;
+        serde_json::to_vec(&traces).map_err(|err| HttpProviderError::Provider(Box::new(err)))
+    }
+
+    /// Commitments are not part of the rpc block header, so they're fetched
+    /// from the feeder gateway, if configured.
+    #[tracing::instrument(skip(self), err(Debug), level = "DEBUG")]
+    async fn get_block_commitments(
+        &self,
+        id: &BlockId,
+    ) -> Result<Option<BlockCommitments>, Self::Error> {
+        let Some(feeder_gateway) = &self.feeder_gateway else {
+            return Ok(None);
+        };
+        let AnyProvider::SequencerGateway(gateway) = feeder_gateway.client.as_ref() else {
+            return Ok(None);
+        };
+
+        let block_id = match id {
+            BlockId::Latest => GatewayBlockId::Latest,
+            BlockId::Pending => GatewayBlockId::Pending,
+            BlockId::Hash(hash) => GatewayBlockId::Hash(hash.try_into()?),
+            BlockId::Number(number) => GatewayBlockId::Number(*number),
+        };
+
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.until_ready().await;
+        }
+
+        #[allow(deprecated)]
+        let block = gateway
+            .get_block(block_id)
+            .await
+            .map_err(HttpProviderError::from_provider_error)?;
+
+        let event_count = block
+            .transaction_receipts
+            .iter()
+            .map(|receipt| receipt.events.len())
+            .sum();
+        Ok(Some(BlockCommitments {
+            transaction_count: block.transactions.len(),
+            event_count,
+            event_commitment: block.event_commitment.map(Into::into),
+        }))
+    }
+
+    async fn wait_for_new_head(&self) {
+        match &self.head_subscription {
+            Some(subscription) => subscription.wait_for_new_head().await,
+            None => futures::future::pending().await,
+        }
+    }
 }
 
 impl BlockId {
@@ -1369,8 +1953,160 @@ impl FieldElementExt for FieldElement {
 
 #[cfg(test)]
 mod tests {
-    use super::FieldElementExt;
-    use starknet::core::types::FieldElement;
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::{Duration, Instant},
+    };
+
+    use assert_matches::assert_matches;
+    use starknet::{
+        core::types::{FieldElement, StarknetError},
+        providers::{Provider as StarknetProvider, ProviderError as StarknetProviderError},
+    };
+    use url::Url;
+    use warp::Filter;
+
+    use super::{
+        check_endpoints, is_retryable, FieldElementExt, HttpProvider, HttpProviderError,
+        RetryConfig,
+    };
+
+    /// Starts a json-rpc server that replies with `body` to all requests.
+    ///
+    /// Returns the server url and the number of requests received.
+    fn serve(body: &'static str) -> (Url, Arc<AtomicUsize>) {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let route = warp::post().map({
+            let requests = requests.clone();
+            move || {
+                requests.fetch_add(1, Ordering::SeqCst);
+                warp::reply::with_header(body, "content-type", "application/json")
+            }
+        });
+        let (address, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        (format!("http://{address}").parse().unwrap(), requests)
+    }
+
+    /// Returns an url nobody is listening on.
+    fn closed_url() -> Url {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+        format!("http://{address}").parse().unwrap()
+    }
+
+    fn retry_config() -> RetryConfig {
+        RetryConfig {
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(1),
+            ..RetryConfig::default()
+        }
+    }
+
+    async fn block_number(provider: &HttpProvider) -> Result<u64, HttpProviderError> {
+        provider
+            .request("starknet_blockNumber", |client| async move {
+                client.block_number().await
+            })
+            .await
+    }
+
+    const BLOCK_NUMBER: &str = r#"{"jsonrpc":"2.0","id":1,"result":42}"#;
+    const BLOCK_NOT_FOUND: &str =
+        r#"{"jsonrpc":"2.0","id":1,"error":{"code":24,"message":"Block not found"}}"#;
+
+    #[tokio::test]
+    async fn test_request_fails_over_to_next_endpoint() {
+        let (url, requests) = serve(BLOCK_NUMBER);
+        let provider = HttpProvider::with_endpoints(vec![closed_url(), url.clone()])
+            .with_retry_config(retry_config());
+
+        assert_eq!(block_number(&provider).await.unwrap(), 42);
+        assert_eq!(provider.active_endpoint(), &url);
+        assert!(!provider.endpoints[0].is_healthy(Instant::now()));
+
+        // the failing endpoint is skipped while unhealthy.
+        assert_eq!(block_number(&provider).await.unwrap(), 42);
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_request_gives_up_after_max_retries() {
+        let provider =
+            HttpProvider::with_endpoints(vec![closed_url()]).with_retry_config(retry_config());
+        assert_matches!(
+            block_number(&provider).await,
+            Err(HttpProviderError::Provider(_))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_does_not_retry_node_errors() {
+        let (url, requests) = serve(BLOCK_NOT_FOUND);
+        let provider = HttpProvider::with_endpoints(vec![url]).with_retry_config(retry_config());
+        assert_matches!(
+            block_number(&provider).await,
+            Err(HttpProviderError::BlockNotFound)
+        );
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_request_does_not_retry_invalid_responses() {
+        let (url, requests) = serve("not json");
+        let provider = HttpProvider::with_endpoints(vec![url]).with_retry_config(retry_config());
+        assert_matches!(
+            block_number(&provider).await,
+            Err(HttpProviderError::Provider(_))
+        );
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(!is_retryable(&StarknetProviderError::StarknetError(
+            StarknetError::BlockNotFound
+        )));
+        assert!(!is_retryable(&StarknetProviderError::RateLimited));
+    }
+
+    #[test]
+    fn test_select_endpoint_skips_unhealthy() {
+        let provider = HttpProvider::with_endpoints(vec![closed_url(), closed_url(), closed_url()]);
+        let cooldown = Duration::from_secs(60);
+        assert_eq!(provider.select_endpoint(), 0);
+
+        provider.endpoints[0].mark_unhealthy(cooldown);
+        assert_eq!(provider.select_endpoint(), 1);
+
+        // the current endpoint is used if all endpoints are unhealthy.
+        provider.endpoints[1].mark_unhealthy(cooldown);
+        provider.endpoints[2].mark_unhealthy(cooldown);
+        assert_eq!(provider.select_endpoint(), 1);
+
+        // unhealthy endpoints are used again after the cooldown.
+        provider.endpoints[0].mark_unhealthy(Duration::ZERO);
+        assert_eq!(provider.select_endpoint(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_health_check_updates_endpoints() {
+        let (url, _) = serve(BLOCK_NUMBER);
+        let provider = HttpProvider::with_endpoints(vec![closed_url(), url]);
+        let cooldown = Duration::from_secs(60);
+        provider.endpoints[1].mark_unhealthy(cooldown);
+
+        check_endpoints(&provider.endpoints, cooldown).await;
+
+        let now = Instant::now();
+        assert!(!provider.endpoints[0].is_healthy(now));
+        assert!(provider.endpoints[1].is_healthy(now));
+    }
 
     #[test]
     fn test_field_element_to_u64() {
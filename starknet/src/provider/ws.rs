@@ -0,0 +1,115 @@
+//! Subscribe to new heads over websocket.
+use std::{sync::Arc, time::Duration};
+
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::sync::Notify;
+use tokio_tungstenite::{connect_async, tungstenite};
+use tokio_util::sync::{CancellationToken, DropGuard};
+use tracing::{debug, info, warn};
+use url::Url;
+
+/// A subscription to new heads published by a pathfinder node.
+///
+/// The subscription reconnects with exponential backoff when the connection
+/// drops. Callers should keep polling the head, since notifications can be
+/// missed while disconnected.
+pub struct HeadSubscription {
+    notify: Arc<Notify>,
+    _guard: DropGuard,
+}
+
+impl HeadSubscription {
+    /// Starts subscribing to new heads in the background.
+    ///
+    /// Must be called from within a tokio runtime.
+    pub fn start(url: Url) -> Self {
+        let notify = Arc::new(Notify::new());
+        let ct = CancellationToken::new();
+        tokio::spawn(run_subscription(url, notify.clone(), ct.clone()));
+        HeadSubscription {
+            notify,
+            _guard: ct.drop_guard(),
+        }
+    }
+
+    /// Waits until a new head is received.
+    pub async fn wait_for_new_head(&self) {
+        self.notify.notified().await
+    }
+}
+
+async fn run_subscription(url: Url, notify: Arc<Notify>, ct: CancellationToken) {
+    let min_backoff = Duration::from_secs(1);
+    let max_backoff = Duration::from_secs(60);
+    let mut backoff = min_backoff;
+    loop {
+        tokio::select! {
+            _ = ct.cancelled() => return,
+            result = subscribe(&url, &notify) => {
+                match result {
+                    Ok(_) => {
+                        warn!(url = %url, "head subscription closed");
+                        backoff = min_backoff;
+                    }
+                    Err(err) => {
+                        warn!(url = %url, error = ?err, "head subscription failed");
+                    }
+                }
+            }
+        }
+
+        tokio::select! {
+            _ = ct.cancelled() => return,
+            _ = tokio::time::sleep(backoff) => {},
+        }
+        backoff = Duration::min(backoff * 2, max_backoff);
+    }
+}
+
+async fn subscribe(url: &Url, notify: &Notify) -> Result<(), tungstenite::Error> {
+    let (mut ws, _) = connect_async(url.as_str()).await?;
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "pathfinder_subscribe",
+        "params": ["newHeads"],
+    });
+    ws.send(tungstenite::Message::Text(request.to_string()))
+        .await?;
+    info!(url = %url, "subscribed to new heads");
+
+    while let Some(message) = ws.next().await {
+        let tungstenite::Message::Text(text) = message? else {
+            continue;
+        };
+        if is_new_head_notification(&text) {
+            debug!("received new head notification");
+            notify.notify_one();
+        }
+    }
+
+    Ok(())
+}
+
+fn is_new_head_notification(text: &str) -> bool {
+    let Ok(value) = serde_json::from_str::<Value>(text) else {
+        return false;
+    };
+    value.get("method").and_then(Value::as_str) == Some("pathfinder_subscription")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_new_head_notification;
+
+    #[test]
+    fn test_is_new_head_notification() {
+        let notification = r#"{"jsonrpc":"2.0","method":"pathfinder_subscription","params":{"result":{"block_number":1},"subscription":0}}"#;
+        assert!(is_new_head_notification(notification));
+
+        let response = r#"{"jsonrpc":"2.0","result":0,"id":1}"#;
+        assert!(!is_new_head_notification(response));
+        assert!(!is_new_head_notification("not json"));
+    }
+}
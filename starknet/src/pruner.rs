@@ -0,0 +1,91 @@
+//! Delete old block data to limit the database size.
+use std::{sync::Arc, time::Duration};
+
+use apibara_node::db::libmdbx::{Environment, EnvironmentKind, Error as MdxError};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info};
+
+use crate::db::{DatabaseStorage, StorageReader, StorageWriter};
+
+/// How often to check for blocks to prune.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Maximum number of blocks pruned in a single transaction.
+///
+/// Keeps write transactions short so that ingestion is not blocked for long.
+const PRUNE_BATCH_SIZE: u64 = 100;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PrunerError {
+    #[error("database error")]
+    Database(#[from] MdxError),
+}
+
+/// A service that periodically deletes the data of old finalized blocks.
+///
+/// Only the data of the latest `keep_blocks` finalized blocks is kept, older
+/// blocks keep their header but are no longer served to clients.
+pub struct Pruner<E: EnvironmentKind> {
+    storage: DatabaseStorage<E>,
+    keep_blocks: u64,
+}
+
+impl<E: EnvironmentKind> Pruner<E> {
+    pub fn new(db: Arc<Environment<E>>, keep_blocks: u64) -> Self {
+        let storage = DatabaseStorage::new(db);
+        Pruner {
+            storage,
+            keep_blocks,
+        }
+    }
+
+    pub async fn start(self, ct: CancellationToken) -> Result<(), PrunerError> {
+        let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = ct.cancelled() => return Ok(()),
+                _ = interval.tick() => {
+                    self.prune(&ct).await?;
+                }
+            }
+        }
+    }
+
+    async fn prune(&self, ct: &CancellationToken) -> Result<(), PrunerError> {
+        let Some(finalized) = self.storage.highest_finalized_block()? else {
+            return Ok(());
+        };
+        let Some(earliest) = self.storage.earliest_available_block()? else {
+            return Ok(());
+        };
+
+        let prune_below = finalized.number().saturating_sub(self.keep_blocks);
+        if earliest.number() >= prune_below {
+            return Ok(());
+        }
+
+        info!(
+            earliest = earliest.number(),
+            prune_below = prune_below,
+            "pruning old blocks"
+        );
+
+        let mut current = earliest.number();
+        while current < prune_below {
+            if ct.is_cancelled() {
+                return Ok(());
+            }
+
+            current = u64::min(current + PRUNE_BATCH_SIZE, prune_below);
+            let mut txn = self.storage.begin_txn()?;
+            txn.prune_blocks_below(current)?;
+            txn.commit()?;
+            debug!(pruned_below = current, "pruned blocks");
+
+            // give other tasks a chance to run between batches.
+            tokio::task::yield_now().await;
+        }
+
+        Ok(())
+    }
+}
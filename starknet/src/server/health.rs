@@ -7,14 +7,21 @@ use apibara_node::db::{
     MdbxTransactionExt,
 };
 use tokio_util::sync::CancellationToken;
-use tonic_health::pb::health_server::{Health, HealthServer};
-use tracing::warn;
+use tonic_health::{
+    pb::health_server::{Health, HealthServer},
+    ServingStatus,
+};
+use tracing::{info, warn};
 
 use crate::db::tables;
 
+/// Name of the stream service, as registered in the health service.
+const STREAM_SERVICE_NAME: &str = "apibara.node.v1alpha2.Stream";
+
 pub struct HealthReporter<E: EnvironmentKind> {
     db: Arc<Environment<E>>,
-    _reporter: tonic_health::server::HealthReporter,
+    reporter: tonic_health::server::HealthReporter,
+    is_serving: Option<bool>,
 }
 
 impl<E> HealthReporter<E>
@@ -26,7 +33,8 @@ where
         (
             HealthReporter {
                 db,
-                _reporter: reporter,
+                reporter,
+                is_serving: None,
             },
             service,
         )
@@ -39,13 +47,15 @@ where
                 return;
             }
 
-            if self.check_db().is_ok() {
-                self.set_serving().await;
-            } else {
-                self.set_not_serving().await;
+            match self.check_db() {
+                Ok(_) => self.set_serving().await,
+                Err(err) => self.set_not_serving(err).await,
             }
 
-            tokio::time::sleep(interval).await;
+            tokio::select! {
+                _ = ct.cancelled() => return,
+                _ = tokio::time::sleep(interval) => {},
+            }
         }
     }
 
@@ -59,19 +69,26 @@ where
     }
 
     async fn set_serving(&mut self) {
-        /*
-        self.reporter
-            .set_serving::<pb::node_server::NodeServer<NodeServer<E>>>()
-            .await
-        */
+        if self.is_serving != Some(true) {
+            info!("server is serving");
+        }
+        self.is_serving = Some(true);
+        self.set_status(ServingStatus::Serving).await;
+    }
+
+    async fn set_not_serving(&mut self, err: MdbxError) {
+        if self.is_serving != Some(false) {
+            warn!(error = ?err, "server is not serving");
+        }
+        self.is_serving = Some(false);
+        self.set_status(ServingStatus::NotServing).await;
     }
 
-    async fn set_not_serving(&mut self) {
-        warn!("server is not serving");
-        /*
+    async fn set_status(&mut self, status: ServingStatus) {
+        // the empty service name is used to report the status of the whole server.
+        self.reporter.set_service_status("", status).await;
         self.reporter
-            .set_not_serving::<pb::node_server::NodeServer<NodeServer<E>>>()
-            .await
-        */
+            .set_service_status(STREAM_SERVICE_NAME, status)
+            .await;
     }
 }
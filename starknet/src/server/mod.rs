@@ -3,14 +3,18 @@ pub mod stream;
 
 use std::{net::SocketAddr, sync::Arc};
 
-use apibara_core::node as node_pb;
+use apibara_core::{node as node_pb, starknet as starknet_pb};
 use apibara_node::{
     db::libmdbx::{Environment, EnvironmentKind},
-    server::{QuotaClientFactory, QuotaConfiguration, RequestObserver, SimpleRequestObserver},
+    o11y,
+    server::{
+        AuthConfiguration, AuthError, Authenticator, QuotaClientFactory, QuotaConfiguration,
+        RequestMetricsLayer, RequestObserver, SimpleRequestObserver,
+    },
 };
 use tokio::task::JoinError;
 use tokio_util::sync::CancellationToken;
-use tonic::transport::Server as TonicServer;
+use tonic::transport::{Server as TonicServer, ServerTlsConfig};
 use tracing::{debug_span, error, info};
 
 use crate::{
@@ -27,6 +31,9 @@ pub struct Server<E: EnvironmentKind, O: RequestObserver> {
     blocks_per_second_quota: u32,
     request_observer: O,
     quota_configuration: QuotaConfiguration,
+    auth_configuration: AuthConfiguration,
+    tls_config: Option<ServerTlsConfig>,
+    max_concurrent_streams: Option<usize>,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -37,6 +44,8 @@ pub enum ServerError {
     Task(#[from] JoinError),
     #[error("error starting reflection server")]
     ReflectionServer(#[from] tonic_reflection::server::Error),
+    #[error("error configuring authentication")]
+    Auth(#[from] AuthError),
 }
 
 impl<E, O> Server<E, O>
@@ -53,6 +62,7 @@ where
         let ingestion = Arc::new(ingestion);
         let request_observer = SimpleRequestObserver::default();
         let quota_configuration = QuotaConfiguration::NoQuota;
+        let auth_configuration = AuthConfiguration::NoAuth;
         Server {
             db,
             ingestion,
@@ -60,6 +70,9 @@ where
             request_observer,
             blocks_per_second_quota,
             quota_configuration,
+            auth_configuration,
+            tls_config: None,
+            max_concurrent_streams: None,
         }
     }
 
@@ -72,6 +85,9 @@ where
             request_observer,
             blocks_per_second_quota: self.blocks_per_second_quota,
             quota_configuration: self.quota_configuration,
+            auth_configuration: self.auth_configuration,
+            tls_config: self.tls_config,
+            max_concurrent_streams: self.max_concurrent_streams,
         }
     }
 
@@ -80,6 +96,23 @@ where
         self
     }
 
+    pub fn with_auth_configuration(mut self, config: AuthConfiguration) -> Self {
+        self.auth_configuration = config;
+        self
+    }
+
+    /// Limit the number of streams served concurrently.
+    pub fn with_max_concurrent_streams(mut self, max_streams: usize) -> Self {
+        self.max_concurrent_streams = Some(max_streams);
+        self
+    }
+
+    /// Serve requests over TLS.
+    pub fn with_tls_config(mut self, config: ServerTlsConfig) -> Self {
+        self.tls_config = Some(config);
+        self
+    }
+
     pub async fn start(self, addr: SocketAddr, ct: CancellationToken) -> Result<(), ServerError> {
         let (mut health_reporter, health_service) = HealthReporter::new(self.db.clone());
 
@@ -90,12 +123,17 @@ where
 
         let reflection_service = tonic_reflection::server::Builder::configure()
             .register_encoded_file_descriptor_set(node_pb::v1alpha2::node_file_descriptor_set())
+            .register_encoded_file_descriptor_set(
+                starknet_pb::v1alpha2::starknet_file_descriptor_set(),
+            )
+            .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
             .build()?;
 
+        let authenticator = Authenticator::new(self.auth_configuration).await?;
         let quota_client_factory = QuotaClientFactory::new(self.quota_configuration);
         let storage = DatabaseStorage::new(self.db);
 
-        let stream_service = StreamService::new(
+        let mut stream_service = StreamService::new(
             self.ingestion,
             self.status,
             storage,
@@ -103,12 +141,29 @@ where
             self.blocks_per_second_quota,
             quota_client_factory,
         )
-        .into_service();
+        .with_shutdown_signal(ct.clone());
+
+        if let Some(max_streams) = self.max_concurrent_streams {
+            stream_service = stream_service.with_max_concurrent_streams(max_streams);
+        }
+
+        let stream_service = stream_service.into_service_with_authenticator(authenticator);
 
         info!(addr = %addr, "starting server");
 
-        TonicServer::builder()
-            .trace_fn(|_| debug_span!("node_server"))
+        let mut server = TonicServer::builder();
+        if let Some(tls_config) = self.tls_config {
+            info!("serving over tls");
+            server = server.tls_config(tls_config)?;
+        }
+
+        server
+            .trace_fn(|request| {
+                let span = debug_span!("node_server");
+                o11y::set_parent_from_headers(&span, request.headers());
+                span
+            })
+            .layer(RequestMetricsLayer::new())
             .add_service(health_service)
             .add_service(stream_service)
             .add_service(reflection_service)
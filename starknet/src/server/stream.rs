@@ -10,12 +10,15 @@ use apibara_core::node::v1alpha2::{
     stream_server, StatusRequest, StatusResponse, StreamDataRequest, StreamDataResponse,
 };
 use apibara_node::{
-    server::{QuotaClientFactory, RequestObserver},
+    o11y::{self, UpDownCounter},
+    server::{Authenticator, QuotaClientFactory, RequestMeter, RequestObserver},
     stream::{new_data_stream, ResponseStream, StreamConfigurationStream, StreamError},
 };
-use futures::Stream;
+use futures::{Stream, StreamExt};
 use pin_project::pin_project;
-use tonic::{metadata::MetadataMap, Request, Response, Streaming};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio_util::sync::CancellationToken;
+use tonic::{codegen::InterceptedService, metadata::MetadataMap, Request, Response, Streaming};
 use tracing::warn;
 use tracing_futures::Instrument;
 
@@ -34,8 +37,14 @@ pub struct StreamService<R: StorageReader, O: RequestObserver> {
     storage: Arc<R>,
     request_observer: O,
     quota_client_factory: QuotaClientFactory,
+    stream_permits: Option<Arc<Semaphore>>,
+    shutdown: CancellationToken,
+    connected_streams: UpDownCounter<i64>,
 }
 
+/// Clients are asked to wait this many seconds before retrying when the server is busy.
+const RETRY_AFTER_SECONDS: &str = "5";
+
 impl<R, O> StreamService<R, O>
 where
     R: StorageReader + Send + Sync + 'static,
@@ -57,13 +66,40 @@ where
             request_observer,
             blocks_per_second_quota,
             quota_client_factory,
+            stream_permits: None,
+            shutdown: CancellationToken::new(),
+            connected_streams: new_connected_streams_counter(),
         }
     }
 
+    /// Limit the number of streams served concurrently.
+    ///
+    /// Streams over the limit are rejected with `RESOURCE_EXHAUSTED`.
+    pub fn with_max_concurrent_streams(mut self, max_streams: usize) -> Self {
+        self.stream_permits = Some(Arc::new(Semaphore::new(max_streams)));
+        self
+    }
+
+    /// End all streams cleanly when `ct` is cancelled.
+    ///
+    /// Without this, open streams keep the server from shutting down.
+    pub fn with_shutdown_signal(mut self, ct: CancellationToken) -> Self {
+        self.shutdown = ct;
+        self
+    }
+
     pub fn into_service(self) -> stream_server::StreamServer<Self> {
         stream_server::StreamServer::new(self)
     }
 
+    /// Returns a service that requires requests to be authenticated.
+    pub fn into_service_with_authenticator(
+        self,
+        authenticator: Authenticator,
+    ) -> InterceptedService<stream_server::StreamServer<Self>, Authenticator> {
+        stream_server::StreamServer::with_interceptor(self, authenticator)
+    }
+
     async fn stream_data_with_configuration<S, E>(
         &self,
         metadata: MetadataMap,
@@ -73,8 +109,30 @@ where
         S: Stream<Item = Result<StreamDataRequest, E>> + Unpin,
         E: std::error::Error + Send + Sync + 'static,
     {
+        let permit = match &self.stream_permits {
+            None => None,
+            Some(permits) => {
+                let permit = permits.clone().try_acquire_owned().map_err(|_| {
+                    warn!("rejecting stream: too many concurrent streams");
+                    let mut status = tonic::Status::resource_exhausted(
+                        "too many concurrent streams, retry later",
+                    );
+                    status
+                        .metadata_mut()
+                        .insert("retry-after", RETRY_AFTER_SECONDS.parse().unwrap());
+                    status
+                })?;
+                Some(permit)
+            }
+        };
+
         let stream_span = self.request_observer.stream_data_span(&metadata);
+        let blocks_per_second_quota = self
+            .request_observer
+            .stream_data_blocks_per_second(&metadata)
+            .unwrap_or(self.blocks_per_second_quota);
         let stream_meter = self.request_observer.stream_data_meter(&metadata);
+        stream_meter.increment_streams_opened_counter();
 
         let quota_client = self
             .quota_client_factory
@@ -99,12 +157,17 @@ where
             ingestion_stream,
             cursor_producer,
             batch_producer,
-            self.blocks_per_second_quota,
+            blocks_per_second_quota,
             stream_meter,
             quota_client,
         );
 
-        Ok(ResponseStream::new(data_stream).instrument(stream_span))
+        let shutdown = self.shutdown.clone();
+        let response = ResponseStream::new(data_stream)
+            .take_until(async move { shutdown.cancelled().await })
+            .instrument(stream_span);
+        let connected = ConnectedStreamGuard::new(self.connected_streams.clone());
+        Ok(PermitStream::new(response, permit, connected))
     }
 }
 
@@ -136,8 +199,11 @@ where
         request: Request<StreamDataRequest>,
     ) -> Result<Response<Self::StreamDataImmutableStream>, tonic::Status> {
         let metadata = request.metadata().clone();
+        let mut request = request.into_inner();
+        // immutable streams cannot acknowledge data.
+        request.max_unacknowledged_batches = None;
         let configuration_stream = ImmutableRequestStream {
-            request: Some(request.into_inner()),
+            request: Some(request),
         };
         let response = self
             .stream_data_with_configuration(metadata, configuration_stream)
@@ -176,6 +242,66 @@ impl Stream for ImmutableRequestStream {
     }
 }
 
+/// A stream that holds a permit until it's dropped.
+#[pin_project]
+struct PermitStream<S> {
+    #[pin]
+    inner: S,
+    _permit: Option<OwnedSemaphorePermit>,
+    _connected: ConnectedStreamGuard,
+}
+
+impl<S> PermitStream<S> {
+    fn new(
+        inner: S,
+        permit: Option<OwnedSemaphorePermit>,
+        connected: ConnectedStreamGuard,
+    ) -> Self {
+        PermitStream {
+            inner,
+            _permit: permit,
+            _connected: connected,
+        }
+    }
+}
+
+/// Counts a stream as connected until it's dropped.
+struct ConnectedStreamGuard {
+    counter: UpDownCounter<i64>,
+}
+
+impl ConnectedStreamGuard {
+    fn new(counter: UpDownCounter<i64>) -> Self {
+        let cx = o11y::Context::current();
+        counter.add(&cx, 1, &[]);
+        ConnectedStreamGuard { counter }
+    }
+}
+
+impl Drop for ConnectedStreamGuard {
+    fn drop(&mut self) {
+        let cx = o11y::Context::current();
+        self.counter.add(&cx, -1, &[]);
+    }
+}
+
+fn new_connected_streams_counter() -> UpDownCounter<i64> {
+    let meter = o11y::meter("stream_data");
+    meter.i64_up_down_counter("streams_connected").init()
+}
+
+impl<S: Stream> Stream for PermitStream<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Option<Self::Item>> {
+        self.project().inner.poll_next(cx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
 /// A simple adapter from a generic ingestion stream to the one used by the server/stream module.
 #[pin_project]
 pub struct IngestionStream<L, E>
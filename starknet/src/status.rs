@@ -7,8 +7,8 @@ use tokio_util::sync::CancellationToken;
 use tracing::warn;
 
 use crate::{
-    core::GlobalBlockId, core::IngestionMessage, ingestion::IngestionStreamClient,
-    provider::Provider,
+    core::GlobalBlockId, core::IngestionMessage, db::StorageReader,
+    ingestion::IngestionStreamClient, provider::Provider,
 };
 
 #[derive(Debug, thiserror::Error)]
@@ -24,21 +24,28 @@ pub enum Message {
     GetStatus(oneshot::Sender<StatusResponse>),
 }
 
-pub struct StatusService<G: Provider> {
+pub struct StatusService<G: Provider, R: StorageReader> {
     provider: Arc<G>,
+    storage: R,
     ingestion: Arc<IngestionStreamClient>,
     rx: mpsc::Receiver<Message>,
 }
 
+#[derive(Clone)]
 pub struct StatusClient {
     tx: mpsc::Sender<Message>,
 }
 
-impl<G: Provider> StatusService<G> {
-    pub fn new(provider: Arc<G>, ingestion: IngestionStreamClient) -> (Self, StatusClient) {
+impl<G: Provider, R: StorageReader> StatusService<G, R> {
+    pub fn new(
+        provider: Arc<G>,
+        storage: R,
+        ingestion: IngestionStreamClient,
+    ) -> (Self, StatusClient) {
         let (tx, rx) = mpsc::channel(32);
         let server = Self {
             provider,
+            storage,
             ingestion: Arc::new(ingestion),
             rx,
         };
@@ -49,7 +56,12 @@ impl<G: Provider> StatusService<G> {
     pub async fn start(mut self, ct: CancellationToken) -> Result<(), StatusServiceError> {
         let mut ingestion = self.ingestion.subscribe().await;
 
-        let mut last_ingested: Option<GlobalBlockId> = None;
+        // Start from the database state so that the status is correct before
+        // the first ingestion message.
+        let mut last_ingested: Option<GlobalBlockId> =
+            self.storage.highest_accepted_block().ok().flatten();
+        let mut finalized: Option<GlobalBlockId> =
+            self.storage.highest_finalized_block().ok().flatten();
 
         loop {
             if ct.is_cancelled() {
@@ -66,9 +78,12 @@ impl<G: Provider> StatusService<G> {
                         },
                         Some(Message::GetStatus(tx)) => {
                             let current_head = self.get_chain_head().await;
+                            let earliest_available = self.get_earliest_available();
                             let response = StatusResponse {
                                 current_head: current_head.map(|c| c.to_cursor()),
                                 last_ingested: last_ingested.map(|c| c.to_cursor()),
+                                finalized: finalized.map(|c| c.to_cursor()),
+                                earliest_available: earliest_available.map(|c| c.to_cursor()),
                             };
                             let _ = tx.send(response);
                         }
@@ -85,6 +100,7 @@ impl<G: Provider> StatusService<G> {
                             break;
                         }
                         Some(Ok(IngestionMessage::Finalized(cursor))) => {
+                            finalized = Some(cursor);
                             // Only update finalized cursor if it is newer than the last ingested cursor.
                             if let Some(prev) = last_ingested {
                                 if prev.number() < cursor.number() {
@@ -114,6 +130,10 @@ impl<G: Provider> StatusService<G> {
     async fn get_chain_head(&self) -> Option<GlobalBlockId> {
         self.provider.get_head().await.ok()
     }
+
+    fn get_earliest_available(&self) -> Option<GlobalBlockId> {
+        self.storage.earliest_available_block().ok().flatten()
+    }
 }
 
 impl StatusClient {
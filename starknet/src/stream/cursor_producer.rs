@@ -4,7 +4,10 @@ use std::{
     task::{self, Poll, Waker},
 };
 
-use apibara_core::{node::v1alpha2::DataFinality, starknet::v1alpha2};
+use apibara_core::{
+    node::v1alpha2::{Cursor, DataFinality},
+    starknet::v1alpha2,
+};
 use apibara_node::{
     async_trait,
     stream::{
@@ -130,6 +133,17 @@ where
         }
 
         if cursors.is_empty() {
+            // The node started ingesting after the requested block, skip to
+            // the earliest block available.
+            if let Some(earliest) = self.storage.earliest_available_block()? {
+                if next_block_number < earliest.number() {
+                    return self.next_cursor_finalized(
+                        starting_cursor,
+                        earliest.number(),
+                        finalized,
+                    );
+                }
+            }
             return Ok(None);
         }
 
@@ -213,6 +227,93 @@ where
             waker.wake();
         }
     }
+
+    /// Returns a response with the cursor closest to the missing `cursor` and
+    /// the node's chain, to help users spot pruned data or the wrong network.
+    ///
+    /// The chain is identified by the chain id stored by ingestion.
+    fn missing_starting_cursor(
+        &self,
+        cursor: GlobalBlockId,
+    ) -> Result<ReconfigureResponse<GlobalBlockId>, StreamError> {
+        let earliest = self
+            .storage
+            .earliest_available_block()
+            .map_err(StreamError::internal)?;
+        let highest = self
+            .storage
+            .highest_accepted_block()
+            .map_err(StreamError::internal)?;
+
+        let nearest_available = match (earliest, highest) {
+            (Some(earliest), _) if cursor.number() < earliest.number() => Some(earliest),
+            (_, Some(highest)) if cursor.number() > highest.number() => Some(highest),
+            _ => self
+                .storage
+                .canonical_block_id(cursor.number())
+                .map_err(StreamError::internal)?,
+        };
+
+        let chain_id = self
+            .storage
+            .chain_id()
+            .map_err(StreamError::internal)?
+            .map(|chain_id| chain_id.to_hex());
+
+        Ok(ReconfigureResponse::MissingStartingCursor {
+            cursor,
+            nearest_available,
+            chain_id,
+        })
+    }
+}
+
+/// Returns the response to a new finalized block.
+///
+/// Clients streaming accepted or pending data are told when finality moves
+/// past data they already received, so they don't need to re-request it.
+fn finalize_response(
+    configuration: Option<&BatchConfiguration>,
+    previous: Option<GlobalBlockId>,
+    finalized: GlobalBlockId,
+) -> IngestionResponse<GlobalBlockId> {
+    let Some(configuration) = configuration else {
+        return IngestionResponse::Ok;
+    };
+
+    // finalized data is sent as regular data.
+    if configuration.data_finality == DataFinality::DataStatusFinalized {
+        return IngestionResponse::Ok;
+    }
+
+    let Some(current) = configuration.current else {
+        return IngestionResponse::Ok;
+    };
+
+    let previous_number = previous.map(|c| c.number());
+    let has_advanced = previous_number
+        .map(|n| n < finalized.number())
+        .unwrap_or(true);
+    let has_non_finalized_data = previous_number
+        .map(|n| n < current.number())
+        .unwrap_or(true);
+
+    if has_advanced && has_non_finalized_data {
+        IngestionResponse::Finalize(lowest_cursor(current, finalized))
+    } else {
+        IngestionResponse::Ok
+    }
+}
+
+/// Returns the cursor used to compare block ids.
+///
+/// Pending blocks have a zero hash, which matches any block at the same height.
+fn comparable_cursor(id: &GlobalBlockId) -> Cursor {
+    if id.hash().is_zero() {
+        Cursor::new(id.number(), Vec::new())
+    } else {
+        id.to_cursor()
+    }
 }
 
 fn lowest_cursor(a: GlobalBlockId, b: GlobalBlockId) -> GlobalBlockId {
@@ -247,19 +348,31 @@ where
                         .map_err(StreamError::internal)?
                     {
                         Some(starting_cursor) => starting_cursor,
-                        None => return Ok(ReconfigureResponse::MissingStartingCursor),
+                        None => return self.missing_starting_cursor(starting_cursor),
                     }
                 } else {
                     starting_cursor
                 };
 
+                // the status and header of pruned blocks are kept, but their data is not
+                // available anymore.
+                if let Some(earliest) = self
+                    .storage
+                    .earliest_available_block()
+                    .map_err(StreamError::internal)?
+                {
+                    if starting_cursor.number() < earliest.number() {
+                        return self.missing_starting_cursor(starting_cursor);
+                    }
+                }
+
                 debug!(starting_cursor = ?starting_cursor, "reconfigure stream with starting cursor");
                 let starting_status = match self
                     .storage
                     .read_status(&starting_cursor)
                     .map_err(StreamError::internal)?
                 {
-                    None => return Ok(ReconfigureResponse::MissingStartingCursor),
+                    None => return self.missing_starting_cursor(starting_cursor),
                     Some(starting_status) => starting_status,
                 };
 
@@ -276,7 +389,7 @@ where
                             .read_status(&new_root)
                             .map_err(StreamError::internal)?
                         {
-                            None => return Ok(ReconfigureResponse::MissingStartingCursor),
+                            None => return self.missing_starting_cursor(starting_cursor),
                             Some(status) => status,
                         };
 
@@ -289,7 +402,7 @@ where
                             .read_header(&new_root)
                             .map_err(StreamError::internal)?
                         {
-                            None => return Ok(ReconfigureResponse::MissingStartingCursor),
+                            None => return self.missing_starting_cursor(starting_cursor),
                             Some(header) => header,
                         };
 
@@ -336,18 +449,19 @@ where
                 IngestionResponse::Ok
             }
             IngestionMessage::Finalized(cursor) => {
-                state.finalized = Some(*cursor);
-                IngestionResponse::Ok
+                let previous = state.finalized.replace(*cursor);
+                finalize_response(self.configuration.as_ref(), previous, *cursor)
             }
             IngestionMessage::Invalidate(cursor) => {
                 state.pending = None;
                 state.accepted = state.accepted.map(|c| lowest_cursor(c, *cursor));
                 state.finalized = state.finalized.map(|c| lowest_cursor(c, *cursor));
-                // if the current cursor is after the new head, then data was invalidated.
+                // if the current cursor is not part of the new chain, then data was invalidated.
                 if let Some(configuration) = self.configuration.as_mut() {
+                    let head = comparable_cursor(cursor);
                     let is_invalidated = configuration
                         .current
-                        .map(|c| c.number() > cursor.number())
+                        .map(|c| comparable_cursor(&c).is_invalidated_by(&head))
                         .unwrap_or(false);
 
                     configuration.current =
@@ -410,10 +524,17 @@ mod tests {
 
     use apibara_core::{
         node::v1alpha2::DataFinality,
-        starknet::v1alpha2::{BlockHeader, BlockStatus, Filter},
+        starknet::v1alpha2::{BlockHeader, BlockStatus, FieldElement, Filter},
     };
-    use apibara_node::stream::{
-        CursorProducer, IngestionMessage, ReconfigureResponse, StreamConfiguration,
+    use apibara_node::{
+        db::{
+            libmdbx::{Environment, NoWriteMap},
+            MdbxEnvironmentExt,
+        },
+        stream::{
+            CursorProducer, IngestionMessage, IngestionResponse, ReconfigureResponse,
+            StreamConfiguration,
+        },
     };
     use assert_matches::assert_matches;
     use futures::{FutureExt, StreamExt, TryStreamExt};
@@ -421,7 +542,7 @@ mod tests {
 
     use crate::{
         core::{BlockHash, GlobalBlockId},
-        db::{MockStorageReader, StorageReader},
+        db::{tables, DatabaseStorage, MockStorageReader, StorageReader, StorageWriter},
     };
 
     use super::SequentialCursorProducer;
@@ -461,6 +582,7 @@ mod tests {
             finality,
             starting_cursor,
             filter: vec![Filter::default()],
+            max_unacknowledged_batches: None,
         }
     }
 
@@ -512,6 +634,34 @@ mod tests {
         }
     }
 
+    /// This test checks that the producer skips to the earliest block available if the node
+    /// started ingesting after genesis.
+    ///
+    /// Finality: FINALIZED
+    #[tokio::test]
+    async fn test_produce_from_earliest_available_block() {
+        let mut storage = MockStorageReader::new();
+        storage
+            .expect_canonical_block_id()
+            .returning(|i| Ok(if i < 50 { None } else { Some(new_block_id(i)) }));
+        storage
+            .expect_earliest_available_block()
+            .returning(|| Ok(Some(new_block_id(50))));
+        storage
+            .expect_highest_accepted_block()
+            .returning(|| Ok(Some(new_block_id(100))));
+        storage
+            .expect_highest_finalized_block()
+            .returning(|| Ok(Some(new_block_id(90))));
+
+        let mut producer =
+            new_producer(None, DataFinality::DataStatusFinalized, Arc::new(storage)).await;
+
+        let batch = producer.try_next().await.unwrap().unwrap();
+        let cursors = batch.as_finalized().unwrap();
+        assert_eq!(cursors.first().unwrap().number(), 50);
+    }
+
     /// This test checks that the producer doesn't produce any cursor if the requested block is
     /// after the most recent finalized block.
     ///
@@ -519,6 +669,9 @@ mod tests {
     #[tokio::test]
     async fn test_produce_nothing_if_after_finalized_as_finalized() {
         let mut storage = MockStorageReader::new();
+        storage
+            .expect_earliest_available_block()
+            .returning(|| Ok(Some(new_block_id(0))));
         storage
             .expect_read_status()
             .returning(|_| Ok(Some(BlockStatus::AcceptedOnL1)));
@@ -609,10 +762,11 @@ mod tests {
         let batch = producer.try_next().now_or_never();
         assert!(batch.is_none());
 
-        producer
+        let response = producer
             .handle_ingestion_message(&IngestionMessage::Finalized(new_block_id(14)))
             .await
             .unwrap();
+        assert_matches!(response, IngestionResponse::Ok);
 
         let mut expected_block = 11;
         for _ in 0..2 {
@@ -634,6 +788,9 @@ mod tests {
     #[tokio::test]
     async fn test_handle_invalidate_message_as_finalized() {
         let mut storage = MockStorageReader::new();
+        storage
+            .expect_earliest_available_block()
+            .returning(|| Ok(Some(new_block_id(0))));
         storage
             .expect_read_status()
             .returning(|_| Ok(Some(BlockStatus::AcceptedOnL1)));
@@ -751,6 +908,9 @@ mod tests {
     #[tokio::test]
     async fn test_full_batch_as_accepted() {
         let mut storage = MockStorageReader::new();
+        storage
+            .expect_earliest_available_block()
+            .returning(|| Ok(Some(new_block_id(0))));
         storage
             .expect_read_status()
             .returning(|_| Ok(Some(BlockStatus::AcceptedOnL1)));
@@ -794,6 +954,9 @@ mod tests {
     #[tokio::test]
     async fn test_handle_finalized_message_as_accepted() {
         let mut storage = MockStorageReader::new();
+        storage
+            .expect_earliest_available_block()
+            .returning(|| Ok(Some(new_block_id(0))));
         storage
             .expect_read_status()
             .returning(|_| Ok(Some(BlockStatus::AcceptedOnL1)));
@@ -824,10 +987,14 @@ mod tests {
         let accepted = batch.as_accepted().unwrap();
         assert_eq!(accepted.number(), 11);
 
-        producer
+        // block 11 was sent as accepted and is now finalized.
+        let response = producer
             .handle_ingestion_message(&IngestionMessage::Finalized(new_block_id(13)))
             .await
             .unwrap();
+        assert_matches!(response, IngestionResponse::Finalize(cursor) => {
+            assert_eq!(cursor.number(), 11);
+        });
 
         // finalized with block 12, 13
         let batch = producer.try_next().await.unwrap().unwrap();
@@ -847,6 +1014,9 @@ mod tests {
     #[tokio::test]
     async fn test_handle_accepted_message_as_accepted() {
         let mut storage = MockStorageReader::new();
+        storage
+            .expect_earliest_available_block()
+            .returning(|| Ok(Some(new_block_id(0))));
         storage
             .expect_read_status()
             .returning(|_| Ok(Some(BlockStatus::AcceptedOnL1)));
@@ -899,6 +1069,9 @@ mod tests {
     #[tokio::test]
     async fn test_handle_invalidate_message_as_accepted() {
         let mut storage = MockStorageReader::new();
+        storage
+            .expect_earliest_available_block()
+            .returning(|| Ok(Some(new_block_id(0))));
         storage
             .expect_read_status()
             .returning(|_| Ok(Some(BlockStatus::AcceptedOnL1)));
@@ -1007,6 +1180,9 @@ mod tests {
     #[tokio::test]
     async fn test_produce_full_batch_pending() {
         let mut storage = MockStorageReader::new();
+        storage
+            .expect_earliest_available_block()
+            .returning(|| Ok(Some(new_block_id(0))));
         storage
             .expect_read_status()
             .returning(|_| Ok(Some(BlockStatus::AcceptedOnL1)));
@@ -1067,6 +1243,9 @@ mod tests {
     #[tokio::test]
     async fn test_configure_with_valid_starting_cursor() {
         let mut storage = MockStorageReader::new();
+        storage
+            .expect_earliest_available_block()
+            .returning(|| Ok(Some(new_block_id(0))));
         storage
             .expect_read_status()
             .returning(|_| Ok(Some(BlockStatus::AcceptedOnL1)));
@@ -1095,6 +1274,9 @@ mod tests {
     #[tokio::test]
     async fn test_configure_with_invalidated_starting_cursor() {
         let mut storage = MockStorageReader::new();
+        storage
+            .expect_earliest_available_block()
+            .returning(|| Ok(Some(new_block_id(0))));
         storage
             .expect_read_status()
             .with(eq(new_block_id(8)))
@@ -1150,6 +1332,12 @@ mod tests {
         storage
             .expect_highest_finalized_block()
             .returning(|| Ok(Some(new_block_id(10))));
+        storage
+            .expect_earliest_available_block()
+            .returning(|| Ok(Some(new_block_id(10))));
+        storage
+            .expect_chain_id()
+            .returning(|| Ok(Some(FieldElement::from_u64(1))));
 
         let cursor = new_block_id(8);
         let mut producer = SequentialCursorProducer::new(Arc::new(storage));
@@ -1160,6 +1348,59 @@ mod tests {
             ))
             .await
             .unwrap();
-        assert_matches!(response, ReconfigureResponse::MissingStartingCursor);
+        assert_matches!(
+            response,
+            ReconfigureResponse::MissingStartingCursor {
+                cursor: c,
+                nearest_available: Some(nearest),
+                chain_id: Some(_),
+            } if c == cursor && nearest == new_block_id(10)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_configure_with_pruned_starting_cursor() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Environment::<NoWriteMap>::builder()
+            .open(dir.path())
+            .unwrap();
+        {
+            let txn = db.begin_rw_txn().unwrap();
+            tables::ensure(&txn).unwrap();
+            txn.commit().unwrap();
+        }
+        let storage = DatabaseStorage::new(Arc::new(db));
+
+        let mut txn = storage.begin_txn().unwrap();
+        for i in 0..5 {
+            let block_id = new_block_id(i);
+            let parent_id = new_block_id(i.saturating_sub(1));
+            txn.write_status(&block_id, BlockStatus::AcceptedOnL1)
+                .unwrap();
+            txn.write_header(&block_id, new_block_header(i, block_id, parent_id))
+                .unwrap();
+            txn.extend_canonical_chain(&block_id).unwrap();
+        }
+        txn.prune_blocks_below(3).unwrap();
+        txn.write_chain_id(&FieldElement::from_u64(1)).unwrap();
+        txn.commit().unwrap();
+
+        let cursor = new_block_id(1);
+        let mut producer = SequentialCursorProducer::new(Arc::new(storage));
+        let response = producer
+            .reconfigure(&new_configuration(
+                Some(cursor),
+                DataFinality::DataStatusFinalized,
+            ))
+            .await
+            .unwrap();
+        assert_matches!(
+            response,
+            ReconfigureResponse::MissingStartingCursor {
+                cursor: c,
+                nearest_available: Some(nearest),
+                chain_id: Some(_),
+            } if c == cursor && nearest == new_block_id(3)
+        );
     }
 }
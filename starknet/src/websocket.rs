@@ -1,20 +1,34 @@
+use crate::core::IngestionMessage;
 use crate::db::StorageReader;
 use crate::ingestion::IngestionStreamClient;
 use crate::server::stream::IngestionStream;
 use crate::stream::{DbBatchProducer, SequentialCursorProducer};
+use apibara_core::node::v1alpha2::StreamDataRequest;
 use apibara_core::starknet::v1alpha2::Block;
 use apibara_core::starknet::v1alpha2::Filter;
 use apibara_node::server::QuotaClient;
 use apibara_node::stream::{new_data_stream, StreamConfigurationStream, StreamError};
 use apibara_sdk::{Configuration, DataMessage};
-use futures::future;
+use futures::{future, stream, Stream};
 use futures::{SinkExt, StreamExt, TryStreamExt};
+use serde::Deserialize;
+use std::convert::Infallible;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use tracing::info;
+use warp::http::StatusCode;
+use warp::reply::{Reply, Response};
+use warp::sse::Event;
 use warp::ws::{Message, WebSocket};
 use warp::Filter as WarpFilter;
 
+/// Query parameters of the server-sent events data stream.
+#[derive(Debug, Deserialize)]
+struct StreamQuery {
+    /// The json-encoded stream configuration.
+    configuration: String,
+}
+
 #[derive(Clone)]
 pub struct WebsocketStreamServer<R: StorageReader + Send + Sync + 'static> {
     address: String,
@@ -42,14 +56,40 @@ impl<R: StorageReader + Send + Sync + 'static> WebsocketStreamServer<R> {
     pub async fn start(self: Arc<Self>) {
         let socket_address: SocketAddr = self.address.parse().expect("valid socket Address");
 
-        let ws = warp::path("ws")
-            .and(warp::ws())
-            .map(move |ws: warp::ws::Ws| {
-                let self_ = self.clone();
+        let ws = warp::path("ws").and(warp::ws()).map({
+            let self_ = self.clone();
+            move |ws: warp::ws::Ws| {
+                let self_ = self_.clone();
                 ws.on_upgrade(move |websocket| self_.connect(websocket))
+            }
+        });
+
+        // Stream data as server-sent events, for clients that cannot use websockets.
+        let data_events = warp::path("stream")
+            .and(warp::get())
+            .and(warp::query::<StreamQuery>())
+            .then({
+                let self_ = self.clone();
+                move |query: StreamQuery| {
+                    let self_ = self_.clone();
+                    async move { self_.stream_events(query).await }
+                }
             });
 
-        let server = warp::serve(ws).try_bind(socket_address);
+        // Stream ingestion progress as server-sent events.
+        let events = warp::path("events").and(warp::get()).then(move || {
+            let self_ = self.clone();
+            async move {
+                let ingestion_stream = self_.ingestion.subscribe().await;
+                let events = ingestion_stream.filter_map(|message| {
+                    let event = message.ok().and_then(|m| ingestion_event(&m));
+                    future::ready(event.map(Ok::<_, Infallible>))
+                });
+                warp::sse::reply(warp::sse::keep_alive().stream(events))
+            }
+        });
+
+        let server = warp::serve(ws.or(data_events).or(events)).try_bind(socket_address);
 
         info!("Running websocket server at {}!", socket_address);
 
@@ -64,19 +104,54 @@ impl<R: StorageReader + Send + Sync + 'static> WebsocketStreamServer<R> {
             user_rx
                 .map_err(Into::into)
                 .map_err(StreamError::Internal)
-                .and_then(|message| async move {
-                    serde_json::from_slice::<Configuration<Filter>>(message.as_bytes())
-                        .map_err(Into::into)
-                        .map_err(StreamError::Internal)
-                        .and_then(|message| {
-                            message
-                                .to_stream_data_request()
-                                .map_err(Into::into)
-                                .map_err(StreamError::Internal)
-                        })
-                }),
+                .and_then(|message| async move { parse_configuration(message.as_bytes()) }),
         );
 
+        // TODO: send the first decoding error downstream
+        self.data_stream(configuration_stream)
+            .await
+            .and_then(|message| async move {
+                serde_json::to_string(&message)
+                    .map(Message::text)
+                    .map_err(Into::into)
+                    .map_err(StreamError::Internal)
+            })
+            .take_while(|result| future::ready(result.is_ok()))
+            .forward(user_tx.sink_map_err(StreamError::internal))
+            .await
+            .unwrap(); // we have to unwrap here since ws.on_upgrade expects ()
+    }
+
+    /// Streams data as server-sent events.
+    ///
+    /// Each event is named after the message type (`data`, `invalidate`,
+    /// `finalize`, or `heartbeat`) and contains the json-encoded message.
+    async fn stream_events(self: Arc<Self>, query: StreamQuery) -> Response {
+        let request = match parse_configuration(query.configuration.as_bytes()) {
+            Ok(request) => request,
+            Err(err) => {
+                return warp::reply::with_status(err.to_string(), StatusCode::BAD_REQUEST)
+                    .into_response();
+            }
+        };
+
+        // the configuration cannot change, keep the stream open after sending it.
+        let configuration_stream =
+            stream::once(future::ready(Ok(request))).chain(stream::pending());
+        let events = self
+            .data_stream(Box::pin(configuration_stream))
+            .await
+            .map(|message| message.and_then(|message| data_event(&message)))
+            .take_while(|result| future::ready(result.is_ok()));
+
+        warp::sse::reply(warp::sse::keep_alive().stream(events)).into_response()
+    }
+
+    /// Creates a stream of data messages for the given configuration requests.
+    async fn data_stream(
+        self: Arc<Self>,
+        configuration_stream: impl Stream<Item = Result<StreamDataRequest, StreamError>> + Unpin,
+    ) -> impl Stream<Item = Result<DataMessage<Block>, StreamError>> {
         let configuration_stream = StreamConfigurationStream::new(configuration_stream);
 
         let meter = apibara_node::server::SimpleMeter::default();
@@ -99,20 +174,55 @@ impl<R: StorageReader + Send + Sync + 'static> WebsocketStreamServer<R> {
             quota_client,
         );
 
-        // TODO: send the first decoding error downstream
-        data_stream
-            .and_then(|message| async {
-                let message = DataMessage::<Block>::from_stream_data_response(message).ok_or(
-                    StreamError::internal("Cannot convert StreamDataResponse to DataMessage"),
-                )?;
-                serde_json::to_string(&message)
-                    .map(Message::text)
-                    .map_err(Into::into)
-                    .map_err(StreamError::Internal)
-            })
-            .take_while(|result| future::ready(result.is_ok()))
-            .forward(user_tx.sink_map_err(StreamError::internal))
-            .await
-            .unwrap(); // we have to unwrap here since ws.on_upgrade expects ()
+        data_stream.and_then(|message| async {
+            DataMessage::<Block>::from_stream_data_response(message).ok_or(StreamError::internal(
+                "Cannot convert StreamDataResponse to DataMessage",
+            ))
+        })
     }
 }
+
+/// Parses a json-encoded stream configuration.
+fn parse_configuration(message: &[u8]) -> Result<StreamDataRequest, StreamError> {
+    let configuration = serde_json::from_slice::<Configuration<Filter>>(message)
+        .map_err(Into::into)
+        .map_err(StreamError::Internal)?;
+    let mut request = configuration
+        .to_stream_data_request()
+        .map_err(Into::into)
+        .map_err(StreamError::Internal)?;
+    // clients of the json gateway cannot acknowledge data.
+    request.max_unacknowledged_batches = None;
+    Ok(request)
+}
+
+/// Converts a data message to a server-sent event.
+fn data_event(message: &DataMessage<Block>) -> Result<Event, StreamError> {
+    let name = match message {
+        DataMessage::Data { .. } => "data",
+        DataMessage::Invalidate { .. } => "invalidate",
+        DataMessage::Finalize { .. } => "finalize",
+        DataMessage::DecodeError { .. } => "decode_error",
+        DataMessage::Heartbeat => "heartbeat",
+    };
+    Event::default()
+        .event(name)
+        .json_data(message)
+        .map_err(StreamError::internal)
+}
+
+/// Converts an ingestion message to a server-sent event.
+///
+/// The event name is the type of message, the data is the json-encoded cursor.
+fn ingestion_event(message: &IngestionMessage) -> Option<Event> {
+    let (name, cursor) = match message {
+        IngestionMessage::Finalized(cursor) => ("finalized", cursor),
+        IngestionMessage::Accepted(cursor) => ("accepted", cursor),
+        IngestionMessage::Pending(cursor) => ("pending", cursor),
+        IngestionMessage::Invalidate(cursor) => ("invalidate", cursor),
+    };
+    Event::default()
+        .event(name)
+        .json_data(cursor.to_cursor())
+        .ok()
+}
@@ -1,8 +1,28 @@
 #![allow(dead_code)]
+use std::{net::TcpListener, time::Duration};
+
+use apibara_core::{
+    node::v1alpha2::DataFinality,
+    starknet::v1alpha2::{Block, Filter, HeaderFilter},
+};
+use apibara_sdk::{
+    configuration::{self, ConfigurationStream},
+    ClientBuilder, Configuration, DataMessage, DataStream, Uri,
+};
+use apibara_starknet::{start_node, StartArgs};
+use clap::Parser;
+use futures::FutureExt;
 use serde_json::json;
+use tempdir::TempDir;
 use testcontainers::{core::WaitFor, Image, ImageArgs};
+use tokio::task::JoinHandle;
+use tokio_stream::StreamExt;
+use tokio_util::sync::CancellationToken;
 use tracing::info;
 
+/// How long to wait for the node to ingest a block.
+const INGESTION_TIMEOUT: Duration = Duration::from_secs(30);
+
 #[derive(Default, Clone, Debug)]
 pub struct Devnet;
 
@@ -78,3 +98,202 @@ impl DevnetClient {
         Ok(())
     }
 }
+
+/// Katana devnet, producing a new block every `block_time_ms`.
+#[derive(Default, Clone, Debug)]
+pub struct Katana;
+
+#[derive(Clone, Debug)]
+pub struct KatanaArgs {
+    pub block_time_ms: u64,
+}
+
+impl Default for KatanaArgs {
+    fn default() -> Self {
+        KatanaArgs {
+            block_time_ms: 1_000,
+        }
+    }
+}
+
+impl Image for Katana {
+    type Args = KatanaArgs;
+
+    fn name(&self) -> String {
+        "ghcr.io/dojoengine/dojo".to_string()
+    }
+
+    fn tag(&self) -> String {
+        "v0.6.0".to_string()
+    }
+
+    fn ready_conditions(&self) -> Vec<WaitFor> {
+        vec![WaitFor::message_on_stdout("JSON-RPC server started")]
+    }
+
+    fn expose_ports(&self) -> Vec<u16> {
+        vec![5050]
+    }
+}
+
+impl ImageArgs for KatanaArgs {
+    fn into_iterator(self) -> Box<dyn Iterator<Item = String>> {
+        let args = vec![
+            "katana".to_string(),
+            "--host".to_string(),
+            "0.0.0.0".to_string(),
+            "--block-time".to_string(),
+            self.block_time_ms.to_string(),
+        ];
+        Box::new(args.into_iter())
+    }
+}
+
+#[derive(Parser)]
+struct TestCli {
+    #[command(flatten)]
+    args: StartArgs,
+}
+
+/// Parses the node arguments as if they were given on the command line.
+///
+/// Using the cli parser means tests don't need updating when new flags are added.
+pub fn start_args(args: &[&str]) -> StartArgs {
+    let args = std::iter::once("apibara-starknet").chain(args.iter().copied());
+    TestCli::parse_from(args).args
+}
+
+/// A DNA node running in-process against a temporary database.
+pub struct TestNode {
+    cts: CancellationToken,
+    handle: JoinHandle<()>,
+    address: String,
+    datadir: TempDir,
+}
+
+impl TestNode {
+    /// Starts a node ingesting from the given rpc.
+    ///
+    /// `extra_args` are appended to the command line flags.
+    pub fn start(rpc_url: &str, extra_args: &[&str]) -> TestNode {
+        let datadir = TempDir::new("apibara-test").unwrap();
+        Self::start_with_datadir(rpc_url, datadir, extra_args)
+    }
+
+    /// Starts a node using an existing database, for example to test restarts.
+    pub fn start_with_datadir(rpc_url: &str, datadir: TempDir, extra_args: &[&str]) -> TestNode {
+        let address = format!("127.0.0.1:{}", free_port());
+        let datadir_str = datadir.path().to_str().unwrap().to_string();
+        let mut args = vec![
+            "--rpc",
+            rpc_url,
+            "--data",
+            &datadir_str,
+            "--address",
+            &address,
+            "--wait-for-rpc",
+        ];
+        args.extend_from_slice(extra_args);
+        let args = start_args(&args);
+
+        let cts = CancellationToken::new();
+        let handle = tokio::spawn({
+            let cts = cts.clone();
+            async move {
+                start_node(args, cts).await.unwrap();
+            }
+        });
+
+        TestNode {
+            cts,
+            handle,
+            address,
+            datadir,
+        }
+    }
+
+    pub fn stream_url(&self) -> Uri {
+        format!("http://{}", self.address).parse().unwrap()
+    }
+
+    /// Waits until the node ingested the block with the given number.
+    pub async fn wait_for_block(&self, block_number: u64) {
+        let wait = async {
+            loop {
+                if let Ok(client) = ClientBuilder::default().connect(self.stream_url()).await {
+                    if let Ok(status) = client.status().await {
+                        let ingested = status.last_ingested.map(|c| c.order_key);
+                        if ingested.unwrap_or_default() >= block_number {
+                            return;
+                        }
+                    }
+                }
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            }
+        };
+
+        tokio::time::timeout(INGESTION_TIMEOUT, wait)
+            .await
+            .unwrap_or_else(|_| panic!("node did not ingest block {block_number}"));
+    }
+
+    /// Streams block headers, starting from genesis.
+    pub async fn stream_headers(
+        &self,
+        finality: DataFinality,
+    ) -> DataStream<Filter, Block, ConfigurationStream<Filter>> {
+        let configuration = Configuration::<Filter>::default()
+            .with_finality(finality)
+            .with_batch_size(1)
+            .with_filter(|mut filter| {
+                filter.with_header(HeaderFilter::new());
+                filter
+            });
+
+        let (config_client, config_stream) = configuration::channel(128);
+        config_client.send(configuration).await.unwrap();
+        ClientBuilder::default()
+            .connect(self.stream_url())
+            .await
+            .unwrap()
+            .start_stream::<Filter, Block, _>(config_stream)
+            .await
+            .unwrap()
+    }
+
+    /// Stops the node and returns its database, so that it can be restarted.
+    pub async fn stop(self) -> TempDir {
+        self.cts.cancel();
+        self.handle.await.unwrap();
+        self.datadir
+    }
+}
+
+/// Reads the next message and checks it contains the blocks with the given numbers.
+pub async fn assert_next_blocks(
+    stream: &mut DataStream<Filter, Block, ConfigurationStream<Filter>>,
+    expected: &[u64],
+) {
+    let message = stream.try_next().await.unwrap().unwrap();
+    let DataMessage::Data { batch, .. } = message else {
+        panic!("expected data message, got {message:?}");
+    };
+
+    let block_numbers = batch
+        .iter()
+        .map(|block| block.header.as_ref().unwrap().block_number)
+        .collect::<Vec<_>>();
+    assert_eq!(block_numbers, expected);
+}
+
+/// Checks that the stream has no message ready.
+pub fn assert_no_message(stream: &mut DataStream<Filter, Block, ConfigurationStream<Filter>>) {
+    let next_message = stream.try_next().now_or_never();
+    assert!(next_message.is_none());
+}
+
+/// Returns a port that is free to bind to.
+fn free_port() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().port()
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use apibara_core::node::v1alpha2::DataFinality;
+use apibara_node::o11y::init_opentelemetry;
+use testcontainers::{clients, RunnableImage};
+
+use common::{assert_next_blocks, Katana, KatanaArgs, TestNode};
+
+#[tokio::test]
+#[ignore = "requires docker"]
+async fn test_stream_katana_blocks_across_restarts() {
+    init_opentelemetry().unwrap();
+
+    let docker = clients::Cli::default();
+    let katana = docker.run(RunnableImage::from((
+        Katana,
+        KatanaArgs { block_time_ms: 500 },
+    )));
+    let rpc_url = format!("http://localhost:{}", katana.get_host_port_ipv4(5050));
+
+    let node = TestNode::start(&rpc_url, &[]);
+    node.wait_for_block(5).await;
+
+    let mut stream = node.stream_headers(DataFinality::DataStatusAccepted).await;
+    for block_number in 0..=5 {
+        assert_next_blocks(&mut stream, &[block_number]).await;
+    }
+
+    // restart with the same database, the node resumes ingestion.
+    let datadir = node.stop().await;
+    let node = TestNode::start_with_datadir(&rpc_url, datadir, &[]);
+    node.wait_for_block(10).await;
+
+    let mut stream = node.stream_headers(DataFinality::DataStatusAccepted).await;
+    for block_number in 0..=10 {
+        assert_next_blocks(&mut stream, &[block_number]).await;
+    }
+
+    node.stop().await;
+}
@@ -8,7 +8,7 @@ use apibara_core::{
 };
 use apibara_node::o11y::init_opentelemetry;
 use apibara_sdk::{configuration, ClientBuilder, Configuration, DataMessage};
-use apibara_starknet::{start_node, StartArgs};
+use apibara_starknet::start_node;
 use futures::FutureExt;
 use tempdir::TempDir;
 use testcontainers::clients;
@@ -16,7 +16,7 @@ use tokio_stream::StreamExt;
 use tokio_util::sync::CancellationToken;
 use tracing::info;
 
-use common::{Devnet, DevnetClient};
+use common::{start_args, Devnet, DevnetClient};
 
 // Starknet-devnet doesn't support RCP 0.4 yet
 // #[tokio::test]
@@ -32,27 +32,14 @@ async fn test_starknet_reorgs() {
     // share data between runs to test restarts.
     let tempdir = TempDir::new("test-starknet-reorgs").unwrap();
 
-    let node_args = StartArgs {
-        rpc: format!("http://localhost:{}/rpc", rpc_port),
-        data: None,
-        name: Some(
-            tempdir
-                .path()
-                .to_path_buf()
-                .into_os_string()
-                .into_string()
-                .unwrap(),
-        ),
-        wait_for_rpc: true,
-        devnet: false,
-        head_refresh_interval_ms: None,
-        use_metadata: Vec::default(),
-        blocks_per_second_limit: None,
-        address: None,
-        websocket_address: None,
-        quota_server: None,
-        dangerously_override_ingestion_start_block: None,
-    };
+    let rpc_url = format!("http://localhost:{}/rpc", rpc_port);
+    let name = tempdir
+        .path()
+        .to_path_buf()
+        .into_os_string()
+        .into_string()
+        .unwrap();
+    let node_args = start_args(&["--rpc", &rpc_url, "--name", &name, "--wait-for-rpc"]);
 
     let configuration = Configuration::<Filter>::default()
         .with_finality(DataFinality::DataStatusAccepted)
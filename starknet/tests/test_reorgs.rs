@@ -8,14 +8,14 @@ use apibara_core::{
 };
 use apibara_node::o11y::init_opentelemetry;
 use apibara_sdk::{configuration, ClientBuilder, Configuration, DataMessage};
-use apibara_starknet::{start_node, StartArgs};
+use apibara_starknet::start_node;
 use futures::FutureExt;
 use testcontainers::clients;
 use tokio_stream::StreamExt;
 use tokio_util::sync::CancellationToken;
 use tracing::info;
 
-use common::{Devnet, DevnetClient};
+use common::{start_args, Devnet, DevnetClient};
 
 // Starknet-devnet doesn't support RCP 0.4 yet
 // #[tokio::test]
@@ -32,20 +32,8 @@ async fn test_reorg_from_client_pov() {
     let node_handle = tokio::spawn({
         let cts = cts.clone();
         async move {
-            let args = StartArgs {
-                rpc: format!("http://localhost:{}/rpc", rpc_port),
-                data: None,
-                name: None,
-                wait_for_rpc: true,
-                devnet: true,
-                use_metadata: Vec::default(),
-                blocks_per_second_limit: None,
-                head_refresh_interval_ms: None,
-                address: None,
-                websocket_address: None,
-                quota_server: None,
-                dangerously_override_ingestion_start_block: None,
-            };
+            let rpc_url = format!("http://localhost:{}/rpc", rpc_port);
+            let args = start_args(&["--rpc", &rpc_url, "--wait-for-rpc", "--devnet"]);
             start_node(args, cts).await.unwrap();
         }
     });